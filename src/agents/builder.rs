@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+use super::runner::AgentLoopConfig;
+
+/// Replacements for an agent's built-in system prompt, iteration limit and
+/// tool allow list
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AgentOverrides {
+    pub system_prompt: Option<String>,
+    pub max_iterations: Option<usize>,
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+impl AgentOverrides {
+    /// The overriding allow list, borrowed in the form `AgentLoopParams` takes
+    pub fn allowed_tools(&self) -> Option<Vec<&str>> {
+        self.allowed_tools
+            .as_ref()
+            .map(|names| names.iter().map(String::as_str).collect())
+    }
+}
+
+/// Builds a coder, planner, tester or reviewer with its system prompt,
+/// iteration limit or tool allow list replaced. Get one from the agent's
+/// `builder()`, e.g. [`CoderAgent::builder`](super::CoderAgent::builder);
+/// unset settings keep the agent's built-in behaviour.
+pub struct AgentBuilder<A> {
+    loop_config: AgentLoopConfig,
+    overrides: AgentOverrides,
+    make: fn(AgentLoopConfig, AgentOverrides) -> A,
+}
+
+impl<A> AgentBuilder<A> {
+    pub(crate) fn new(make: fn(AgentLoopConfig, AgentOverrides) -> A) -> Self {
+        Self {
+            loop_config: AgentLoopConfig::default(),
+            overrides: AgentOverrides::default(),
+            make,
+        }
+    }
+
+    /// Override the agent loop settings (e.g. the inter-request delay)
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.loop_config = config;
+        self
+    }
+
+    /// Replace the built-in system prompt. To extend it instead, start from
+    /// the agent's `new().system_prompt()`.
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.overrides.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Cap the LLM round-trips per run; must be at least 1
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.overrides.max_iterations = Some(max_iterations);
+        self
+    }
+
+    /// Restrict the agent to these tools (default: the agent's own set)
+    pub fn with_allowed_tools(mut self, tools: Vec<String>) -> Self {
+        self.overrides.allowed_tools = Some(tools);
+        self
+    }
+
+    /// Build the agent. Fails if the iteration limit is 0, which would
+    /// stop every run before its first LLM call.
+    pub fn build(self) -> Result<A> {
+        if self.overrides.max_iterations == Some(0) {
+            anyhow::bail!("max_iterations must be at least 1");
+        }
+        Ok((self.make)(self.loop_config, self.overrides))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agents::{Agent, CoderAgent, ReviewerAgent};
+
+    #[test]
+    fn zero_max_iterations_is_rejected() {
+        let err = CoderAgent::builder()
+            .with_max_iterations(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "max_iterations must be at least 1");
+
+        assert!(
+            ReviewerAgent::builder()
+                .with_max_iterations(1)
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn unset_settings_keep_the_built_in_prompt() {
+        let reviewer = ReviewerAgent::builder().build().unwrap();
+        assert_eq!(
+            reviewer.system_prompt(),
+            ReviewerAgent::new().system_prompt()
+        );
+    }
+}
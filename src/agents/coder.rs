@@ -1,20 +1,42 @@
 use anyhow::Result;
 use async_trait::async_trait;
 
-use super::Agent;
-use super::runner::agent_loop;
+use super::builder::{AgentBuilder, AgentOverrides};
+use super::runner::{AgentLoopConfig, AgentLoopParams, agent_loop};
+use super::{Agent, RunContext};
 use crate::llm::{LlmProvider, Message};
 use crate::tools::ToolRegistry;
 
 const MAX_ITERATIONS: usize = 20;
 
 /// A coding agent that can read and write files
-pub struct CoderAgent;
+pub struct CoderAgent {
+    loop_config: AgentLoopConfig,
+    overrides: AgentOverrides,
+}
 
 impl CoderAgent {
     /// Create a new coder agent
     pub fn new() -> Self {
-        Self
+        Self {
+            loop_config: AgentLoopConfig::default(),
+            overrides: AgentOverrides::default(),
+        }
+    }
+
+    /// Override the agent loop settings (e.g. the inter-request delay)
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.loop_config = config;
+        self
+    }
+
+    /// Start building a coder with its system prompt, iteration limit or
+    /// tools replaced
+    pub fn builder() -> AgentBuilder<Self> {
+        AgentBuilder::new(|loop_config, overrides| Self {
+            loop_config,
+            overrides,
+        })
     }
 }
 
@@ -27,15 +49,20 @@ impl Default for CoderAgent {
 #[async_trait]
 impl Agent for CoderAgent {
     fn system_prompt(&self) -> String {
+        if let Some(prompt) = &self.overrides.system_prompt {
+            return prompt.clone();
+        }
         r#"You are a coding agent that implements software changes.
 
 Available tools:
-- read_file: Read file contents
+- read_file: Read file contents (pass start_line/end_line to read part of a large file)
 - write_file: Create or overwrite a file (parent dirs created automatically)
-- edit_file: Find-and-replace in a file (old_string must be unique)
+- edit_file: Find-and-replace in a file (old_string must be unique, or set replace_all to replace every occurrence)
+- move_file: Move or rename a file (set overwrite to replace an existing destination)
 - shell: Run shell commands (builds, tests, git, etc.)
 - glob: Find files by pattern
 - grep: Search file contents by regex
+- ask_user: Ask the user a clarifying question (only when available and you are truly blocked)
 
 Workflow:
 1. Read relevant files to understand context before making changes
@@ -64,18 +91,34 @@ Output format when complete:
         task: &str,
         provider: &dyn LlmProvider,
         tools: &ToolRegistry,
+    ) -> Result<String> {
+        self.run_with_context(task, provider, tools, &RunContext::new())
+            .await
+    }
+
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
     ) -> Result<String> {
         let messages = vec![Message::user(task)];
 
-        agent_loop(
-            "coder",
-            &self.system_prompt(),
+        let allowed = self.overrides.allowed_tools();
+        agent_loop(AgentLoopParams {
+            agent_name: "coder",
+            system_prompt: &self.system_prompt(),
             messages,
             provider,
             tools,
-            None, // All tools available
-            MAX_ITERATIONS,
-        )
+            allowed_tools: allowed.as_deref(), // All tools unless overridden
+            read_only: false,
+            max_iterations: self.overrides.max_iterations.unwrap_or(MAX_ITERATIONS),
+            config: &self.loop_config,
+            session: ctx.session(),
+            cancellation: Some(ctx.cancellation()),
+        })
         .await
     }
 }
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::session::{SessionState, Storage};
+
+/// Shared handle to the session an agent is running under.
+///
+/// Every update is persisted immediately so progress survives a crash.
+#[derive(Clone)]
+pub struct SessionHandle {
+    storage: Arc<dyn Storage>,
+    state: Arc<Mutex<SessionState>>,
+}
+
+impl SessionHandle {
+    pub fn new(storage: Arc<dyn Storage>, state: SessionState) -> Self {
+        Self {
+            storage,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Apply a change to the session and save it
+    pub async fn update(&self, f: impl FnOnce(&mut SessionState) + Send) -> Result<()> {
+        let mut state = self.state.lock().await;
+        f(&mut state);
+        self.storage.save(&state).await
+    }
+
+    /// Read from the current session state without copying all of it
+    pub async fn read<T>(&self, f: impl FnOnce(&SessionState) -> T) -> T {
+        f(&*self.state.lock().await)
+    }
+
+    /// A copy of the current session state
+    pub async fn snapshot(&self) -> SessionState {
+        self.state.lock().await.clone()
+    }
+}
+
+/// A run stopped because its cancellation token fired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunCancelled;
+
+impl std::fmt::Display for RunCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "run was cancelled")
+    }
+}
+
+impl std::error::Error for RunCancelled {}
+
+/// Per-run context passed down to agents
+#[derive(Clone, Default)]
+pub struct RunContext {
+    session: Option<SessionHandle>,
+    cancellation: CancellationToken,
+    plan: Option<String>,
+}
+
+impl RunContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach the session this run is tracked under
+    pub fn with_session(mut self, session: SessionHandle) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Stop the run when `token` is cancelled. Agents finish the tool call
+    /// in progress, then fail with [`RunCancelled`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Use a plan computed ahead of time instead of running the planner
+    pub fn with_plan(mut self, plan: impl Into<String>) -> Self {
+        self.plan = Some(plan.into());
+        self
+    }
+
+    /// The pre-computed plan for this run, if one was supplied
+    pub fn plan(&self) -> Option<&str> {
+        self.plan.as_deref()
+    }
+
+    /// The token that cancels this run (never cancelled unless one was attached)
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// The session this run is tracked under, if any
+    pub fn session(&self) -> Option<&SessionHandle> {
+        self.session.as_ref()
+    }
+
+    /// Update and save the session, doing nothing for untracked runs
+    pub async fn update_session(&self, f: impl FnOnce(&mut SessionState) + Send) -> Result<()> {
+        match &self.session {
+            Some(session) => session.update(f).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -1,20 +1,30 @@
+mod builder;
 mod coder;
+mod context;
 mod orchestrator;
 mod planner;
 mod reviewer;
 mod runner;
+mod security_audit;
 mod tester;
 
+pub use builder::AgentBuilder;
 pub use coder::CoderAgent;
-pub use orchestrator::OrchestratorAgent;
-pub use planner::PlannerAgent;
+pub use context::{RunCancelled, RunContext, SessionHandle};
+pub use orchestrator::{
+    ApprovalDetector, OrchestratorAgent, ReviewOutcome, SkipCondition, detect_review_verdict,
+};
+pub use planner::{FileChange, Plan, PlannerAgent};
 pub use reviewer::ReviewerAgent;
+pub use runner::{AgentLoopConfig, BeforeLlmCall};
+pub use security_audit::SecurityAuditAgent;
 pub use tester::TesterAgent;
 
 use anyhow::Result;
 use async_trait::async_trait;
 
 use crate::llm::LlmProvider;
+use crate::session::Pipeline;
 use crate::tools::ToolRegistry;
 
 /// An agent that can perform tasks using LLM and tools
@@ -30,4 +40,31 @@ pub trait Agent: Send + Sync {
         provider: &dyn LlmProvider,
         tools: &ToolRegistry,
     ) -> Result<String>;
+
+    /// Run the agent with access to the per-run context (e.g. the session
+    /// being tracked). Agents that don't need it can rely on the default,
+    /// which ignores the context.
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        _ctx: &RunContext,
+    ) -> Result<String> {
+        self.run(task, provider, tools).await
+    }
+
+    /// Whether the agent uses a pre-computed plan from its [`RunContext`]
+    /// instead of planning itself. Runs that supply a plan to an agent that
+    /// doesn't are refused rather than silently dropping it.
+    fn accepts_plan(&self) -> bool {
+        false
+    }
+
+    /// The pipeline this agent implements, recorded on the sessions it runs
+    /// so a resume can tell when it switches pipelines. Single agents run
+    /// the simple pipeline.
+    fn pipeline(&self) -> Pipeline {
+        Pipeline::Simple
+    }
 }
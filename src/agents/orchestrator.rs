@@ -1,31 +1,169 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use tracing::{info, warn};
 
-use super::{Agent, CoderAgent, PlannerAgent, ReviewerAgent, TesterAgent};
-use crate::llm::LlmProvider;
+use super::runner::{AgentLoopParams, agent_loop};
+use super::security_audit::is_audit_clean;
+use super::{
+    Agent, AgentLoopConfig, CoderAgent, PlannerAgent, ReviewerAgent, RunCancelled, RunContext,
+    SecurityAuditAgent, TesterAgent,
+};
+use crate::llm::{LlmProvider, Message};
+use crate::session::{Pipeline, SessionPhase, SessionState};
 use crate::tools::ToolRegistry;
 
 const MAX_REVIEW_ITERATIONS: usize = 3;
 
-/// Check if the review output indicates approval.
-/// Looks for "VERDICT: APPROVED" on its own line, falling back to
-/// the presence of "approved" without "needs_work".
-fn is_review_approved(review: &str) -> bool {
-    // Strict check: look for "VERDICT: APPROVED" on its own line
+const SUMMARY_PROMPT: &str = r#"You summarize the report of an automated coding run for the developer who requested it.
+
+Write a concise summary with these sections:
+- What changed: the files and behavior that changed
+- What to review: anything risky, incomplete or worth a closer look
+- How to test: the commands or steps to verify the change
+
+Be brief. Do not repeat the full plan or test output."#;
+
+/// What a reviewer's output says about the implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewOutcome {
+    Approved,
+    NeedsWork,
+    /// No verdict could be found; treated as `NeedsWork`
+    Unknown,
+}
+
+/// Reads a [`ReviewOutcome`] out of the reviewer's output
+pub type ApprovalDetector = Box<dyn Fn(&str) -> ReviewOutcome + Send + Sync>;
+
+/// The default approval detector.
+/// Looks for "VERDICT: APPROVED" or "VERDICT: NEEDS_WORK" on its own line,
+/// falling back to the presence of "approved" without "needs_work".
+pub fn detect_review_verdict(review: &str) -> ReviewOutcome {
+    // Strict check: look for a verdict on its own line
     for line in review.lines() {
         let trimmed = line.trim().to_uppercase();
         if trimmed == "VERDICT: APPROVED" {
-            return true;
+            return ReviewOutcome::Approved;
         }
         if trimmed == "VERDICT: NEEDS_WORK" {
-            return false;
+            return ReviewOutcome::NeedsWork;
         }
     }
 
     // Fallback: looser check for backwards compatibility
     let lower = review.to_lowercase();
-    lower.contains("approved") && !lower.contains("needs_work")
+    if lower.contains("needs_work") {
+        ReviewOutcome::NeedsWork
+    } else if lower.contains("approved") {
+        ReviewOutcome::Approved
+    } else {
+        ReviewOutcome::Unknown
+    }
+}
+
+/// Move the tracked session to a phase and persist it, along with which
+/// review-driven fix iteration the pipeline is on
+async fn enter_phase(ctx: &RunContext, phase: SessionPhase, review_iteration: u32) -> Result<()> {
+    stop_if_cancelled(ctx, &phase)?;
+    info!(%phase, review_iteration, "phase changed");
+    ctx.update_session(|session| {
+        session.set_phase(phase);
+        session.set_review_iteration(review_iteration);
+    })
+    .await
+}
+
+/// Fail with [`RunCancelled`] before starting `step` if the run was
+/// cancelled, so work done outside an agent loop (configured test commands,
+/// the summary) doesn't start after a cancel
+fn stop_if_cancelled(ctx: &RunContext, step: &dyn std::fmt::Display) -> Result<()> {
+    if ctx.cancellation().is_cancelled() {
+        info!(%step, "run cancelled between pipeline steps");
+        return Err(RunCancelled.into());
+    }
+    Ok(())
+}
+
+/// Persist the current phase's output to the tracked session
+async fn record_output(ctx: &RunContext, output: &str) -> Result<()> {
+    ctx.update_session(|session| session.record_phase_output(output))
+        .await
+}
+
+/// Decides from the task and the plan whether a phase should be skipped
+pub type SkipCondition = Box<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// Phases a [`SkipCondition`] may skip: planning produces what conditions
+/// read, and review decides whether the run succeeded
+const SKIPPABLE_PHASES: &[SessionPhase] = &[
+    SessionPhase::Implementing,
+    SessionPhase::Testing,
+    SessionPhase::Auditing,
+];
+
+/// Record that `phase` was skipped, returning the text that stands in for
+/// its output in later prompts and the report
+async fn record_skipped(ctx: &RunContext, phase: SessionPhase, iteration: usize) -> Result<String> {
+    info!(%phase, "phase skipped by condition");
+    enter_phase(ctx, phase, iteration as u32).await?;
+    let note = format!("({} skipped by a pipeline condition)", phase.title());
+    record_output(ctx, &note).await?;
+    Ok(note)
+}
+
+/// Where a resumed run picks up: the outputs of the phases the session had
+/// already finished, and the review iteration it was on
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ResumePoint {
+    plan: Option<String>,
+    implementation: Option<String>,
+    test_results: Option<String>,
+    review_iteration: usize,
+}
+
+impl ResumePoint {
+    /// Rebuild the progress recorded in `session`. A phase is only skipped
+    /// if every phase before it has a recorded output too; otherwise the
+    /// run restarts from the first gap.
+    fn from_session(session: &SessionState) -> Self {
+        let (mut phase, mut iteration) = (session.phase, session.review_iteration);
+        if phase == SessionPhase::Completed {
+            return Self::default();
+        }
+        // A fix that was cut short is redone from the review that asked for it
+        if phase == SessionPhase::Implementing && iteration > 0 {
+            phase = SessionPhase::Reviewing;
+            iteration -= 1;
+        }
+
+        let latest = |done: SessionPhase| {
+            (phase > done)
+                .then(|| {
+                    session
+                        .phase_outputs
+                        .iter()
+                        .rev()
+                        .find(|o| o.phase == done)
+                        .map(|o| o.output.clone())
+                })
+                .flatten()
+        };
+        let plan = latest(SessionPhase::Planning);
+        let implementation = plan.as_ref().and(latest(SessionPhase::Implementing));
+        let test_results = implementation.as_ref().and(latest(SessionPhase::Testing));
+        let review_iteration = if implementation.is_some() {
+            iteration as usize
+        } else {
+            0
+        };
+
+        Self {
+            plan,
+            implementation,
+            test_results,
+            review_iteration,
+        }
+    }
 }
 
 /// Orchestrator agent that coordinates multiple specialized agents
@@ -34,6 +172,17 @@ pub struct OrchestratorAgent {
     coder: CoderAgent,
     tester: TesterAgent,
     reviewer: ReviewerAgent,
+    auditor: SecurityAuditAgent,
+    /// Audit each implementation for vulnerabilities before review
+    security_audit: bool,
+    /// Phases to skip when their condition holds
+    skip_conditions: Vec<(SessionPhase, SkipCondition)>,
+    /// Decides whether a review approves the implementation
+    approval_detector: ApprovalDetector,
+    /// Finish with a concise LLM-written summary of the run
+    summarize: bool,
+    /// Loop settings for the summary step
+    loop_config: AgentLoopConfig,
 }
 
 impl OrchestratorAgent {
@@ -43,133 +192,302 @@ impl OrchestratorAgent {
             coder: CoderAgent::new(),
             tester: TesterAgent::new(),
             reviewer: ReviewerAgent::new(),
+            auditor: SecurityAuditAgent::new(),
+            security_audit: false,
+            skip_conditions: Vec::new(),
+            approval_detector: Box::new(detect_review_verdict),
+            summarize: false,
+            loop_config: AgentLoopConfig::default(),
         }
     }
 
-    /// Run tests and return the results
-    async fn run_tests(
-        &self,
-        task: &str,
-        implementation: &str,
-        provider: &dyn LlmProvider,
-        tools: &ToolRegistry,
-    ) -> Result<String> {
-        let tester_task = format!(
-            "Test the implementation of this task:\n\n\
-            ## Original Task\n{}\n\n\
-            ## Implementation Summary\n{}",
-            task, implementation
+    /// Run a read-only security audit after testing. Findings send the
+    /// implementation back to the coder before it reaches review; fixes
+    /// count toward the same iteration limit as review feedback.
+    pub fn with_security_audit(mut self) -> Self {
+        self.security_audit = true;
+        self
+    }
+
+    /// Finish with a summary step: the output starts with a concise summary
+    /// (what changed, what to review, how to test), followed by the detailed
+    /// per-phase report after a `---` separator
+    pub fn with_summary(mut self) -> Self {
+        self.summarize = true;
+        self
+    }
+
+    /// Decide review approval with `detector` instead of looking for
+    /// `VERDICT: APPROVED`, e.g. for a reviewer prompted to answer `LGTM`.
+    /// An `Unknown` outcome is logged and treated as `NeedsWork`.
+    pub fn with_approval_detector(
+        mut self,
+        detector: impl Fn(&str) -> ReviewOutcome + Send + Sync + 'static,
+    ) -> Self {
+        self.approval_detector = Box::new(detector);
+        self
+    }
+
+    /// Skip `phase` whenever `condition(task, plan)` returns true, e.g. the
+    /// testing phase for documentation-only tasks. The condition is checked
+    /// once the plan exists; a skipped phase's output reads
+    /// "(<Phase> skipped by a pipeline condition)".
+    ///
+    /// # Panics
+    ///
+    /// If `phase` is not `Implementing`, `Testing` or `Auditing`.
+    pub fn skip_if(
+        mut self,
+        phase: SessionPhase,
+        condition: impl Fn(&str, &str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        assert!(
+            SKIPPABLE_PHASES.contains(&phase),
+            "the {} phase can't be skipped",
+            phase
         );
+        self.skip_conditions.push((phase, Box::new(condition)));
+        self
+    }
 
-        let test_results = self.tester.run(&tester_task, provider, tools).await?;
-        info!("tester completed");
-        Ok(test_results)
+    /// Whether any condition registered for `phase` holds
+    fn skips(&self, phase: SessionPhase, task: &str, plan: &str) -> bool {
+        self.skip_conditions
+            .iter()
+            .any(|(skipped, condition)| *skipped == phase && condition(task, plan))
     }
-}
 
-impl Default for OrchestratorAgent {
-    fn default() -> Self {
-        Self::new()
+    /// Have the tester run these commands concurrently before analyzing results
+    pub fn with_test_commands(mut self, commands: Vec<String>, concurrency: Option<usize>) -> Self {
+        self.tester = self.tester.with_test_commands(commands, concurrency);
+        self
     }
-}
 
-#[async_trait]
-impl Agent for OrchestratorAgent {
-    fn system_prompt(&self) -> String {
-        // Orchestrator doesn't use LLM directly, it coordinates other agents
-        String::new()
+    /// Override the agent loop settings for every sub-agent
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.planner = self.planner.with_loop_config(config.clone());
+        self.coder = self.coder.with_loop_config(config.clone());
+        self.tester = self.tester.with_loop_config(config.clone());
+        self.auditor = self.auditor.with_loop_config(config.clone());
+        self.reviewer = self.reviewer.with_loop_config(config.clone());
+        self.loop_config = config;
+        self
     }
 
-    async fn run(
+    /// Run plan -> implement -> test -> (audit) -> review and return the
+    /// detailed report.
+    ///
+    /// A resumed session skips the phases it already finished, reusing
+    /// their recorded outputs.
+    async fn run_pipeline(
         &self,
         task: &str,
         provider: &dyn LlmProvider,
         tools: &ToolRegistry,
+        ctx: &RunContext,
     ) -> Result<String> {
         info!(task, "orchestrator starting");
 
+        let resume = match ctx.session() {
+            Some(session) => ResumePoint::from_session(&session.snapshot().await),
+            None => ResumePoint::default(),
+        };
+        let start_iteration = resume.review_iteration;
+
         // Phase 1: Planning
-        info!("=== PHASE 1: PLANNING ===");
+        let plan = match resume.plan {
+            Some(plan) => {
+                info!("resuming with the recorded plan");
+                plan
+            }
+            None => {
+                info!("=== PHASE 1: PLANNING ===");
+                enter_phase(ctx, SessionPhase::Planning, 0).await?;
+
+                let plan = match ctx.plan() {
+                    Some(plan) => {
+                        info!("using the pre-computed plan");
+                        plan.to_string()
+                    }
+                    None => {
+                        self.planner
+                            .run_with_context(task, provider, tools, ctx)
+                            .await?
+                    }
+                };
+                info!(plan_length = plan.len(), "planner completed");
+                record_output(ctx, &plan).await?;
+                plan
+            }
+        };
 
-        let plan = self.planner.run(task, provider, tools).await?;
-        info!(plan_length = plan.len(), "planner completed");
+        let skip_implementing = self.skips(SessionPhase::Implementing, task, &plan);
+        let skip_testing = self.skips(SessionPhase::Testing, task, &plan);
+        let audit_enabled = self.security_audit && !self.skips(SessionPhase::Auditing, task, &plan);
 
         // Phase 2: Implementation
-        info!("=== PHASE 2: IMPLEMENTATION ===");
+        let mut implementation = match resume.implementation {
+            Some(implementation) => {
+                info!("resuming with the recorded implementation");
+                implementation
+            }
+            None if skip_implementing => record_skipped(ctx, SessionPhase::Implementing, 0).await?,
+            None => {
+                info!("=== PHASE 2: IMPLEMENTATION ===");
+                enter_phase(ctx, SessionPhase::Implementing, 0).await?;
 
-        let coder_task = format!(
-            "Implement the following task according to this plan:\n\n\
-            ## Original Task\n{}\n\n\
-            ## Implementation Plan\n{}",
-            task, plan
-        );
+                let coder_task = format!(
+                    "Implement the following task according to this plan:\n\n\
+                    ## Original Task\n{}\n\n\
+                    ## Implementation Plan\n{}",
+                    task, plan
+                );
 
-        let mut implementation = self.coder.run(&coder_task, provider, tools).await?;
-        info!(impl_length = implementation.len(), "coder completed");
+                let implementation = self
+                    .coder
+                    .run_with_context(&coder_task, provider, tools, ctx)
+                    .await?;
+                info!(impl_length = implementation.len(), "coder completed");
+                record_output(ctx, &implementation).await?;
+                implementation
+            }
+        };
 
         // Phase 3: Testing
-        info!("=== PHASE 3: TESTING ===");
+        let mut test_results = match resume.test_results {
+            Some(test_results) => {
+                info!("resuming with the recorded test results");
+                test_results
+            }
+            None if skip_testing => {
+                record_skipped(ctx, SessionPhase::Testing, start_iteration).await?
+            }
+            None => {
+                info!("=== PHASE 3: TESTING ===");
+                enter_phase(ctx, SessionPhase::Testing, start_iteration as u32).await?;
 
-        let mut test_results = self
-            .run_tests(task, &implementation, provider, tools)
-            .await?;
+                self.run_tests(task, &implementation, provider, tools, ctx)
+                    .await?
+            }
+        };
 
         // Phase 4: Review (with retry loop)
         info!("=== PHASE 4: REVIEW ===");
 
-        for review_iteration in 0..MAX_REVIEW_ITERATIONS {
+        for review_iteration in start_iteration..MAX_REVIEW_ITERATIONS {
             info!(iteration = review_iteration, "review iteration");
 
-            let reviewer_task = format!(
-                "Review the implementation of this task:\n\n\
-                ## Original Task\n{}\n\n\
-                ## Implementation Summary\n{}\n\n\
-                ## Test Results\n{}",
-                task, implementation, test_results
-            );
+            // The audit gates review: findings go straight back to the coder
+            let audit = if audit_enabled {
+                Some(
+                    self.run_security_audit(
+                        task,
+                        &implementation,
+                        review_iteration,
+                        provider,
+                        tools,
+                        ctx,
+                    )
+                    .await?,
+                )
+            } else {
+                None
+            };
 
-            let review = self.reviewer.run(&reviewer_task, provider, tools).await?;
-            info!("reviewer completed");
+            let (source, feedback_heading, feedback) =
+                match audit.as_deref().filter(|audit| !is_audit_clean(audit)) {
+                    Some(findings) => {
+                        warn!("security audit found issues");
+                        (
+                            "security audit",
+                            "Security Audit Findings",
+                            findings.to_string(),
+                        )
+                    }
+                    None => {
+                        enter_phase(ctx, SessionPhase::Reviewing, review_iteration as u32).await?;
 
-            // Check if approved — look for "VERDICT: APPROVED" on its own line
-            if is_review_approved(&review) {
-                info!("task APPROVED");
+                        let reviewer_task = format!(
+                            "Review the implementation of this task:\n\n\
+                            ## Original Task\n{}\n\n\
+                            ## Implementation Summary\n{}\n\n\
+                            ## Test Results\n{}",
+                            task, implementation, test_results
+                        );
 
-                return Ok(format!(
-                    "# Task Completed\n\n\
-                    ## Original Task\n{}\n\n\
-                    ## Plan\n{}\n\n\
-                    ## Implementation\n{}\n\n\
-                    ## Test Results\n{}\n\n\
-                    ## Review\n{}\n\n\
-                    ---\nStatus: SUCCESS",
-                    task, plan, implementation, test_results, review
-                ));
-            }
+                        let review = self
+                            .reviewer
+                            .run_with_context(&reviewer_task, provider, tools, ctx)
+                            .await?;
+                        info!("reviewer completed");
+                        record_output(ctx, &review).await?;
+
+                        let outcome = (self.approval_detector)(&review);
+                        if outcome == ReviewOutcome::Unknown {
+                            warn!("no verdict found in the review, treating it as needs work");
+                        }
+                        if outcome == ReviewOutcome::Approved {
+                            info!("task APPROVED");
+
+                            let audit_section = audit
+                                .map(|audit| format!("## Security Audit\n{}\n\n", audit))
+                                .unwrap_or_default();
+                            return Ok(format!(
+                                "# Task Completed\n\n\
+                                ## Original Task\n{}\n\n\
+                                ## Plan\n{}\n\n\
+                                ## Implementation\n{}\n\n\
+                                ## Test Results\n{}\n\n\
+                                {}\
+                                ## Review\n{}\n\n\
+                                ---\nStatus: SUCCESS",
+                                task, plan, implementation, test_results, audit_section, review
+                            ));
+                        }
+                        ("code review", "Review Feedback", review)
+                    }
+                };
 
             // Needs work - try to fix
             if review_iteration < MAX_REVIEW_ITERATIONS - 1 {
-                warn!("review requested changes, attempting fixes");
+                warn!(source, "changes requested, attempting fixes");
 
                 let fix_task = format!(
-                    "Fix the following issues identified in code review:\n\n\
+                    "Fix the following issues identified in {}:\n\n\
                     ## Original Task\n{}\n\n\
                     ## Implementation Plan\n{}\n\n\
                     ## Previous Implementation\n{}\n\n\
                     ## Test Results\n{}\n\n\
-                    ## Review Feedback\n{}\n\n\
-                    Please address all issues mentioned in the review.",
-                    task, plan, implementation, test_results, review
+                    ## {}\n{}\n\n\
+                    Please address all issues mentioned in the {}.",
+                    source,
+                    task,
+                    plan,
+                    implementation,
+                    test_results,
+                    feedback_heading,
+                    feedback,
+                    source
                 );
 
                 // Apply fixes
-                implementation = self.coder.run(&fix_task, provider, tools).await?;
+                let fix_iteration = review_iteration as u32 + 1;
+                enter_phase(ctx, SessionPhase::Implementing, fix_iteration).await?;
+                implementation = self
+                    .coder
+                    .run_with_context(&fix_task, provider, tools, ctx)
+                    .await?;
+                record_output(ctx, &implementation).await?;
 
                 // Re-run tests after fixes
-                info!("re-running tests after fixes");
-                test_results = self
-                    .run_tests(task, &implementation, provider, tools)
-                    .await?;
+                test_results = if skip_testing {
+                    record_skipped(ctx, SessionPhase::Testing, fix_iteration as usize).await?
+                } else {
+                    info!("re-running tests after fixes");
+                    enter_phase(ctx, SessionPhase::Testing, fix_iteration).await?;
+                    self.run_tests(task, &implementation, provider, tools, ctx)
+                        .await?
+                };
             }
         }
 
@@ -185,4 +503,709 @@ impl Agent for OrchestratorAgent {
             task, MAX_REVIEW_ITERATIONS
         ))
     }
+
+    /// Audit the implementation and return the auditor's report
+    async fn run_security_audit(
+        &self,
+        task: &str,
+        implementation: &str,
+        review_iteration: usize,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
+    ) -> Result<String> {
+        info!("=== SECURITY AUDIT ===");
+        enter_phase(ctx, SessionPhase::Auditing, review_iteration as u32).await?;
+
+        let audit_task = format!(
+            "Audit the implementation of this task:\n\n\
+            ## Original Task\n{}\n\n\
+            ## Implementation Summary\n{}",
+            task, implementation
+        );
+        let audit = self
+            .auditor
+            .run_with_context(&audit_task, provider, tools, ctx)
+            .await?;
+        info!(clean = is_audit_clean(&audit), "security audit completed");
+        record_output(ctx, &audit).await?;
+        Ok(audit)
+    }
+
+    /// Run tests and return the results
+    async fn run_tests(
+        &self,
+        task: &str,
+        implementation: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
+    ) -> Result<String> {
+        let tester_task = format!(
+            "Test the implementation of this task:\n\n\
+            ## Original Task\n{}\n\n\
+            ## Implementation Summary\n{}",
+            task, implementation
+        );
+
+        let test_results = self
+            .tester
+            .run_with_context(&tester_task, provider, tools, ctx)
+            .await?;
+        info!("tester completed");
+        record_output(ctx, &test_results).await?;
+        Ok(test_results)
+    }
+}
+
+impl Default for OrchestratorAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Agent for OrchestratorAgent {
+    fn system_prompt(&self) -> String {
+        // Orchestrator doesn't use LLM directly, it coordinates other agents
+        String::new()
+    }
+
+    fn accepts_plan(&self) -> bool {
+        true
+    }
+
+    fn pipeline(&self) -> Pipeline {
+        Pipeline::Orchestrated
+    }
+
+    async fn run(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+    ) -> Result<String> {
+        self.run_with_context(task, provider, tools, &RunContext::new())
+            .await
+    }
+
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
+    ) -> Result<String> {
+        let report = self.run_pipeline(task, provider, tools, ctx).await?;
+
+        if !self.summarize {
+            return Ok(report);
+        }
+
+        stop_if_cancelled(ctx, &"summary")?;
+        info!("=== SUMMARY ===");
+        // A tool-less agent loop, so the call can be cancelled and its token
+        // usage is counted on the session like every other round-trip
+        let summary = agent_loop(AgentLoopParams {
+            agent_name: "summarizer",
+            system_prompt: SUMMARY_PROMPT,
+            messages: vec![Message::user(&report)],
+            provider,
+            tools,
+            allowed_tools: Some(&[]),
+            read_only: true,
+            max_iterations: 1,
+            config: &self.loop_config,
+            session: ctx.session(),
+            cancellation: Some(ctx.cancellation()),
+        })
+        .await
+        .context("summary step failed")?;
+        info!(summary_length = summary.len(), "summary completed");
+
+        Ok(format!("{}\n\n---\n\n{}", summary, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::SessionHandle;
+    use crate::config::Policy;
+    use crate::llm::{LlmResponse, TokenUsage};
+    use crate::session::{MemoryStorage, SessionState, Storage};
+    use crate::testing::{
+        MockLlmProvider, StubTool, stub_registry, text_response, tool_call_response,
+    };
+    use crate::tools::Tool;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio::sync::Notify;
+
+    #[tokio::test]
+    async fn success_output_sections_follow_pipeline_order() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("PLAN-OUTPUT"),
+            text_response("IMPL-OUTPUT"),
+            text_response("TEST-OUTPUT"),
+            text_response("REVIEW-OUTPUT\nVERDICT: APPROVED"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .run(
+                "do the thing",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        let positions: Vec<usize> = [
+            "## Plan\nPLAN-OUTPUT",
+            "## Implementation\nIMPL-OUTPUT",
+            "## Test Results\nTEST-OUTPUT",
+            "## Review\nREVIEW-OUTPUT",
+        ]
+        .iter()
+        .map(|section| output.find(section).expect("section missing"))
+        .collect();
+
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn needs_work_cycle_records_review_iteration() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("impl"),
+            text_response("tests pass"),
+            text_response("missing edge case\nVERDICT: NEEDS_WORK"),
+            text_response("fixed impl"),
+            text_response("tests pass"),
+            text_response("looks good\nVERDICT: APPROVED"),
+        ]);
+
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let session = SessionState::new("do the thing", ".");
+        let session_id = session.id.clone();
+        let ctx = RunContext::new().with_session(SessionHandle::new(storage.clone(), session));
+
+        OrchestratorAgent::new()
+            .run_with_context(
+                "do the thing",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        let saved = storage.load(&session_id).await.unwrap().unwrap();
+        assert_eq!(saved.review_iteration, 1);
+        assert_eq!(saved.phase, SessionPhase::Reviewing);
+    }
+
+    #[tokio::test]
+    async fn summary_runs_last_and_leads_the_output() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("PLAN-OUTPUT"),
+            text_response("IMPL-OUTPUT"),
+            text_response("TEST-OUTPUT"),
+            text_response("REVIEW-OUTPUT\nVERDICT: APPROVED"),
+            text_response("SUMMARY-OUTPUT"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .with_summary()
+            .run(
+                "do the thing",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        // The summarizer sees the full report from every earlier phase
+        let summary_request = provider.requests().pop().unwrap();
+        assert!(
+            summary_request[0]
+                .content
+                .contains("## Review\nREVIEW-OUTPUT")
+        );
+
+        assert!(output.starts_with("SUMMARY-OUTPUT\n\n---\n\n# Task Completed"));
+        assert!(output.contains("## Plan\nPLAN-OUTPUT"));
+    }
+
+    #[tokio::test]
+    async fn summary_usage_is_counted_on_the_session() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("impl"),
+            text_response("tests pass"),
+            text_response("looks good\nVERDICT: APPROVED"),
+            LlmResponse {
+                usage: Some(TokenUsage {
+                    input_tokens: 900,
+                    output_tokens: 40,
+                    cache_read_tokens: None,
+                }),
+                ..text_response("summary")
+            },
+        ]);
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let session = SessionState::new("do the thing", ".");
+        let session_id = session.id.clone();
+        let ctx = RunContext::new().with_session(SessionHandle::new(storage.clone(), session));
+
+        OrchestratorAgent::new()
+            .with_summary()
+            .run_with_context(
+                "do the thing",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        let saved = storage.load(&session_id).await.unwrap().unwrap();
+        assert_eq!(saved.total_iterations, 5);
+        assert_eq!(saved.total_usage.unwrap().input_tokens, 900);
+    }
+
+    /// Provider whose summary call never returns
+    struct StalledSummary {
+        phases: MockLlmProvider,
+        summary_started: Notify,
+    }
+
+    #[async_trait]
+    impl LlmProvider for StalledSummary {
+        async fn chat(
+            &self,
+            system_prompt: &str,
+            messages: &[Message],
+            tools: &[&dyn Tool],
+        ) -> Result<LlmResponse> {
+            if system_prompt == SUMMARY_PROMPT {
+                self.summary_started.notify_one();
+                std::future::pending::<()>().await;
+            }
+            self.phases.chat(system_prompt, messages, tools).await
+        }
+
+        fn name(&self) -> &str {
+            "stalled-summary"
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_during_the_summary_stops_the_run() {
+        let provider = StalledSummary {
+            phases: MockLlmProvider::with_responses(vec![
+                text_response("plan"),
+                text_response("impl"),
+                text_response("tests pass"),
+                text_response("looks good\nVERDICT: APPROVED"),
+            ]),
+            summary_started: Notify::new(),
+        };
+        let ctx = RunContext::new();
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+        let agent = OrchestratorAgent::new().with_summary();
+
+        let (result, ()) = tokio::join!(
+            agent.run_with_context("do the thing", &provider, &tools, &ctx),
+            async {
+                provider.summary_started.notified().await;
+                ctx.cancellation().cancel();
+            }
+        );
+        assert!(result.unwrap_err().is::<RunCancelled>());
+    }
+
+    #[tokio::test]
+    async fn full_cycle_runs_offline_with_stubbed_tools() {
+        let read_file =
+            StubTool::new("read_file").with_output("fn add(a: i32, b: i32) -> i32 { a - b }");
+        let edit_file = StubTool::new("edit_file")
+            .with_output("edited src/lib.rs")
+            .with_output("edited src/lib.rs");
+        let shell = StubTool::new("shell")
+            .with_output("test result: FAILED. 0 passed; 1 failed")
+            .with_output("test result: ok. 1 passed; 0 failed");
+        let diff = StubTool::new("diff").with_output("-a - b\n+a + b");
+        let tools = stub_registry([
+            read_file.clone(),
+            edit_file.clone(),
+            shell.clone(),
+            diff.clone(),
+        ]);
+
+        let provider = MockLlmProvider::with_responses(vec![
+            // Plan
+            tool_call_response("1", "read_file", json!({ "path": "src/lib.rs" })),
+            text_response("1. fix add"),
+            // Code
+            tool_call_response("2", "edit_file", json!({ "path": "src/lib.rs" })),
+            text_response("changed the operator"),
+            // Test
+            tool_call_response("3", "shell", json!({ "command": "cargo test" })),
+            text_response("1 test failed"),
+            // Review: needs work
+            text_response("tests fail\nVERDICT: NEEDS_WORK"),
+            // Fix and re-test
+            tool_call_response("4", "edit_file", json!({ "path": "src/lib.rs" })),
+            text_response("fixed it"),
+            tool_call_response("5", "shell", json!({ "command": "cargo test" })),
+            text_response("all tests pass"),
+            // Review: approved
+            tool_call_response("6", "diff", json!({ "path": "src/lib.rs" })),
+            text_response("correct now\nVERDICT: APPROVED"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .with_loop_config(AgentLoopConfig {
+                inter_request_delay: std::time::Duration::ZERO,
+                ..Default::default()
+            })
+            .run("fix add", &provider, &tools)
+            .await
+            .unwrap();
+
+        assert!(output.contains("Status: SUCCESS"));
+        assert_eq!(read_file.calls().len(), 1);
+        assert_eq!(edit_file.calls().len(), 2);
+        assert_eq!(shell.calls()[1]["command"], "cargo test");
+        assert_eq!(diff.calls().len(), 1);
+
+        // The scripted tool output is what the model saw
+        let requests = provider.requests();
+        let seen = requests[1].last().unwrap().tool_result.as_ref().unwrap();
+        assert!(seen.result.contains("a - b"));
+    }
+
+    /// A session that finished `phases` (recording "<PHASE>-OUTPUT" for
+    /// each) and is now in `phase`
+    fn session_in(phase: SessionPhase, phases: &[SessionPhase], iteration: u32) -> SessionState {
+        let mut session = SessionState::new("do the thing", ".");
+        for done in phases {
+            session.set_phase(*done);
+            session.record_phase_output(format!("{}-OUTPUT", done));
+        }
+        session.set_phase(phase);
+        session.set_review_iteration(iteration);
+        session
+    }
+
+    #[tokio::test]
+    async fn resume_mid_testing_skips_planning_and_implementation() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("TEST-OUTPUT"),
+            text_response("REVIEW-OUTPUT\nVERDICT: APPROVED"),
+        ]);
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let session = session_in(
+            SessionPhase::Testing,
+            &[SessionPhase::Planning, SessionPhase::Implementing],
+            0,
+        );
+        let ctx = RunContext::new().with_session(SessionHandle::new(storage, session));
+
+        let output = OrchestratorAgent::new()
+            .run_with_context(
+                "do the thing",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        // The first LLM call is the tester's, working on the recorded implementation
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0][0].content.starts_with("Test and validate"));
+        assert!(requests[0][0].content.contains("implementing-OUTPUT"));
+        assert!(output.contains("## Plan\nplanning-OUTPUT"));
+        assert!(output.contains("## Test Results\nTEST-OUTPUT"));
+    }
+
+    #[test]
+    fn resume_point_restarts_from_the_first_missing_output() {
+        // Mid-review, but the implementation was never recorded
+        let session = session_in(SessionPhase::Reviewing, &[SessionPhase::Planning], 1);
+        let resume = ResumePoint::from_session(&session);
+
+        assert_eq!(resume.plan.as_deref(), Some("planning-OUTPUT"));
+        assert_eq!(resume.implementation, None);
+        assert_eq!(resume.test_results, None);
+        assert_eq!(resume.review_iteration, 0);
+    }
+
+    #[test]
+    fn resume_point_redoes_an_interrupted_fix_from_its_review() {
+        let all = [
+            SessionPhase::Planning,
+            SessionPhase::Implementing,
+            SessionPhase::Testing,
+            SessionPhase::Reviewing,
+        ];
+
+        let resume = ResumePoint::from_session(&session_in(SessionPhase::Implementing, &all, 2));
+        assert_eq!(resume.review_iteration, 1);
+        assert_eq!(resume.test_results.as_deref(), Some("testing-OUTPUT"));
+
+        // A fix that was implemented but not yet tested goes straight to testing
+        let resume = ResumePoint::from_session(&session_in(SessionPhase::Testing, &all, 2));
+        assert_eq!(resume.review_iteration, 2);
+        assert!(resume.implementation.is_some());
+        assert_eq!(resume.test_results, None);
+
+        // A fresh session starts from scratch
+        let fresh = SessionState::new("do the thing", ".");
+        assert_eq!(ResumePoint::from_session(&fresh), ResumePoint::default());
+    }
+
+    #[tokio::test]
+    async fn security_audit_findings_go_back_to_the_coder_before_review() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("impl"),
+            text_response("tests pass"),
+            text_response("API key hardcoded in src/client.rs:4\nVERDICT: ISSUES_FOUND"),
+            text_response("moved key to env var"),
+            text_response("tests pass"),
+            text_response("None\nVERDICT: CLEAN"),
+            text_response("looks good\nVERDICT: APPROVED"),
+        ]);
+
+        let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let session = SessionState::new("add an API client", ".");
+        let session_id = session.id.clone();
+        let ctx = RunContext::new().with_session(SessionHandle::new(storage.clone(), session));
+
+        let output = OrchestratorAgent::new()
+            .with_security_audit()
+            .run_with_context(
+                "add an API client",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        // The fix request carries the audit findings, not a review
+        let requests = provider.requests();
+        let fix_prompt = &requests[4][0].content;
+        assert!(
+            fix_prompt.contains("identified in security audit"),
+            "{fix_prompt}"
+        );
+        assert!(fix_prompt.contains("## Security Audit Findings\nAPI key hardcoded"));
+        assert!(output.contains("## Security Audit\nNone\nVERDICT: CLEAN\n\n## Review\n"));
+
+        let saved = storage.load(&session_id).await.unwrap().unwrap();
+        let phases: Vec<SessionPhase> = saved.phase_outputs.iter().map(|o| o.phase).collect();
+        assert_eq!(
+            phases,
+            [
+                SessionPhase::Planning,
+                SessionPhase::Implementing,
+                SessionPhase::Testing,
+                SessionPhase::Auditing,
+                SessionPhase::Implementing,
+                SessionPhase::Testing,
+                SessionPhase::Auditing,
+                SessionPhase::Reviewing,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn condition_on_the_plan_skips_the_coder() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("Already implemented; nothing to change."),
+            text_response("tests pass"),
+            text_response("nothing needed\nVERDICT: APPROVED"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .skip_if(SessionPhase::Implementing, |_task, plan| {
+                plan.contains("nothing to change")
+            })
+            .run(
+                "add a greeting",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        // Planner, tester and reviewer ran; the coder never did
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1][0].content.starts_with("Test and validate"));
+        assert!(
+            output.contains("## Implementation\n(Implementing skipped by a pipeline condition)")
+        );
+    }
+
+    #[tokio::test]
+    async fn condition_on_the_task_skips_testing_in_every_iteration() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("updated README"),
+            text_response("typo in README\nVERDICT: NEEDS_WORK"),
+            text_response("fixed typo"),
+            text_response("VERDICT: APPROVED"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .skip_if(SessionPhase::Testing, |task, _plan| {
+                task.starts_with("docs:")
+            })
+            .run(
+                "docs: explain the config file",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(provider.requests().len(), 5);
+        assert!(output.contains("## Test Results\n(Testing skipped by a pipeline condition)"));
+    }
+
+    #[test]
+    #[should_panic(expected = "the reviewing phase can't be skipped")]
+    fn review_cannot_be_skipped() {
+        let _ = OrchestratorAgent::new().skip_if(SessionPhase::Reviewing, |_, _| true);
+    }
+
+    #[test]
+    fn default_detector_reads_the_verdict_line() {
+        let cases = [
+            ("looks good\nVERDICT: APPROVED", ReviewOutcome::Approved),
+            ("  verdict: needs_work  \n", ReviewOutcome::NeedsWork),
+            // The verdict line wins over words elsewhere in the review
+            (
+                "not approved yet\nVERDICT: NEEDS_WORK",
+                ReviewOutcome::NeedsWork,
+            ),
+            ("Approved, nice work", ReviewOutcome::Approved),
+            (
+                "approved parts, but needs_work on errors",
+                ReviewOutcome::NeedsWork,
+            ),
+            ("LGTM", ReviewOutcome::Unknown),
+        ];
+        for (review, expected) in cases {
+            assert_eq!(detect_review_verdict(review), expected, "{review:?}");
+        }
+    }
+
+    fn lgtm_detector(review: &str) -> ReviewOutcome {
+        match review.lines().last().map(str::trim) {
+            Some("LGTM") => ReviewOutcome::Approved,
+            Some("CHANGES REQUESTED") => ReviewOutcome::NeedsWork,
+            _ => ReviewOutcome::Unknown,
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_detector_recognizes_lgtm() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("implemented"),
+            text_response("tests pass"),
+            text_response("error handling is missing\nCHANGES REQUESTED"),
+            text_response("added error handling"),
+            text_response("tests pass"),
+            text_response("LGTM"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .with_approval_detector(lgtm_detector)
+            .run(
+                "add a greeting",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(provider.requests().len(), 7);
+        assert!(output.contains("## Review\nLGTM"));
+        assert!(output.ends_with("Status: SUCCESS"));
+    }
+
+    #[tokio::test]
+    async fn unknown_outcome_is_treated_as_needs_work() {
+        // "VERDICT: APPROVED" means nothing to the custom detector
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("implemented"),
+            text_response("tests pass"),
+            text_response("VERDICT: APPROVED"),
+            text_response("implemented again"),
+            text_response("tests pass"),
+            text_response("LGTM"),
+        ]);
+
+        let output = OrchestratorAgent::new()
+            .with_approval_detector(lgtm_detector)
+            .run(
+                "add a greeting",
+                &provider,
+                &ToolRegistry::with_default_tools(&Policy::default()),
+            )
+            .await
+            .unwrap();
+
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 7);
+        assert!(
+            requests[4][0]
+                .content
+                .starts_with("Fix the following issues")
+        );
+        assert!(output.ends_with("Status: SUCCESS"));
+    }
+
+    #[tokio::test]
+    async fn cancelling_between_phases_skips_configured_test_commands() {
+        let token = tokio_util::sync::CancellationToken::new();
+        let cancel = token.clone();
+        // Cancel while the coder is answering, so the cancel lands between
+        // the implementation and testing phases
+        let provider = MockLlmProvider::from_script(move |message| {
+            if message.starts_with("Implement") {
+                cancel.cancel();
+                text_response("implemented")
+            } else {
+                text_response("plan")
+            }
+        });
+        let shell = StubTool::new("shell").with_output("test result: ok");
+        let tools = stub_registry([shell.clone(), StubTool::new("read_file")]);
+
+        let err = OrchestratorAgent::new()
+            .with_test_commands(vec!["cargo test".to_string()], None)
+            .run_with_context(
+                "add a greeting",
+                &provider,
+                &tools,
+                &RunContext::new().with_cancellation(token),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<RunCancelled>());
+        assert!(shell.calls().is_empty());
+        assert_eq!(provider.requests().len(), 2);
+    }
 }
@@ -1,19 +1,122 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::fmt::Write;
+use tracing::{debug, warn};
 
-use super::Agent;
-use super::runner::agent_loop;
+use super::builder::{AgentBuilder, AgentOverrides};
+use super::runner::{AgentLoopConfig, AgentLoopParams, agent_loop};
+use super::{Agent, RunContext};
 use crate::llm::{LlmProvider, Message};
 use crate::tools::ToolRegistry;
 
+const ALLOWED_TOOLS: &[&str] = &["file_stat", "glob", "grep", "list_dir", "read_file", "tree"];
 const MAX_ITERATIONS: usize = 10;
 
+const STRUCTURE_PROMPT: &str = "You convert software implementation plans into JSON. \
+Keep every step, file path, and code reference from the plan; do not invent new ones.";
+
+/// An implementation plan in machine-readable form
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Plan {
+    pub overview: String,
+    pub steps: Vec<String>,
+    pub files_to_modify: Vec<FileChange>,
+    pub files_to_create: Vec<FileChange>,
+    pub testing_strategy: Vec<String>,
+}
+
+/// A file the plan touches and what happens to it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub description: String,
+}
+
+impl Plan {
+    /// JSON schema for [`Plan`], strict-mode compatible (every property
+    /// required, no additional properties)
+    pub fn schema() -> Value {
+        let file_change = json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "description": { "type": "string" }
+            },
+            "required": ["path", "description"],
+            "additionalProperties": false
+        });
+        json!({
+            "type": "object",
+            "properties": {
+                "overview": { "type": "string" },
+                "steps": { "type": "array", "items": { "type": "string" } },
+                "files_to_modify": { "type": "array", "items": file_change },
+                "files_to_create": { "type": "array", "items": file_change },
+                "testing_strategy": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["overview", "steps", "files_to_modify", "files_to_create", "testing_strategy"],
+            "additionalProperties": false
+        })
+    }
+
+    /// Render the plan in the same Markdown layout the planner asks for
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "## Implementation Plan\n\n### Overview\n{}\n\n### Steps\n",
+            self.overview
+        );
+        for (i, step) in self.steps.iter().enumerate() {
+            let _ = writeln!(out, "{}. {}", i + 1, step);
+        }
+        for (heading, files) in [
+            ("Files to Modify", &self.files_to_modify),
+            ("Files to Create", &self.files_to_create),
+        ] {
+            if files.is_empty() {
+                continue;
+            }
+            let _ = write!(out, "\n### {}\n", heading);
+            for file in files {
+                let _ = writeln!(out, "- {}: {}", file.path, file.description);
+            }
+        }
+        out.push_str("\n### Testing Strategy\n");
+        for item in &self.testing_strategy {
+            let _ = writeln!(out, "- {}", item);
+        }
+        out
+    }
+}
+
 /// An agent that analyzes tasks and creates implementation plans
-pub struct PlannerAgent;
+pub struct PlannerAgent {
+    loop_config: AgentLoopConfig,
+    overrides: AgentOverrides,
+}
 
 impl PlannerAgent {
     pub fn new() -> Self {
-        Self
+        Self {
+            loop_config: AgentLoopConfig::default(),
+            overrides: AgentOverrides::default(),
+        }
+    }
+
+    /// Override the agent loop settings (e.g. the inter-request delay)
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.loop_config = config;
+        self
+    }
+
+    /// Start building a planner with its system prompt, iteration limit or
+    /// tools replaced
+    pub fn builder() -> AgentBuilder<Self> {
+        AgentBuilder::new(|loop_config, overrides| Self {
+            loop_config,
+            overrides,
+        })
     }
 }
 
@@ -26,6 +129,9 @@ impl Default for PlannerAgent {
 #[async_trait]
 impl Agent for PlannerAgent {
     fn system_prompt(&self) -> String {
+        if let Some(prompt) = &self.overrides.system_prompt {
+            return prompt.clone();
+        }
         r#"You are a planning agent that analyzes software development tasks and creates detailed implementation plans.
 
 Your job is to:
@@ -74,21 +180,167 @@ Important:
         task: &str,
         provider: &dyn LlmProvider,
         tools: &ToolRegistry,
+    ) -> Result<String> {
+        self.run_with_context(task, provider, tools, &RunContext::new())
+            .await
+    }
+
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
     ) -> Result<String> {
         let messages = vec![Message::user(format!(
             "Create an implementation plan for the following task:\n\n{}",
             task
         ))];
 
-        agent_loop(
-            "planner",
-            &self.system_prompt(),
+        let allowed = self.overrides.allowed_tools();
+        let plan = agent_loop(AgentLoopParams {
+            agent_name: "planner",
+            system_prompt: &self.system_prompt(),
             messages,
             provider,
             tools,
-            Some(&["glob", "grep", "read_file"]),
-            MAX_ITERATIONS,
-        )
-        .await
+            allowed_tools: allowed.as_deref().or(Some(ALLOWED_TOOLS)),
+            read_only: false,
+            max_iterations: self.overrides.max_iterations.unwrap_or(MAX_ITERATIONS),
+            config: &self.loop_config,
+            session: ctx.session(),
+            cancellation: Some(ctx.cancellation()),
+        })
+        .await?;
+
+        if !provider.supports_structured_output() {
+            return Ok(plan);
+        }
+
+        // Normalize the plan through JSON mode so downstream agents always
+        // get the same layout; keep the free-form plan if that fails
+        match structure_plan(&plan, provider).await {
+            Ok(structured) => Ok(structured.to_markdown()),
+            Err(e) => {
+                warn!(error = %format!("{:#}", e), "could not structure plan, using it as written");
+                Ok(plan)
+            }
+        }
+    }
+}
+
+/// Convert a free-form plan into a [`Plan`] using the provider's JSON mode
+async fn structure_plan(plan: &str, provider: &dyn LlmProvider) -> Result<Plan> {
+    let value = provider
+        .chat_structured(STRUCTURE_PROMPT, &[Message::user(plan)], &Plan::schema())
+        .await?;
+    debug!("structured plan received");
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Policy;
+    use crate::testing::{MockLlmProvider, text_response, tool_call_response};
+
+    const FREE_FORM: &str = "1. Edit src/lib.rs";
+
+    fn structured_plan() -> String {
+        json!({
+            "overview": "Add a greeting",
+            "steps": ["Edit src/lib.rs"],
+            "files_to_modify": [{ "path": "src/lib.rs", "description": "add greet()" }],
+            "files_to_create": [],
+            "testing_strategy": ["cargo test"]
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn structures_plan_when_provider_supports_json_mode() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response(FREE_FORM),
+            text_response(structured_plan()),
+        ])
+        .with_structured_output();
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+
+        let output = PlannerAgent::new()
+            .run("greet", &provider, &tools)
+            .await
+            .unwrap();
+
+        assert_eq!(provider.schemas(), vec![Plan::schema()]);
+        assert!(output.starts_with("## Implementation Plan"));
+        assert!(output.contains("1. Edit src/lib.rs"));
+        assert!(output.contains("- src/lib.rs: add greet()"));
+        assert!(!output.contains("Files to Create"));
+    }
+
+    #[tokio::test]
+    async fn keeps_free_form_plan_without_json_mode() {
+        let provider = MockLlmProvider::with_responses(vec![text_response(FREE_FORM)]);
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+
+        let output = PlannerAgent::new()
+            .run("greet", &provider, &tools)
+            .await
+            .unwrap();
+
+        assert_eq!(output, FREE_FORM);
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_free_form_plan_when_structuring_fails() {
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response(FREE_FORM),
+            text_response(r#"{"overview": "missing fields"}"#),
+        ])
+        .with_structured_output();
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+
+        let output = PlannerAgent::new()
+            .run("greet", &provider, &tools)
+            .await
+            .unwrap();
+
+        assert_eq!(output, FREE_FORM);
+    }
+
+    #[tokio::test]
+    async fn overrides_replace_prompt_tools_and_iteration_limit() {
+        let planner = PlannerAgent::builder()
+            .with_system_prompt("Plan in the style of our RFC template.")
+            .with_allowed_tools(vec!["read_file".to_string()])
+            .with_max_iterations(2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            planner.system_prompt(),
+            "Plan in the style of our RFC template."
+        );
+
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "grep", json!({"pattern": "fn main"})),
+            tool_call_response("2", "read_file", json!({"path": "Cargo.toml"})),
+            text_response("never reached"),
+        ]);
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+
+        let err = planner.run("greet", &provider, &tools).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "planner agent exceeded maximum iterations (2)"
+        );
+
+        // grep is normally allowed for the planner
+        let second_request = &provider.requests()[1];
+        let result = second_request.last().unwrap().tool_result.as_ref().unwrap();
+        assert_eq!(
+            result.result,
+            "Error: tool 'grep' is not allowed for the planner agent. Use one of: read_file"
+        );
     }
 }
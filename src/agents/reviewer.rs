@@ -1,19 +1,51 @@
 use anyhow::Result;
 use async_trait::async_trait;
 
-use super::Agent;
-use super::runner::agent_loop;
+use super::builder::{AgentBuilder, AgentOverrides};
+use super::runner::{AgentLoopConfig, AgentLoopParams, agent_loop};
+use super::{Agent, RunContext};
 use crate::llm::{LlmProvider, Message};
 use crate::tools::ToolRegistry;
 
+/// Reviewer is read-only — no shell, no write tools
+const ALLOWED_TOOLS: &[&str] = &[
+    "diff",
+    "file_stat",
+    "glob",
+    "grep",
+    "list_dir",
+    "read_file",
+    "tree",
+];
 const MAX_ITERATIONS: usize = 10;
 
 /// An agent that reviews implementations and validates task completion
-pub struct ReviewerAgent;
+pub struct ReviewerAgent {
+    loop_config: AgentLoopConfig,
+    overrides: AgentOverrides,
+}
 
 impl ReviewerAgent {
     pub fn new() -> Self {
-        Self
+        Self {
+            loop_config: AgentLoopConfig::default(),
+            overrides: AgentOverrides::default(),
+        }
+    }
+
+    /// Override the agent loop settings (e.g. the inter-request delay)
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.loop_config = config;
+        self
+    }
+
+    /// Start building a reviewer with its system prompt, iteration limit or
+    /// tools replaced
+    pub fn builder() -> AgentBuilder<Self> {
+        AgentBuilder::new(|loop_config, overrides| Self {
+            loop_config,
+            overrides,
+        })
     }
 }
 
@@ -26,6 +58,9 @@ impl Default for ReviewerAgent {
 #[async_trait]
 impl Agent for ReviewerAgent {
     fn system_prompt(&self) -> String {
+        if let Some(prompt) = &self.overrides.system_prompt {
+            return prompt.clone();
+        }
         r#"You are a code review agent that validates whether a task has been completed correctly.
 
 Your job is to:
@@ -88,22 +123,37 @@ Important:
         task: &str,
         provider: &dyn LlmProvider,
         tools: &ToolRegistry,
+    ) -> Result<String> {
+        self.run_with_context(task, provider, tools, &RunContext::new())
+            .await
+    }
+
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
     ) -> Result<String> {
         let messages = vec![Message::user(format!(
             "Review the following implementation and determine if it is complete:\n\n{}",
             task
         ))];
 
-        // Reviewer is read-only — no shell, no write tools
-        agent_loop(
-            "reviewer",
-            &self.system_prompt(),
+        let allowed = self.overrides.allowed_tools();
+        agent_loop(AgentLoopParams {
+            agent_name: "reviewer",
+            system_prompt: &self.system_prompt(),
             messages,
             provider,
             tools,
-            Some(&["glob", "grep", "read_file"]),
-            MAX_ITERATIONS,
-        )
+            allowed_tools: allowed.as_deref().or(Some(ALLOWED_TOOLS)),
+            read_only: true,
+            max_iterations: self.overrides.max_iterations.unwrap_or(MAX_ITERATIONS),
+            config: &self.loop_config,
+            session: ctx.session(),
+            cancellation: Some(ctx.cancellation()),
+        })
         .await
     }
 }
@@ -1,14 +1,98 @@
 use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::sync::Arc;
 use tokio::time::{Duration, sleep};
-use tracing::{debug, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
-use crate::llm::{LlmProvider, Message};
-use crate::tools::ToolRegistry;
+use super::{RunCancelled, SessionHandle};
+use crate::config::PolicyViolation;
+use crate::llm::{LlmProvider, Message, MessageSummarizer, NaiveSummarizer, TokenUsage};
+use crate::session::PolicyDenial;
+use crate::tools::{ToolRegistry, is_transient};
 
-/// Shared agent execution loop.
-///
-/// Handles the common pattern of iterating with an LLM, executing tool calls,
-/// and assembling messages until the LLM stops requesting tools.
+const DEFAULT_INTER_REQUEST_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_JITTER_FACTOR: f64 = 0.5;
+const DEFAULT_TOOL_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Callback that can inspect or modify the messages sent on each LLM call
+pub type BeforeLlmCall = Arc<dyn Fn(&mut Vec<Message>) + Send + Sync>;
+
+/// Tunable behaviour of the shared agent loop
+#[derive(Clone)]
+pub struct AgentLoopConfig {
+    /// Pause between LLM round-trips to avoid hammering the API
+    /// (default: 100ms, zero disables it)
+    pub inter_request_delay: Duration,
+    /// Fraction of `inter_request_delay` that is randomized, so concurrent
+    /// loops don't fall into step: each pause lasts between
+    /// `delay * (1 - jitter_factor)` and `delay` (default: 0.5, clamped to 0..=1)
+    pub jitter_factor: f64,
+    /// Collapse runs of identical consecutive tool results before sending
+    /// the conversation to the LLM (default: off)
+    pub compact_repeated_tool_results: bool,
+    /// Prune the oldest round-trips from the conversation sent to the LLM
+    /// once its estimated size passes this many tokens (default: off). Like
+    /// compaction, this only affects what is sent, not the kept history.
+    pub token_budget: Option<usize>,
+    /// Summarizes what `token_budget` pruning removes
+    /// (default: [`NaiveSummarizer`])
+    pub summarizer: Arc<dyn MessageSummarizer>,
+    /// Called right before every LLM call, after compaction and pruning, with the
+    /// messages about to be sent. Changes only affect that call, not the
+    /// conversation history kept by the loop.
+    pub before_llm_call: Option<BeforeLlmCall>,
+    /// How many times a failed call to an idempotent tool is retried
+    /// before its error is returned to the model (default: 0)
+    pub tool_retries: u32,
+    /// Pause before each tool retry (default: 200ms)
+    pub tool_retry_delay: Duration,
+}
+
+impl AgentLoopConfig {
+    /// Inspect or modify the outgoing messages before each LLM call
+    pub fn with_before_llm_call(
+        mut self,
+        callback: impl Fn(&mut Vec<Message>) + Send + Sync + 'static,
+    ) -> Self {
+        self.before_llm_call = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl Default for AgentLoopConfig {
+    fn default() -> Self {
+        Self {
+            inter_request_delay: DEFAULT_INTER_REQUEST_DELAY,
+            jitter_factor: DEFAULT_JITTER_FACTOR,
+            compact_repeated_tool_results: false,
+            token_budget: None,
+            summarizer: Arc::new(NaiveSummarizer),
+            before_llm_call: None,
+            tool_retries: 0,
+            tool_retry_delay: DEFAULT_TOOL_RETRY_DELAY,
+        }
+    }
+}
+
+impl std::fmt::Debug for AgentLoopConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentLoopConfig")
+            .field("inter_request_delay", &self.inter_request_delay)
+            .field("jitter_factor", &self.jitter_factor)
+            .field(
+                "compact_repeated_tool_results",
+                &self.compact_repeated_tool_results,
+            )
+            .field("token_budget", &self.token_budget)
+            .field("before_llm_call", &self.before_llm_call.is_some())
+            .field("tool_retries", &self.tool_retries)
+            .field("tool_retry_delay", &self.tool_retry_delay)
+            .finish()
+    }
+}
+
+/// Parameters for the shared agent loop
 ///
 /// - `agent_name`: For logging (e.g., "planner", "coder")
 /// - `system_prompt`: The system prompt for this agent
@@ -17,63 +101,231 @@ use crate::tools::ToolRegistry;
 /// - `tools`: Full tool registry
 /// - `allowed_tools`: If `Some`, only these tools are presented and allowed for execution.
 ///   If `None`, all tools are available.
+/// - `read_only`: Deny every tool call that would change files (see
+///   [`Tool::modifies_files`](crate::tools::Tool::modifies_files)), even if
+///   `allowed_tools` lists the tool
 /// - `max_iterations`: Maximum number of LLM round-trips before bailing
-pub async fn agent_loop(
-    agent_name: &str,
-    system_prompt: &str,
-    mut messages: Vec<Message>,
-    provider: &dyn LlmProvider,
-    tools: &ToolRegistry,
-    allowed_tools: Option<&[&str]>,
-    max_iterations: usize,
-) -> Result<String> {
+/// - `config`: Tunable loop behaviour
+/// - `session`: Session to account LLM round-trips against, if the run is tracked
+/// - `cancellation`: Stops the loop between round-trips (or during an LLM call)
+///   with [`RunCancelled`]
+pub struct AgentLoopParams<'a> {
+    pub agent_name: &'a str,
+    pub system_prompt: &'a str,
+    pub messages: Vec<Message>,
+    pub provider: &'a dyn LlmProvider,
+    pub tools: &'a ToolRegistry,
+    pub allowed_tools: Option<&'a [&'a str]>,
+    pub read_only: bool,
+    pub max_iterations: usize,
+    pub config: &'a AgentLoopConfig,
+    pub session: Option<&'a SessionHandle>,
+    pub cancellation: Option<&'a CancellationToken>,
+}
+
+/// Shared agent execution loop.
+///
+/// Handles the common pattern of iterating with an LLM, executing tool calls,
+/// and assembling messages until the LLM stops requesting tools.
+pub async fn agent_loop(params: AgentLoopParams<'_>) -> Result<String> {
+    let AgentLoopParams {
+        agent_name,
+        system_prompt,
+        mut messages,
+        provider,
+        tools,
+        allowed_tools,
+        read_only,
+        max_iterations,
+        config,
+        session,
+        cancellation,
+    } = params;
+
+    // Factory-registered tools must exist before their schemas are sent
+    tools
+        .initialize(allowed_tools)
+        .await
+        .with_context(|| format!("{} agent: tool setup failed", agent_name))?;
+
+    // Build tool references — filter if allowed_tools is specified
+    let tool_refs: Vec<&dyn crate::tools::Tool> = if let Some(allowed) = allowed_tools {
+        for name in allowed.iter().filter(|name| tools.get(name).is_none()) {
+            warn!(agent = agent_name, tool = %name, "allowed tool is not registered");
+        }
+        tools
+            .all()
+            .into_iter()
+            .filter(|t| allowed.contains(&t.name()))
+            .collect()
+    } else {
+        tools.all()
+    };
+
+    // An empty allow list means a deliberately tool-less agent; a non-empty
+    // one that matches nothing is a misconfiguration (e.g. a typo'd name)
+    if let Some(allowed) = allowed_tools {
+        if !allowed.is_empty() && tool_refs.is_empty() {
+            anyhow::bail!(
+                "{} agent: none of its allowed tools are registered ({})",
+                agent_name,
+                allowed.join(", ")
+            );
+        }
+    }
+
+    let available: Vec<&str> = tool_refs.iter().map(|t| t.name()).collect();
+
+    // Messages before this index are already in the session
+    let mut saved = 0;
+
+    // A resumed run continues the conversation this agent left unfinished,
+    // as long as it was working on the same prompt
+    if let Some(session) = session {
+        let history = session
+            .read(|s| s.conversation(agent_name).map(<[Message]>::to_vec))
+            .await;
+        if let Some(history) = history
+            .filter(|h| h.first().map(|m| &m.content) == messages.first().map(|m| &m.content))
+        {
+            info!(
+                agent = agent_name,
+                messages = history.len(),
+                "continuing saved conversation"
+            );
+            messages = history;
+            saved = messages.len();
+        }
+    }
+
     for iteration in 0..max_iterations {
         debug!(agent = agent_name, iteration, "agent iteration");
 
+        // Tool calls from the previous round-trip have finished and been
+        // checkpointed, so this is a safe place to stop
+        if cancellation.is_some_and(CancellationToken::is_cancelled) {
+            info!(agent = agent_name, "agent cancelled");
+            return Err(RunCancelled.into());
+        }
+
         // Rate limiting to avoid hammering the API
-        if iteration > 0 {
-            sleep(Duration::from_millis(100)).await;
+        if iteration > 0 && !config.inter_request_delay.is_zero() {
+            sleep(jittered(
+                config.inter_request_delay,
+                config.jitter_factor,
+                fastrand::f64(),
+            ))
+            .await;
         }
 
-        // Build tool references — filter if allowed_tools is specified
-        let tool_refs: Vec<&dyn crate::tools::Tool> = if let Some(allowed) = allowed_tools {
-            tools
-                .all()
-                .into_iter()
-                .filter(|t| allowed.contains(&t.name()))
-                .collect()
-        } else {
-            tools.all()
-        };
+        // Prepare the outgoing messages: compaction, then pruning, then the
+        // caller's hook
+        let mut outgoing = Cow::Borrowed(&messages);
+        if config.compact_repeated_tool_results {
+            outgoing = Cow::Owned(compact_repeated_tool_results(&messages));
+        }
+        if let Some(budget) = config.token_budget {
+            if crate::llm::estimate_tokens(&outgoing) > budget {
+                let removed = crate::llm::prune_to_token_budget(
+                    outgoing.to_mut(),
+                    budget,
+                    config.summarizer.as_ref(),
+                );
+                if removed > 0 {
+                    warn!(
+                        agent = agent_name,
+                        removed, budget, "conversation pruned to fit the token budget"
+                    );
+                }
+            }
+        }
+        if let Some(before_llm_call) = &config.before_llm_call {
+            before_llm_call(outgoing.to_mut());
+        }
 
         // Call the LLM
-        let response = provider
-            .chat(system_prompt, &messages, &tool_refs)
-            .await
-            .with_context(|| format!("{} agent: LLM chat failed", agent_name))?;
+        // An in-flight LLM call is abandoned on cancellation; nothing from
+        // this round-trip has been saved yet
+        let chat = provider.chat(system_prompt, &outgoing, &tool_refs);
+        let response = match cancellation {
+            Some(token) => tokio::select! {
+                response = chat => response,
+                _ = token.cancelled() => {
+                    info!(agent = agent_name, "agent cancelled during LLM call");
+                    return Err(RunCancelled.into());
+                }
+            },
+            None => chat.await,
+        }
+        .with_context(|| format!("{} agent: LLM chat failed", agent_name))?;
 
         debug!(agent = agent_name, content = %response.message.content, "llm response");
+        let usage = response.usage;
+        if let Some(usage) = &usage {
+            debug!(
+                agent = agent_name,
+                input_tokens = usage.input_tokens,
+                output_tokens = usage.output_tokens,
+                cache_read_tokens = usage.cache_read_tokens,
+                "llm usage"
+            );
+        }
 
         let tool_calls = response.tool_calls;
 
         if tool_calls.is_empty() {
+            messages.push(Message::assistant(&response.message.content));
+            let round_trip = RoundTrip {
+                agent: agent_name,
+                messages: &messages,
+                saved,
+                finished: true,
+            };
+            checkpoint(session, round_trip, usage, Vec::new()).await?;
             info!(agent = agent_name, "agent completed (no more tool calls)");
             return Ok(response.message.content);
         }
 
         // Execute each tool call with filter enforcement
         let mut tool_results = Vec::with_capacity(tool_calls.len());
+        let mut denials = Vec::new();
         for tool_call in &tool_calls {
             debug!(agent = agent_name, tool = %tool_call.name, "executing tool");
 
-            let result = if let Some(allowed) = allowed_tools {
-                if !allowed.contains(&tool_call.name.as_str()) {
-                    format!("Tool '{}' is not available to this agent", tool_call.name)
-                } else {
-                    execute_tool_call(tools, tool_call).await
-                }
+            // A tool that doesn't exist and one this agent may not use call
+            // for different fixes, so the model gets a distinct message for each
+            let tool = tools.get(&tool_call.name);
+            let result = if tool.is_none() {
+                warn!(agent = agent_name, tool = %tool_call.name, "model called an unknown tool");
+                unknown_tool_message(&tool_call.name, &available)
+            } else if read_only && tool.is_some_and(|t| t.modifies_files(&tool_call.arguments)) {
+                let violation = PolicyViolation::new(format!(
+                    "the {} agent is read-only and this '{}' call would modify files",
+                    agent_name, tool_call.name
+                ));
+                warn!(agent = agent_name, tool = %tool_call.name, "read-only agent tried to modify files");
+                denials.push(PolicyDenial {
+                    tool: tool_call.name.clone(),
+                    reason: violation.reason.clone(),
+                });
+                tool_result_text(Err(violation.into()))
+            } else if !available.contains(&tool_call.name.as_str()) {
+                warn!(agent = agent_name, tool = %tool_call.name, "model called a tool it is not allowed to use");
+                tool_not_allowed_message(agent_name, &tool_call.name, &available)
             } else {
-                execute_tool_call(tools, tool_call).await
+                let result = execute_tool_call(tools, tool_call, config).await;
+                let violation = result
+                    .as_ref()
+                    .err()
+                    .and_then(|e| e.downcast_ref::<PolicyViolation>());
+                if let Some(violation) = violation {
+                    warn!(agent = agent_name, tool = %tool_call.name, reason = %violation, "tool call denied by policy");
+                    denials.push(PolicyDenial {
+                        tool: tool_call.name.clone(),
+                        reason: violation.reason.clone(),
+                    });
+                }
+                tool_result_text(result)
             };
 
             debug!(agent = agent_name, tool = %tool_call.name, result = %result, "tool result");
@@ -90,6 +342,15 @@ pub async fn agent_loop(
         for (id, result) in tool_results {
             messages.push(Message::tool_result(&id, result));
         }
+
+        let round_trip = RoundTrip {
+            agent: agent_name,
+            messages: &messages,
+            saved,
+            finished: false,
+        };
+        checkpoint(session, round_trip, usage, denials).await?;
+        saved = messages.len();
     }
 
     anyhow::bail!(
@@ -99,13 +360,1073 @@ pub async fn agent_loop(
     );
 }
 
-async fn execute_tool_call(tools: &ToolRegistry, tool_call: &crate::llm::ToolCall) -> String {
+/// Scale `base` down by up to `factor`, using `random` in `0.0..1.0`
+fn jittered(base: Duration, factor: f64, random: f64) -> Duration {
+    let factor = if factor.is_nan() {
+        0.0
+    } else {
+        factor.clamp(0.0, 1.0)
+    };
+    base.mul_f64(1.0 - factor * random)
+}
+
+/// One finished LLM round-trip of an agent
+struct RoundTrip<'a> {
+    agent: &'a str,
+    /// The agent's whole conversation so far
+    messages: &'a [Message],
+    /// Messages before this index are already in the session
+    saved: usize,
+    /// Whether the agent is done, so its conversation needn't be resumed
+    finished: bool,
+}
+
+/// Save one LLM round-trip to the session, if the run is tracked: append the
+/// messages it added to the session's log, keep the agent's conversation so
+/// a resumed run can continue it, and count the round-trip and its token
+/// usage, so an interrupted run keeps everything up to its last completed step
+async fn checkpoint(
+    session: Option<&SessionHandle>,
+    round_trip: RoundTrip<'_>,
+    usage: Option<TokenUsage>,
+    denials: Vec<PolicyDenial>,
+) -> Result<()> {
+    if let Some(session) = session {
+        let RoundTrip {
+            agent,
+            messages,
+            saved,
+            finished,
+        } = round_trip;
+        let new_messages = messages[saved..].to_vec();
+        let conversation = (!finished).then(|| messages.to_vec());
+        session
+            .update(move |s| {
+                s.messages.extend(new_messages);
+                match conversation {
+                    Some(conversation) => s.save_conversation(agent, conversation),
+                    None => s.finish_conversation(agent),
+                }
+                s.policy_denials.extend(denials);
+                s.total_iterations += 1;
+                if let Some(usage) = usage {
+                    s.total_usage.get_or_insert_default().add(usage);
+                }
+            })
+            .await
+            .context("failed to checkpoint session")?;
+    }
+    Ok(())
+}
+
+/// Collapse runs of identical consecutive tool results (e.g. the agent
+/// re-running `cargo check` and getting the same errors).
+///
+/// Every tool call keeps its result message so call/result pairing stays
+/// intact; earlier duplicates are replaced with a short placeholder and the
+/// last one is annotated with the repeat count.
+fn compact_repeated_tool_results(messages: &[Message]) -> Vec<Message> {
+    let mut compacted = messages.to_vec();
+
+    let result_indices: Vec<usize> = compacted
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.tool_result.is_some())
+        .map(|(i, _)| i)
+        .collect();
+    let result_text = |i: usize| {
+        messages[i]
+            .tool_result
+            .as_ref()
+            .map(|r| r.result.as_str())
+            .unwrap_or_default()
+    };
+
+    for run in result_indices.chunk_by(|&a, &b| result_text(a) == result_text(b)) {
+        let Some((&last, earlier)) = run.split_last() else {
+            continue;
+        };
+        if earlier.is_empty() {
+            continue;
+        }
+
+        for &i in earlier {
+            if let Some(result) = compacted[i].tool_result.as_mut() {
+                result.result = "(same output as a later call, omitted)".to_string();
+            }
+        }
+        if let Some(result) = compacted[last].tool_result.as_mut() {
+            result
+                .result
+                .push_str(&format!("\n\n(repeated {} times)", run.len()));
+        }
+    }
+
+    compacted
+}
+
+/// Run a tool call, retrying idempotent tools per `config`. Only transient
+/// failures are retried: a policy violation or a missing file would just
+/// fail the same way again.
+async fn execute_tool_call(
+    tools: &ToolRegistry,
+    tool_call: &crate::llm::ToolCall,
+    config: &AgentLoopConfig,
+) -> Result<String> {
     if let Some(tool) = tools.get(&tool_call.name) {
-        match tool.execute(tool_call.arguments.clone()).await {
-            Ok(output) => output,
-            Err(e) => format!("Error: {}", e),
+        // Models occasionally send `null` (or no) arguments; indexing into that
+        // would surface as a confusing "missing parameter" error
+        if !tool_call.arguments.is_object() {
+            anyhow::bail!(
+                "arguments must be a JSON object with fields: {}",
+                expected_fields(&tool.schema())
+            );
         }
+
+        let retries = if tool.is_idempotent() {
+            config.tool_retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            match tool.execute(tool_call.arguments.clone()).await {
+                Ok(output) => return Ok(output),
+                Err(e) if attempt < retries && is_transient(&e) => {
+                    attempt += 1;
+                    warn!(tool = %tool_call.name, attempt, error = %e, "tool failed, retrying");
+                    sleep(config.tool_retry_delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    } else {
+        Ok(unknown_tool_message(&tool_call.name, &tools.list_names()))
+    }
+}
+
+/// What the model sees for a tool call's result
+fn tool_result_text(result: Result<String>) -> String {
+    result.unwrap_or_else(|e| format!("Error: {}", e))
+}
+
+/// Result for a call to a tool that isn't registered at all
+fn unknown_tool_message(name: &str, available: &[&str]) -> String {
+    format!(
+        "Error: unknown tool '{}': no tool with this name exists. Available tools: {}",
+        name,
+        tool_list(available)
+    )
+}
+
+/// Result for a call to a registered tool outside the agent's allow list
+fn tool_not_allowed_message(agent_name: &str, name: &str, available: &[&str]) -> String {
+    format!(
+        "Error: tool '{}' is not allowed for the {} agent. Use one of: {}",
+        name,
+        agent_name,
+        tool_list(available)
+    )
+}
+
+fn tool_list(names: &[&str]) -> String {
+    if names.is_empty() {
+        "(none)".to_string()
     } else {
-        format!("Error: unknown tool '{}'", tool_call.name)
+        names.join(", ")
+    }
+}
+
+/// List the fields a tool expects, preferring its required parameters
+fn expected_fields(schema: &serde_json::Value) -> String {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|fields| fields.iter().filter_map(|f| f.as_str()).collect())
+        .unwrap_or_default();
+
+    if !required.is_empty() {
+        return required.join(", ");
+    }
+
+    schema["properties"]
+        .as_object()
+        .map(|props| {
+            props
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Policy;
+    use crate::llm::{LlmResponse, TokenTotals, ToolCall};
+    use crate::session::{MemoryStorage, SessionState, Storage};
+    use crate::testing::{MockLlmProvider, text_response, tool_call_response};
+    use crate::tools::{ReadFileTool, ShellTool, Tool, TransientToolError};
+    use async_trait::async_trait;
+    use serde_json::{Value, json};
+    use std::time::Instant;
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        fn description(&self) -> &str {
+            "echo"
+        }
+        fn schema(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}})
+        }
+        async fn execute(&self, params: Value) -> Result<String> {
+            Ok(params["text"].as_str().unwrap_or_default().to_string())
+        }
+    }
+
+    fn registry() -> ToolRegistry {
+        let mut tools = ToolRegistry::new();
+        tools.register(ReadFileTool {
+            policy: Policy::default(),
+        });
+        tools.register(ShellTool {
+            policy: Policy::default(),
+        });
+        tools
+    }
+
+    /// Provider scripted for three round-trips: two tool calls, then a final answer
+    fn three_iteration_provider() -> MockLlmProvider {
+        MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "echo", json!({"text": "a"})),
+            tool_call_response("2", "echo", json!({"text": "b"})),
+            text_response("done"),
+        ])
+    }
+
+    async fn run_with_delay(provider: &MockLlmProvider, delay: Duration) -> Result<String> {
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let config = AgentLoopConfig {
+            inter_request_delay: delay,
+            ..Default::default()
+        };
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: None,
+            cancellation: None,
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn zero_delay_skips_inter_iteration_pause() {
+        let provider = three_iteration_provider();
+        let start = Instant::now();
+        let output = run_with_delay(&provider, Duration::ZERO).await.unwrap();
+
+        assert_eq!(output, "done");
+        // Two pauses at the default 100ms would take at least 200ms
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn configured_delay_pauses_between_iterations() {
+        let provider = three_iteration_provider();
+        let start = Instant::now();
+        run_with_delay(&provider, Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        // With the default jitter each of the two pauses is at least 25ms
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn jitter_stays_within_the_configured_fraction() {
+        let base = Duration::from_millis(100);
+        assert_eq!(jittered(base, 0.5, 0.0), base);
+        assert_eq!(jittered(base, 0.5, 0.5), Duration::from_millis(75));
+        assert!(jittered(base, 0.5, 0.999) > Duration::from_millis(50));
+        // No jitter, and out-of-range factors are clamped
+        assert_eq!(jittered(base, 0.0, 0.9), base);
+        assert_eq!(jittered(base, 2.0, 0.5), Duration::from_millis(50));
+        assert_eq!(jittered(base, -1.0, 0.5), base);
+        assert_eq!(jittered(base, f64::NAN, 0.5), base);
+    }
+
+    #[tokio::test]
+    async fn session_is_checkpointed_after_each_round_trip() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+
+        // Two round-trips succeed, then the provider fails mid-conversation
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "echo", json!({"text": "a"})),
+            tool_call_response("2", "echo", json!({"text": "b"})),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let result = agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: Some(&handle),
+            cancellation: None,
+        })
+        .await;
+        assert!(result.is_err());
+
+        // Everything up to the last completed round-trip was saved
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.total_iterations, 2);
+        let results: Vec<&str> = saved
+            .messages
+            .iter()
+            .filter_map(|m| m.tool_result.as_ref())
+            .map(|r| r.result.as_str())
+            .collect();
+        assert_eq!(saved.messages.len(), 5);
+        assert_eq!(saved.messages[0].content, "go");
+        assert_eq!(results, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn resumed_agent_continues_its_saved_conversation() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let run = |provider, handle| {
+            agent_loop(AgentLoopParams {
+                agent_name: "test",
+                system_prompt: "",
+                messages: vec![Message::user("go")],
+                provider,
+                tools: &tools,
+                allowed_tools: None,
+                read_only: false,
+                max_iterations: 5,
+                config: &config,
+                session: Some(handle),
+                cancellation: None,
+            })
+        };
+
+        // The first run dies after one completed round-trip
+        let provider = MockLlmProvider::with_responses(vec![tool_call_response(
+            "1",
+            "echo",
+            json!({"text": "a"}),
+        )]);
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+        assert!(run(&provider, &handle).await.is_err());
+
+        // The resumed run picks the conversation up where it stopped
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        let provider = MockLlmProvider::single_response("done");
+        let handle = SessionHandle::new(Arc::new(storage.clone()), saved);
+        assert_eq!(run(&provider, &handle).await.unwrap(), "done");
+
+        let sent = &provider.requests()[0];
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].content, "go");
+        assert_eq!(sent[2].tool_result.as_ref().unwrap().result, "a");
+
+        // The session log isn't duplicated and the finished conversation is dropped
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.messages.len(), 4);
+        assert!(saved.conversations.is_empty());
+    }
+
+    struct PanickingTool;
+
+    #[async_trait]
+    impl Tool for PanickingTool {
+        fn name(&self) -> &str {
+            "boom"
+        }
+        fn description(&self) -> &str {
+            "boom"
+        }
+        fn schema(&self) -> Value {
+            json!({"type": "object", "properties": {}})
+        }
+        async fn execute(&self, _params: Value) -> Result<String> {
+            panic!("process died mid-run");
+        }
+    }
+
+    #[tokio::test]
+    async fn checkpoints_survive_a_panic_mid_run() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+
+        // The third round-trip's tool call takes the run down
+        let run = tokio::spawn(async move {
+            let provider = MockLlmProvider::with_responses(vec![
+                tool_call_response("1", "echo", json!({"text": "a"})),
+                tool_call_response("2", "echo", json!({"text": "b"})),
+                tool_call_response("3", "boom", json!({})),
+                text_response("never reached"),
+            ]);
+            let mut tools = ToolRegistry::new();
+            tools.register(EchoTool);
+            tools.register(PanickingTool);
+            let config = AgentLoopConfig {
+                inter_request_delay: Duration::ZERO,
+                ..Default::default()
+            };
+            agent_loop(AgentLoopParams {
+                agent_name: "test",
+                system_prompt: "",
+                messages: vec![Message::user("go")],
+                provider: &provider,
+                tools: &tools,
+                allowed_tools: None,
+                read_only: false,
+                max_iterations: 5,
+                config: &config,
+                session: Some(&handle),
+                cancellation: None,
+            })
+            .await
+        });
+        assert!(run.await.unwrap_err().is_panic());
+
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.total_iterations, 2);
+        let results: Vec<&str> = saved
+            .messages
+            .iter()
+            .filter_map(|m| m.tool_result.as_ref())
+            .map(|r| r.result.as_str())
+            .collect();
+        assert_eq!(results, ["a", "b"]);
+    }
+
+    /// Tool that cancels the run while it executes
+    struct CancellingTool(CancellationToken);
+
+    #[async_trait]
+    impl Tool for CancellingTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        fn description(&self) -> &str {
+            "cancels the run"
+        }
+        fn schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+        async fn execute(&self, _params: Value) -> Result<String> {
+            self.0.cancel();
+            Ok("finished anyway".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_after_current_tool_call() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+        let token = CancellationToken::new();
+
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "echo", json!({})),
+            text_response("never reached"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(CancellingTool(token.clone()));
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let err = agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: Some(&handle),
+            cancellation: Some(&token),
+        })
+        .await
+        .unwrap_err();
+
+        assert!(err.is::<RunCancelled>());
+        assert_eq!(provider.requests().len(), 1);
+        // The interrupted tool call's result was still saved
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        let result = saved.messages.last().unwrap().tool_result.as_ref().unwrap();
+        assert_eq!(result.result, "finished anyway");
+    }
+
+    fn null_call(name: &str) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments: Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn null_arguments_for_read_file_yield_clear_message() {
+        let result = tool_result_text(
+            execute_tool_call(
+                &registry(),
+                &null_call("read_file"),
+                &AgentLoopConfig::default(),
+            )
+            .await,
+        );
+        assert_eq!(
+            result,
+            "Error: arguments must be a JSON object with fields: path"
+        );
+    }
+
+    #[tokio::test]
+    async fn null_arguments_for_shell_yield_clear_message() {
+        let result = tool_result_text(
+            execute_tool_call(
+                &registry(),
+                &null_call("shell"),
+                &AgentLoopConfig::default(),
+            )
+            .await,
+        );
+        assert_eq!(
+            result,
+            "Error: arguments must be a JSON object with fields: command"
+        );
+    }
+
+    #[tokio::test]
+    async fn compaction_collapses_identical_tool_results() {
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let args = json!({ "text": "same error" });
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("call_1", "echo", args.clone()),
+            tool_call_response("call_2", "echo", args.clone()),
+            tool_call_response("call_3", "echo", args),
+            text_response("done"),
+        ]);
+
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            compact_repeated_tool_results: true,
+            ..Default::default()
+        };
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: None,
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        let last_request = provider.requests().pop().unwrap();
+        let results: Vec<&str> = last_request
+            .iter()
+            .filter_map(|m| m.tool_result.as_ref())
+            .map(|r| r.result.as_str())
+            .collect();
+
+        // Every call keeps a result, but the output is only sent once
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results.iter().filter(|r| r.contains("same error")).count(),
+            1
+        );
+        assert!(results[2].ends_with("(repeated 3 times)"));
+    }
+
+    #[tokio::test]
+    async fn before_llm_call_changes_reach_the_provider() {
+        let provider = MockLlmProvider::with_responses(vec![text_response("done")]);
+        let config = AgentLoopConfig::default()
+            .with_before_llm_call(|messages| messages.push(Message::user("reminder")));
+
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &ToolRegistry::new(),
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 1,
+            config: &config,
+            session: None,
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        let sent = provider.requests().pop().unwrap();
+        let contents: Vec<&str> = sent.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["go", "reminder"]);
+    }
+
+    #[tokio::test]
+    async fn unknown_allowed_tools_are_a_configuration_error() {
+        let provider = MockLlmProvider::with_responses(vec![text_response("done")]);
+
+        let err = agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &ToolRegistry::with_default_tools(&Policy::default()),
+            allowed_tools: Some(&["raed_file"]),
+            read_only: false,
+            max_iterations: 1,
+            config: &AgentLoopConfig::default(),
+            session: None,
+            cancellation: None,
+        })
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("none of its allowed tools"));
+        assert!(provider.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_allow_list_runs_without_tools() {
+        let provider = MockLlmProvider::with_responses(vec![text_response("done")]);
+
+        let output = agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &ToolRegistry::with_default_tools(&Policy::default()),
+            allowed_tools: Some(&[]),
+            read_only: false,
+            max_iterations: 1,
+            config: &AgentLoopConfig::default(),
+            session: None,
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(output, "done");
+    }
+
+    #[tokio::test]
+    async fn unknown_and_disallowed_tools_get_distinct_messages() {
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "shell", json!({"command": "ls"})),
+            tool_call_response("2", "teleport", json!({})),
+            text_response("done"),
+        ]);
+
+        agent_loop(AgentLoopParams {
+            agent_name: "planner",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &registry(),
+            allowed_tools: Some(&["read_file"]),
+            read_only: false,
+            max_iterations: 5,
+            config: &AgentLoopConfig {
+                inter_request_delay: Duration::ZERO,
+                ..Default::default()
+            },
+            session: None,
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        let requests = provider.requests();
+        let result_of = |request: usize| {
+            requests[request]
+                .last()
+                .and_then(|m| m.tool_result.as_ref())
+                .map(|r| r.result.clone())
+                .unwrap()
+        };
+        assert_eq!(
+            result_of(1),
+            "Error: tool 'shell' is not allowed for the planner agent. Use one of: read_file"
+        );
+        assert_eq!(
+            result_of(2),
+            "Error: unknown tool 'teleport': no tool with this name exists. Available tools: read_file"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_tool_without_allow_list_lists_registered_tools() {
+        let result = tool_result_text(
+            execute_tool_call(
+                &registry(),
+                &null_call("teleport"),
+                &AgentLoopConfig::default(),
+            )
+            .await,
+        );
+        assert_eq!(
+            result,
+            "Error: unknown tool 'teleport': no tool with this name exists. Available tools: read_file, shell"
+        );
+    }
+
+    /// Fails its first `failures` calls, then echoes
+    struct FlakyTool {
+        idempotent: bool,
+        failures: usize,
+        transient: bool,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl FlakyTool {
+        fn new(idempotent: bool, failures: usize) -> Self {
+            Self {
+                idempotent,
+                failures,
+                transient: true,
+                calls: Arc::default(),
+            }
+        }
+
+        /// Fail with an error that retrying won't fix
+        fn permanent(mut self) -> Self {
+            self.transient = false;
+            self
+        }
+
+        /// Register the tool, returning a way to read its call count
+        fn register(self, tools: &mut ToolRegistry) -> impl Fn() -> usize + use<> {
+            let calls = Arc::clone(&self.calls);
+            tools.register(self);
+            move || calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl Tool for FlakyTool {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+        fn description(&self) -> &str {
+            "flaky"
+        }
+        fn schema(&self) -> Value {
+            json!({"type": "object", "properties": {}})
+        }
+        async fn execute(&self, _params: Value) -> Result<String> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call < self.failures && self.transient {
+                return Err(TransientToolError::new("file is locked").into());
+            }
+            if call < self.failures {
+                anyhow::bail!("file not found");
+            }
+            Ok("ok".to_string())
+        }
+        fn is_idempotent(&self) -> bool {
+            self.idempotent
+        }
+    }
+
+    fn retrying_config() -> AgentLoopConfig {
+        AgentLoopConfig {
+            tool_retries: 2,
+            tool_retry_delay: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    fn flaky_call() -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: "flaky".to_string(),
+            arguments: json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn idempotent_tool_failing_once_is_retried() {
+        let mut tools = ToolRegistry::new();
+        let calls = FlakyTool::new(true, 1).register(&mut tools);
+
+        let result =
+            tool_result_text(execute_tool_call(&tools, &flaky_call(), &retrying_config()).await);
+
+        assert_eq!(result, "ok");
+        assert_eq!(calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_are_bounded() {
+        let mut tools = ToolRegistry::new();
+        let calls = FlakyTool::new(true, 10).register(&mut tools);
+
+        let result =
+            tool_result_text(execute_tool_call(&tools, &flaky_call(), &retrying_config()).await);
+
+        assert_eq!(result, "Error: file is locked");
+        assert_eq!(calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_tool_is_not_retried() {
+        let mut tools = ToolRegistry::new();
+        let calls = FlakyTool::new(false, 1).register(&mut tools);
+
+        let result =
+            tool_result_text(execute_tool_call(&tools, &flaky_call(), &retrying_config()).await);
+
+        assert_eq!(result, "Error: file is locked");
+        assert_eq!(calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried() {
+        let mut tools = ToolRegistry::new();
+        let calls = FlakyTool::new(true, 1).permanent().register(&mut tools);
+
+        let result =
+            tool_result_text(execute_tool_call(&tools, &flaky_call(), &retrying_config()).await);
+
+        assert_eq!(result, "Error: file not found");
+        assert_eq!(calls(), 1);
+    }
+
+    #[test]
+    fn io_timeouts_are_transient() {
+        let timeout = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::TimedOut))
+            .context("failed to read file");
+        let missing = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("failed to read file");
+
+        assert!(is_transient(&timeout));
+        assert!(!is_transient(&missing));
+        assert!(!is_transient(&PolicyViolation::new("denied").into()));
+    }
+
+    #[test]
+    fn only_read_only_default_tools_are_retryable() {
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+        for name in ["write_file", "edit_file", "move_file", "shell"] {
+            assert!(!tools.get(name).unwrap().is_idempotent(), "{}", name);
+        }
+        for name in ["read_file", "grep", "glob", "list_dir"] {
+            assert!(tools.get(name).unwrap().is_idempotent(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn token_budget_prunes_what_is_sent_but_not_the_history() {
+        let big = "x".repeat(2_000);
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "echo", json!({ "text": big })),
+            tool_call_response("2", "echo", json!({ "text": big })),
+            tool_call_response("3", "echo", json!({ "text": "small" })),
+            text_response("done"),
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &AgentLoopConfig {
+                inter_request_delay: Duration::ZERO,
+                token_budget: Some(500),
+                ..Default::default()
+            },
+            session: Some(&handle),
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        // The last request carried only the task (with a summary) and the
+        // latest round-trip
+        let last = provider.requests().pop().unwrap();
+        assert_eq!(last.len(), 3);
+        assert!(last[0].content.starts_with("go\n\n## Earlier conversation"));
+        assert_eq!(last[1].tool_calls[0].id, "3");
+
+        // The saved conversation is complete
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.messages.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn token_usage_accumulates_on_the_session() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+
+        let provider = MockLlmProvider::with_responses(vec![
+            LlmResponse {
+                usage: Some(TokenUsage {
+                    input_tokens: 120,
+                    output_tokens: 30,
+                    cache_read_tokens: Some(100),
+                }),
+                ..tool_call_response("1", "echo", json!({"text": "a"}))
+            },
+            LlmResponse {
+                usage: Some(TokenUsage {
+                    input_tokens: 200,
+                    output_tokens: 15,
+                    cache_read_tokens: None,
+                }),
+                ..text_response("done")
+            },
+        ]);
+        let mut tools = ToolRegistry::new();
+        tools.register(EchoTool);
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: Some(&handle),
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(
+            saved.total_usage,
+            Some(TokenTotals {
+                input_tokens: 320,
+                output_tokens: 45,
+                cache_read_tokens: Some(100),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_denials_are_fed_back_and_recorded_on_the_session() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        let id = session.id.clone();
+        let handle = SessionHandle::new(Arc::new(storage.clone()), session);
+
+        let provider = MockLlmProvider::with_responses(vec![
+            tool_call_response("1", "shell", json!({"command": "sudo reboot"})),
+            tool_call_response("2", "shell", json!({"command": "echo fine"})),
+            text_response("done"),
+        ]);
+        let policy = Policy {
+            deny_commands: vec!["sudo".to_string()],
+            tool_retries: Some(2),
+            ..Policy::default()
+        };
+        let tools = ToolRegistry::with_default_tools(&policy);
+        let config = AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            tool_retries: 2,
+            tool_retry_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        agent_loop(AgentLoopParams {
+            agent_name: "test",
+            system_prompt: "",
+            messages: vec![Message::user("go")],
+            provider: &provider,
+            tools: &tools,
+            allowed_tools: None,
+            read_only: false,
+            max_iterations: 5,
+            config: &config,
+            session: Some(&handle),
+            cancellation: None,
+        })
+        .await
+        .unwrap();
+
+        // The model still gets the reason as before
+        let second_request = &provider.requests()[1];
+        let result = second_request.last().unwrap().tool_result.as_ref().unwrap();
+        assert_eq!(result.result, "Error: command 'sudo' is denied by policy");
+
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(
+            saved.policy_denials,
+            [PolicyDenial {
+                tool: "shell".to_string(),
+                reason: "command 'sudo' is denied by policy".to_string(),
+            }]
+        );
     }
 }
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::runner::{AgentLoopConfig, AgentLoopParams, agent_loop};
+use super::{Agent, RunContext};
+use crate::llm::{LlmProvider, Message};
+use crate::tools::ToolRegistry;
+
+const MAX_ITERATIONS: usize = 10;
+
+/// A read-only agent that checks an implementation for common security
+/// vulnerabilities before it goes to review
+pub struct SecurityAuditAgent {
+    loop_config: AgentLoopConfig,
+}
+
+impl SecurityAuditAgent {
+    pub fn new() -> Self {
+        Self {
+            loop_config: AgentLoopConfig::default(),
+        }
+    }
+
+    /// Override the agent loop settings (e.g. the inter-request delay)
+    pub fn with_loop_config(mut self, config: AgentLoopConfig) -> Self {
+        self.loop_config = config;
+        self
+    }
+}
+
+impl Default for SecurityAuditAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check if the audit output found nothing to fix.
+/// Looks for "VERDICT: CLEAN" or "VERDICT: ISSUES_FOUND" on its own line;
+/// an audit without a verdict counts as finding issues, so a confused
+/// auditor can't wave changes through.
+pub(crate) fn is_audit_clean(audit: &str) -> bool {
+    for line in audit.lines() {
+        match line.trim().to_uppercase().as_str() {
+            "VERDICT: CLEAN" => return true,
+            "VERDICT: ISSUES_FOUND" => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[async_trait]
+impl Agent for SecurityAuditAgent {
+    fn system_prompt(&self) -> String {
+        r#"You are a security audit agent that checks freshly written code for common vulnerabilities.
+
+Your job is to inspect the files the implementation changed and report real, exploitable problems:
+- Secrets in code: API keys, passwords, tokens or private keys committed to source
+- Injection: SQL, shell or template strings built from unvalidated input
+- Path traversal: file paths built from input without normalizing or restricting them
+- Panics on untrusted input: chains of unwrap()/expect() on data from users, files or the network
+- Other OWASP-style issues: missing authentication checks, unsafe deserialization, weak crypto
+
+You have access to read-only tools to inspect the codebase. Use grep to look for patterns
+(e.g. "unwrap()", "format!(\"SELECT", "password") in the changed files.
+
+Format your response like this:
+## Security Audit
+
+### Findings
+[For each issue: file and line, the vulnerability, and how to fix it. "None" if clean.]
+
+### Verdict
+VERDICT: CLEAN
+or
+VERDICT: ISSUES_FOUND
+
+The verdict line must appear on its own line starting with "VERDICT: ".
+
+Important:
+- Only report issues in code the implementation added or changed
+- Don't flag unwrap() in tests, or on values that can't fail
+- Style problems are not security issues
+- You are read-only — do not attempt to modify any files
+"#
+        .to_string()
+    }
+
+    async fn run(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+    ) -> Result<String> {
+        self.run_with_context(task, provider, tools, &RunContext::new())
+            .await
+    }
+
+    async fn run_with_context(
+        &self,
+        task: &str,
+        provider: &dyn LlmProvider,
+        tools: &ToolRegistry,
+        ctx: &RunContext,
+    ) -> Result<String> {
+        let messages = vec![Message::user(format!(
+            "Audit the following implementation for security vulnerabilities:\n\n{}",
+            task
+        ))];
+
+        // The auditor is read-only, like the reviewer
+        agent_loop(AgentLoopParams {
+            agent_name: "security_audit",
+            system_prompt: &self.system_prompt(),
+            messages,
+            provider,
+            tools,
+            allowed_tools: Some(&[
+                "diff",
+                "file_stat",
+                "glob",
+                "grep",
+                "list_dir",
+                "read_file",
+                "tree",
+            ]),
+            read_only: true,
+            max_iterations: MAX_ITERATIONS,
+            config: &self.loop_config,
+            session: ctx.session(),
+            cancellation: Some(ctx.cancellation()),
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdict_line_decides_the_outcome() {
+        assert!(is_audit_clean("## Security Audit\nNone\nVERDICT: CLEAN"));
+        assert!(is_audit_clean("  verdict: clean  "));
+        assert!(!is_audit_clean(
+            "hardcoded key in src/api.rs:3\nVERDICT: ISSUES_FOUND"
+        ));
+        // No verdict: not waved through
+        assert!(!is_audit_clean("looks clean to me"));
+    }
+}
@@ -1,5 +1,7 @@
+mod error;
 mod policy;
 mod project;
 
-pub use policy::Policy;
-pub use project::ProjectConfig;
+pub use error::{ConfigError, InvalidConfig, PolicyViolation};
+pub use policy::{PROTECTED_ENV_VARS, Policy};
+pub use project::{ProjectConfig, SessionConfig};
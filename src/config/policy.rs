@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::session::SessionBudget;
 
 /// Security policy configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -18,4 +22,139 @@ pub struct Policy {
     /// Commands that are denied for shell execution
     #[serde(default)]
     pub deny_commands: Vec<String>,
+
+    /// Reject any shell command whose program isn't in `allow_commands`.
+    /// Entries match on their first word, so `cargo` and `cargo *` both
+    /// allow every cargo command.
+    #[serde(default)]
+    pub command_allowlist_only: bool,
+
+    /// Interpreters (shells, python, node, ...) the agent may invoke from a
+    /// shell command. An interpreter not listed here is rejected, since it
+    /// could run arbitrary code that skips the command checks.
+    /// Default: `["bash"]`.
+    #[serde(default)]
+    pub allowed_shell_interpreters: Option<Vec<String>>,
+
+    /// Interpreters that are always rejected, even if allowed above
+    #[serde(default)]
+    pub deny_interpreters: Vec<String>,
+
+    /// Hosts `http_fetch` may contact; an entry also allows its subdomains.
+    /// Empty (the default) allows every host.
+    #[serde(default)]
+    pub allow_http_hosts: Vec<String>,
+
+    /// Let `http_fetch` reach loopback, private and link-local addresses,
+    /// e.g. docs served on localhost. Off by default, so an allowed host
+    /// can't be pointed at local services or cloud metadata endpoints.
+    #[serde(default)]
+    pub allow_private_http: bool,
+
+    /// Default shell command timeout in seconds (default: 120)
+    #[serde(default)]
+    pub shell_timeout_secs: Option<u64>,
+
+    /// Upper bound for any shell command timeout, including ones the LLM
+    /// asks for, in seconds (default: 300)
+    #[serde(default)]
+    pub shell_max_timeout_secs: Option<u64>,
+
+    /// Time limit for a single grep/glob search in seconds (default: 30)
+    #[serde(default)]
+    pub search_timeout_secs: Option<u64>,
+
+    /// Files larger than this are skipped by grep (default: 1 MiB)
+    #[serde(default)]
+    pub search_max_file_bytes: Option<u64>,
+
+    /// Wrapper every shell command runs through, e.g. `firejail --quiet --`.
+    /// Split on whitespace; the command is appended as `bash -c '<cmd>'`.
+    #[serde(default)]
+    pub command_wrapper: Option<String>,
+
+    /// Refuse to read, write or edit files larger than this many bytes
+    /// (default: unlimited; 10 MiB is a sensible limit)
+    #[serde(default)]
+    pub max_file_size_bytes: Option<u64>,
+
+    /// How many times a failed read-only tool call (read_file, grep, ...)
+    /// is retried before the error goes back to the model (default: 0).
+    /// Tools that change files or run commands are never retried.
+    #[serde(default)]
+    pub tool_retries: Option<u32>,
+
+    /// Environment variables set for every shell command, e.g. a
+    /// `DATABASE_URL` for migrations. Values are kept out of logs; the
+    /// provider credentials in [`PROTECTED_ENV_VARS`] can't be overridden.
+    #[serde(default)]
+    pub shell_env: HashMap<String, String>,
+
+    /// `shell_env` variables holding secrets. Their values are shown as
+    /// `***` in command output (values shorter than 8 characters are left
+    /// alone, since masking them would mangle ordinary output). This only
+    /// guards against accidental echoes: a command can still print an
+    /// encoded value.
+    #[serde(default)]
+    pub shell_secret_env: Vec<String>,
+
+    /// Preview file writes and edits as diffs instead of changing files
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Refuse to resume a session once it has run this many seconds in
+    /// total, across all of its resumes (default: unlimited)
+    #[serde(default)]
+    pub max_session_runtime_secs: Option<u64>,
+
+    /// Stop a single run after this many seconds of wall-clock time; the
+    /// session is saved as interrupted so it can be resumed (default:
+    /// unlimited)
+    #[serde(default)]
+    pub run_timeout_secs: Option<u64>,
+
+    /// Refuse to resume a session once it has made this many LLM
+    /// round-trips in total, across all of its resumes (default: unlimited)
+    #[serde(default)]
+    pub max_session_iterations: Option<u64>,
+}
+
+/// Variables `shell_env` may not set: the credentials and endpoints the LLM
+/// providers read
+pub const PROTECTED_ENV_VARS: &[&str] = &[
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "GEMINI_API_KEY",
+    "OLLAMA_HOST",
+];
+
+impl Policy {
+    /// The interpreters shell commands may invoke, falling back to the
+    /// default of `bash` alone
+    pub fn allowed_shell_interpreters(&self) -> Vec<String> {
+        self.allowed_shell_interpreters
+            .clone()
+            .unwrap_or_else(|| vec!["bash".to_string()])
+    }
+
+    /// The wall-clock limit for a single run, if any
+    pub fn run_timeout(&self) -> Option<Duration> {
+        self.run_timeout_secs.map(Duration::from_secs)
+    }
+
+    /// The first `shell_env` variable that would override a protected one
+    pub fn protected_shell_env_var(&self) -> Option<&str> {
+        self.shell_env
+            .keys()
+            .map(String::as_str)
+            .find(|name| PROTECTED_ENV_VARS.contains(name))
+    }
+
+    /// The cumulative per-session budget these limits describe
+    pub fn session_budget(&self) -> SessionBudget {
+        SessionBudget {
+            max_runtime: self.max_session_runtime_secs.map(Duration::from_secs),
+            max_iterations: self.max_session_iterations,
+        }
+    }
 }
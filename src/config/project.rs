@@ -1,14 +1,19 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{debug, warn};
 
-use super::Policy;
+use super::{ConfigError, Policy};
+use crate::agents::AgentLoopConfig;
+use crate::llm::RetryConfig;
+use crate::session::{RESERVED_METADATA_KEYS, SessionState};
 
 /// Project-level configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProjectConfig {
-    /// LLM provider to use (e.g., "anthropic", "openai")
+    /// LLM provider to use (e.g., "anthropic", "openai", "gemini", "ollama")
     #[serde(default)]
     pub provider: Option<String>,
 
@@ -35,6 +40,57 @@ pub struct ProjectConfig {
     /// Always save sessions
     #[serde(default)]
     pub save_sessions: Option<bool>,
+
+    /// Defaults applied to every new session
+    #[serde(default)]
+    pub session: SessionConfig,
+
+    /// Test commands the tester runs concurrently before analyzing results
+    #[serde(default)]
+    pub test_commands: Vec<String>,
+
+    /// Maximum number of test commands running at once (default: 4)
+    #[serde(default)]
+    pub test_concurrency: Option<usize>,
+
+    /// Delay between an agent's LLM round-trips in milliseconds (default: 100, 0 disables)
+    #[serde(default)]
+    pub iteration_delay_ms: Option<u64>,
+
+    /// Collapse repeated identical tool results before sending them to the LLM
+    #[serde(default)]
+    pub compact_tool_results: Option<bool>,
+
+    /// Prune the oldest tool round-trips from what is sent to the LLM once
+    /// the conversation passes roughly this many tokens (default: off)
+    #[serde(default)]
+    pub token_budget: Option<usize>,
+
+    /// Finish orchestrated runs with a concise summary ahead of the detailed report
+    #[serde(default)]
+    pub summarize: Option<bool>,
+
+    /// Audit orchestrated runs' changes for security issues before review
+    #[serde(default)]
+    pub security_audit: Option<bool>,
+
+    /// Maximum tokens per LLM response (default: 8192)
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+
+    /// API keys per provider (`[api_keys]` table, e.g. `anthropic = "..."`).
+    /// Only read from the global config so keys never end up in a repository;
+    /// the provider's environment variable takes precedence.
+    #[serde(default, skip_serializing)]
+    pub api_keys: BTreeMap<String, String>,
+}
+
+/// Session defaults (`[session]` table)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Metadata merged into every new session (`[session.metadata]` table)
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
 }
 
 fn default_max_retries() -> u32 {
@@ -46,7 +102,9 @@ fn default_retry_delay_ms() -> u64 {
 }
 
 impl ProjectConfig {
-    /// Load configuration with precedence: project -> global -> defaults
+    /// Load configuration with precedence: project -> global -> defaults.
+    /// A config file that exists but can't be read or parsed is an error
+    /// rather than being skipped, so a typo doesn't go unnoticed.
     pub fn load() -> Result<Self> {
         let mut config = Self::default();
 
@@ -54,24 +112,15 @@ impl ProjectConfig {
         if let Some(global_path) = Self::global_config_path() {
             if global_path.exists() {
                 debug!(path = %global_path.display(), "loading global config");
-                match Self::load_from_file(&global_path) {
-                    Ok(global) => config = config.merge(global),
-                    Err(e) => {
-                        warn!(path = %global_path.display(), error = %e, "failed to load global config")
-                    }
-                }
+                config = config.merge(Self::load_from_file(&global_path)?);
             }
         }
 
         // Load project config (dev-killer.toml in current directory or parents)
         if let Some(project_path) = Self::find_project_config() {
             debug!(path = %project_path.display(), "loading project config");
-            match Self::load_from_file(&project_path) {
-                Ok(project) => config = config.merge(project),
-                Err(e) => {
-                    warn!(path = %project_path.display(), error = %e, "failed to load project config")
-                }
-            }
+            let project = Self::load_from_file(&project_path)?;
+            config = config.merge(project.without_api_keys(&project_path));
         }
 
         // Environment variable overrides
@@ -80,13 +129,37 @@ impl ProjectConfig {
         Ok(config)
     }
 
-    /// Load config from a specific file
+    /// Drop API keys from a project config; they belong in the global config
+    fn without_api_keys(mut self, path: &Path) -> Self {
+        if !self.api_keys.is_empty() {
+            warn!(
+                path = %path.display(),
+                "ignoring [api_keys] in project config; set API keys in the global config or environment"
+            );
+            self.api_keys.clear();
+        }
+        self
+    }
+
+    /// Load config from a specific file. Relative `policy.allow_paths` are
+    /// taken relative to the file's directory, not the current directory.
     pub fn load_from_file(path: &Path) -> Result<Self> {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("failed to read config file: {}", path.display()))?;
 
-        toml::from_str(&content)
-            .with_context(|| format!("failed to parse config file: {}", path.display()))
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("failed to parse config file: {}", path.display()))?;
+        // Allowed paths are matched against canonical paths
+        if let Some(dir) = path.parent() {
+            for allowed in &mut config.policy.allow_paths {
+                if Path::new(allowed.as_str()).is_relative() {
+                    let joined = dir.join(&*allowed);
+                    let resolved = std::fs::canonicalize(&joined).unwrap_or(joined);
+                    *allowed = resolved.to_string_lossy().into_owned();
+                }
+            }
+        }
+        Ok(config)
     }
 
     /// Get global config path (~/.config/dev-killer/config.toml)
@@ -128,6 +201,9 @@ impl ProjectConfig {
         // Deny lists should union, not replace
         self.policy.deny_paths.extend(other.policy.deny_paths);
         self.policy.deny_commands.extend(other.policy.deny_commands);
+        self.policy
+            .deny_interpreters
+            .extend(other.policy.deny_interpreters);
         // Allow lists replace (more specific config wins)
         if !other.policy.allow_paths.is_empty() {
             self.policy.allow_paths = other.policy.allow_paths;
@@ -135,6 +211,51 @@ impl ProjectConfig {
         if !other.policy.allow_commands.is_empty() {
             self.policy.allow_commands = other.policy.allow_commands;
         }
+        if !other.policy.allow_http_hosts.is_empty() {
+            self.policy.allow_http_hosts = other.policy.allow_http_hosts;
+        }
+        if other.policy.allowed_shell_interpreters.is_some() {
+            self.policy.allowed_shell_interpreters = other.policy.allowed_shell_interpreters;
+        }
+        // Allow-list enforcement can be turned on but not off by a more
+        // specific config
+        self.policy.command_allowlist_only |= other.policy.command_allowlist_only;
+        self.policy.dry_run |= other.policy.dry_run;
+        self.policy.allow_private_http |= other.policy.allow_private_http;
+        if other.policy.shell_timeout_secs.is_some() {
+            self.policy.shell_timeout_secs = other.policy.shell_timeout_secs;
+        }
+        if other.policy.shell_max_timeout_secs.is_some() {
+            self.policy.shell_max_timeout_secs = other.policy.shell_max_timeout_secs;
+        }
+        if other.policy.search_timeout_secs.is_some() {
+            self.policy.search_timeout_secs = other.policy.search_timeout_secs;
+        }
+        if other.policy.search_max_file_bytes.is_some() {
+            self.policy.search_max_file_bytes = other.policy.search_max_file_bytes;
+        }
+        if other.policy.command_wrapper.is_some() {
+            self.policy.command_wrapper = other.policy.command_wrapper;
+        }
+        if other.policy.max_file_size_bytes.is_some() {
+            self.policy.max_file_size_bytes = other.policy.max_file_size_bytes;
+        }
+        if other.policy.tool_retries.is_some() {
+            self.policy.tool_retries = other.policy.tool_retries;
+        }
+        self.policy.shell_env.extend(other.policy.shell_env);
+        self.policy
+            .shell_secret_env
+            .extend(other.policy.shell_secret_env);
+        if other.policy.run_timeout_secs.is_some() {
+            self.policy.run_timeout_secs = other.policy.run_timeout_secs;
+        }
+        if other.policy.max_session_runtime_secs.is_some() {
+            self.policy.max_session_runtime_secs = other.policy.max_session_runtime_secs;
+        }
+        if other.policy.max_session_iterations.is_some() {
+            self.policy.max_session_iterations = other.policy.max_session_iterations;
+        }
         // Always take explicit non-default values
         if other.max_retries != default_max_retries() {
             self.max_retries = other.max_retries;
@@ -149,6 +270,46 @@ impl ProjectConfig {
         if other.save_sessions.is_some() {
             self.save_sessions = other.save_sessions;
         }
+        // Metadata merges per key (more specific config wins)
+        self.session.metadata.extend(other.session.metadata);
+        if !other.test_commands.is_empty() {
+            self.test_commands = other.test_commands;
+        }
+        if other.test_concurrency.is_some() {
+            self.test_concurrency = other.test_concurrency;
+        }
+        if other.iteration_delay_ms.is_some() {
+            self.iteration_delay_ms = other.iteration_delay_ms;
+        }
+        if other.compact_tool_results.is_some() {
+            self.compact_tool_results = other.compact_tool_results;
+        }
+        if other.token_budget.is_some() {
+            self.token_budget = other.token_budget;
+        }
+        if other.summarize.is_some() {
+            self.summarize = other.summarize;
+        }
+        if other.security_audit.is_some() {
+            self.security_audit = other.security_audit;
+        }
+        if other.max_tokens.is_some() {
+            self.max_tokens = other.max_tokens;
+        }
+        self.api_keys.extend(other.api_keys);
+        self
+    }
+
+    /// Apply the `--provider` and `--model` command-line flags, which take
+    /// precedence over every config source. Call before [`Self::validate`]
+    /// so the settings actually used are the ones checked.
+    pub fn apply_cli_overrides(mut self, provider: Option<&str>, model: Option<&str>) -> Self {
+        if let Some(provider) = provider {
+            self.provider = Some(provider.to_string());
+        }
+        if let Some(model) = model {
+            self.model = Some(model.to_string());
+        }
         self
     }
 
@@ -184,9 +345,119 @@ impl ProjectConfig {
         if let Ok(val) = std::env::var("DEV_KILLER_SAVE_SESSIONS") {
             self.save_sessions = Some(parse_bool_env(&val));
         }
+        if let Ok(delay) = std::env::var("DEV_KILLER_ITERATION_DELAY_MS") {
+            match delay.parse() {
+                Ok(n) => self.iteration_delay_ms = Some(n),
+                Err(_) => warn!(
+                    value = %delay,
+                    "invalid DEV_KILLER_ITERATION_DELAY_MS value, ignoring"
+                ),
+            }
+        }
         self
     }
 
+    /// Check settings that parse but can't work, reporting every problem
+    /// rather than just the first
+    pub fn validate(&self) -> std::result::Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if let Some(provider) = &self.provider {
+            if !KNOWN_PROVIDERS.contains(&provider.as_str()) {
+                errors.push(ConfigError::new(
+                    "provider",
+                    format!(
+                        "unknown provider '{}' (expected one of: {})",
+                        provider,
+                        KNOWN_PROVIDERS.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        let must_be_positive = [
+            ("max_tokens", self.max_tokens.map(u64::from)),
+            ("test_concurrency", self.test_concurrency.map(|n| n as u64)),
+            ("token_budget", self.token_budget.map(|n| n as u64)),
+            ("policy.shell_timeout_secs", self.policy.shell_timeout_secs),
+            (
+                "policy.shell_max_timeout_secs",
+                self.policy.shell_max_timeout_secs,
+            ),
+            (
+                "policy.search_timeout_secs",
+                self.policy.search_timeout_secs,
+            ),
+            ("policy.run_timeout_secs", self.policy.run_timeout_secs),
+        ];
+        for (field, value) in must_be_positive {
+            if value == Some(0) {
+                errors.push(ConfigError::new(field, "must be greater than 0"));
+            }
+        }
+        if let (Some(timeout), Some(max)) = (
+            self.policy.shell_timeout_secs,
+            self.policy.shell_max_timeout_secs,
+        ) {
+            if timeout > max {
+                errors.push(ConfigError::new(
+                    "policy.shell_timeout_secs",
+                    format!(
+                        "{} exceeds policy.shell_max_timeout_secs ({})",
+                        timeout, max
+                    ),
+                ));
+            }
+        }
+
+        // Allowed paths are matched against canonical paths, so one that
+        // doesn't exist can never match
+        for path in &self.policy.allow_paths {
+            if !Path::new(path).exists() {
+                errors.push(ConfigError::new(
+                    "policy.allow_paths",
+                    format!("'{}' does not exist", path),
+                ));
+            }
+        }
+
+        // Deny entries are substring/prefix matches: an empty one matches
+        // everything
+        for (field, entries) in [
+            ("policy.deny_commands", &self.policy.deny_commands),
+            ("policy.deny_paths", &self.policy.deny_paths),
+        ] {
+            if entries.iter().any(|entry| entry.trim().is_empty()) {
+                errors.push(ConfigError::new(
+                    field,
+                    "contains an empty entry, which would match everything",
+                ));
+            }
+        }
+
+        for key in self.session.metadata.keys() {
+            if RESERVED_METADATA_KEYS.contains(&key.as_str()) {
+                errors.push(ConfigError::new(
+                    "session.metadata",
+                    format!("'{}' is a reserved key", key),
+                ));
+            }
+        }
+
+        if let Some(name) = self.policy.protected_shell_env_var() {
+            errors.push(ConfigError::new(
+                "policy.shell_env",
+                format!("'{}' is protected and can't be overridden", name),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Get simple_mode value (defaults to false)
     pub fn is_simple_mode(&self) -> bool {
         self.simple_mode.unwrap_or(false)
@@ -196,9 +467,376 @@ impl ProjectConfig {
     pub fn is_save_sessions(&self) -> bool {
         self.save_sessions.unwrap_or(false)
     }
+
+    /// Agent loop settings derived from this config
+    pub fn agent_loop_config(&self) -> AgentLoopConfig {
+        let mut loop_config = AgentLoopConfig::default();
+        if let Some(delay_ms) = self.iteration_delay_ms {
+            loop_config.inter_request_delay = Duration::from_millis(delay_ms);
+        }
+        if let Some(compact) = self.compact_tool_results {
+            loop_config.compact_repeated_tool_results = compact;
+        }
+        if let Some(budget) = self.token_budget {
+            loop_config.token_budget = Some(budget);
+        }
+        if let Some(retries) = self.policy.tool_retries {
+            loop_config.tool_retries = retries;
+        }
+        loop_config
+    }
+
+    /// Retry policy for transient LLM API failures, from `max_retries` and
+    /// `retry_delay_ms`
+    pub fn retry_config(&self) -> RetryConfig {
+        RetryConfig::new(self.max_retries, self.retry_delay_ms)
+    }
+
+    /// The API key for a provider: its environment variable if set,
+    /// otherwise the key from the global config
+    pub fn api_key(&self, provider: &str) -> Option<String> {
+        self.api_key_with_env(provider, |var| std::env::var(var).ok())
+    }
+
+    fn api_key_with_env(
+        &self,
+        provider: &str,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        api_key_env_var(provider)
+            .and_then(env)
+            .filter(|key| !key.is_empty())
+            .or_else(|| self.api_keys.get(provider).cloned())
+    }
+
+    /// Merge the configured session metadata into a newly created session
+    pub fn apply_session_defaults(&self, session: &mut SessionState) {
+        for (key, value) in &self.session.metadata {
+            session.set_metadata(key, value);
+        }
+    }
 }
 
 /// Parse a boolean-like environment variable value
 fn parse_bool_env(val: &str) -> bool {
     !matches!(val.to_lowercase().as_str(), "false" | "0" | "no" | "off")
 }
+
+/// Providers the CLI can create
+const KNOWN_PROVIDERS: &[&str] = &["anthropic", "openai", "gemini", "ollama"];
+
+/// Environment variable holding a provider's API key
+fn api_key_env_var(provider: &str) -> Option<&'static str> {
+    match provider {
+        "anthropic" => Some("ANTHROPIC_API_KEY"),
+        "openai" => Some("OPENAI_API_KEY"),
+        "gemini" => Some("GEMINI_API_KEY"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InvalidConfig;
+
+    #[test]
+    fn session_metadata_from_config_applies_to_new_session() {
+        let config: ProjectConfig = toml::from_str(
+            r#"
+            [session.metadata]
+            team = "platform"
+            "#,
+        )
+        .unwrap();
+
+        let mut session = SessionState::new("task", ".");
+        config.apply_session_defaults(&mut session);
+
+        assert_eq!(
+            session.metadata.get("team").map(String::as_str),
+            Some("platform")
+        );
+    }
+
+    #[test]
+    fn iteration_delay_zero_disables_loop_delay() {
+        let config: ProjectConfig = toml::from_str("iteration_delay_ms = 0").unwrap();
+        assert!(config.agent_loop_config().inter_request_delay.is_zero());
+    }
+
+    #[test]
+    fn policy_tool_retries_reach_the_loop_config() {
+        let config: ProjectConfig = toml::from_str("[policy]\ntool_retries = 3").unwrap();
+        assert_eq!(config.agent_loop_config().tool_retries, 3);
+        assert_eq!(ProjectConfig::default().agent_loop_config().tool_retries, 0);
+    }
+
+    #[test]
+    fn token_budget_reaches_the_loop_config() {
+        let config: ProjectConfig = toml::from_str("token_budget = 100000").unwrap();
+        assert_eq!(config.agent_loop_config().token_budget, Some(100_000));
+    }
+
+    #[test]
+    fn merge_session_metadata_prefers_more_specific_config() {
+        let global: ProjectConfig = toml::from_str(
+            r#"
+            [session.metadata]
+            team = "platform"
+            repo = "global"
+            "#,
+        )
+        .unwrap();
+        let project: ProjectConfig = toml::from_str(
+            r#"
+            [session.metadata]
+            repo = "dev-killer"
+            "#,
+        )
+        .unwrap();
+
+        let merged = ProjectConfig::default().merge(global).merge(project);
+
+        assert_eq!(merged.session.metadata["team"], "platform");
+        assert_eq!(merged.session.metadata["repo"], "dev-killer");
+    }
+
+    #[test]
+    fn project_config_can_narrow_the_allowed_interpreters() {
+        let global: ProjectConfig = toml::from_str(
+            r#"
+            [policy]
+            allowed_shell_interpreters = ["bash", "python3"]
+            "#,
+        )
+        .unwrap();
+        let project: ProjectConfig = toml::from_str(
+            r#"
+            [policy]
+            allowed_shell_interpreters = ["bash"]
+            "#,
+        )
+        .unwrap();
+
+        let merged = ProjectConfig::default()
+            .merge(global.clone())
+            .merge(project);
+        assert_eq!(merged.policy.allowed_shell_interpreters(), ["bash"]);
+
+        // A config that doesn't set the list keeps the wider one
+        let merged = ProjectConfig::default()
+            .merge(global)
+            .merge(ProjectConfig::default());
+        assert_eq!(
+            merged.policy.allowed_shell_interpreters(),
+            ["bash", "python3"]
+        );
+    }
+
+    #[test]
+    fn config_api_key_is_used_when_env_var_is_absent() {
+        let config: ProjectConfig = toml::from_str(
+            r#"
+            [api_keys]
+            anthropic = "sk-from-config"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.api_key_with_env("anthropic", |_| None).as_deref(),
+            Some("sk-from-config")
+        );
+        assert_eq!(
+            config
+                .api_key_with_env("anthropic", |_| Some("sk-from-env".into()))
+                .as_deref(),
+            Some("sk-from-env")
+        );
+        assert_eq!(config.api_key_with_env("openai", |_| None), None);
+    }
+
+    #[test]
+    fn gemini_key_is_read_from_its_env_var() {
+        let config = ProjectConfig::default();
+        let key = config.api_key_with_env("gemini", |var| {
+            (var == "GEMINI_API_KEY").then(|| "from-env".to_string())
+        });
+        assert_eq!(key.as_deref(), Some("from-env"));
+    }
+
+    #[test]
+    fn project_config_api_keys_are_ignored() {
+        let project: ProjectConfig = toml::from_str(
+            r#"
+            [api_keys]
+            anthropic = "sk-committed-by-mistake"
+            "#,
+        )
+        .unwrap();
+
+        let config =
+            ProjectConfig::default().merge(project.without_api_keys(Path::new("dev-killer.toml")));
+        assert!(config.api_keys.is_empty());
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(ProjectConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: ProjectConfig = toml::from_str(&format!(
+            r#"
+            provider = "bedrock"
+            max_tokens = 0
+
+            [policy]
+            allow_paths = ["{}", "/no/such/dir"]
+            deny_commands = ["git push", ""]
+            shell_timeout_secs = 600
+            shell_max_timeout_secs = 60
+            "#,
+            dir.path().display()
+        ))
+        .unwrap();
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+
+        assert_eq!(
+            fields,
+            [
+                "provider",
+                "max_tokens",
+                "policy.shell_timeout_secs",
+                "policy.allow_paths",
+                "policy.deny_commands",
+            ]
+        );
+        assert!(errors[0].message.contains("unknown provider 'bedrock'"));
+        assert!(errors[3].message.contains("/no/such/dir"));
+    }
+
+    #[test]
+    fn cli_provider_is_validated_instead_of_the_configured_one() {
+        let config = ProjectConfig {
+            provider: Some("bedrock".to_string()),
+            ..ProjectConfig::default()
+        };
+
+        assert!(
+            config
+                .apply_cli_overrides(Some("openai"), None)
+                .validate()
+                .is_ok()
+        );
+        let errors = ProjectConfig::default()
+            .apply_cli_overrides(Some("bedrock"), None)
+            .validate()
+            .unwrap_err();
+        assert_eq!(errors[0].field, "provider");
+    }
+
+    #[test]
+    fn relative_allow_paths_are_relative_to_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let path = dir.path().join("dev-killer.toml");
+        std::fs::write(&path, "[policy]\nallow_paths = [\"src\", \"/tmp\"]").unwrap();
+
+        let config = ProjectConfig::load_from_file(&path).unwrap();
+
+        assert_eq!(
+            config.policy.allow_paths,
+            [
+                std::fs::canonicalize(dir.path().join("src"))
+                    .unwrap()
+                    .to_string_lossy(),
+                "/tmp".into()
+            ]
+        );
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn bad_types_fail_to_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dev-killer.toml");
+        std::fs::write(&path, "max_retries = \"abc\"").unwrap();
+
+        let err = ProjectConfig::load_from_file(&path).unwrap_err();
+
+        assert!(format!("{:#}", err).contains("max_retries"));
+    }
+
+    #[test]
+    fn invalid_config_lists_every_violation() {
+        let err = InvalidConfig(vec![
+            ConfigError::new("provider", "unknown provider 'bedrock'"),
+            ConfigError::new("max_tokens", "must be greater than 0"),
+        ]);
+
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration:\n  - provider: unknown provider 'bedrock'\n  - max_tokens: must be greater than 0"
+        );
+    }
+
+    #[test]
+    fn zero_max_tokens_is_rejected() {
+        let config: ProjectConfig = toml::from_str("max_tokens = 0").unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::new(
+                "max_tokens",
+                "must be greater than 0"
+            )])
+        );
+
+        let config: ProjectConfig = toml::from_str("max_tokens = 32000").unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn shell_env_rejects_protected_variables() {
+        let config: ProjectConfig = toml::from_str(
+            "[policy.shell_env]\nDATABASE_URL = \"postgres://localhost/app\"\nOPENAI_API_KEY = \"sk-x\"",
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::new(
+                "policy.shell_env",
+                "'OPENAI_API_KEY' is protected and can't be overridden"
+            )])
+        );
+    }
+
+    #[test]
+    fn session_metadata_rejects_reserved_keys() {
+        let config: ProjectConfig =
+            toml::from_str("[session.metadata]\nteam = \"infra\"\nlabels = \"urgent\"").unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::new(
+                "session.metadata",
+                "'labels' is a reserved key"
+            )])
+        );
+    }
+
+    #[test]
+    fn retry_settings_reach_the_retry_config() {
+        let config: ProjectConfig =
+            toml::from_str("max_retries = 5\nretry_delay_ms = 250").unwrap();
+
+        let retry = config.retry_config();
+
+        assert_eq!(retry.max_retries, 5);
+        assert_eq!(retry.base_delay, Duration::from_millis(250));
+    }
+}
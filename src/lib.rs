@@ -5,16 +5,30 @@ pub mod runtime;
 pub mod session;
 pub mod tools;
 
-pub use agents::{Agent, CoderAgent, OrchestratorAgent};
-pub use config::{Policy, ProjectConfig};
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use agents::{
+    Agent, AgentBuilder, AgentLoopConfig, ApprovalDetector, CoderAgent, OrchestratorAgent,
+    ReviewOutcome, RunCancelled, RunContext, SecurityAuditAgent, SessionHandle, SkipCondition,
+    TesterAgent,
+};
+pub use config::{ConfigError, InvalidConfig, Policy, PolicyViolation, ProjectConfig};
 pub use llm::{
-    AnthropicProvider, LlmProvider, LlmResponse, Message, MessageRole, OpenAIProvider, RetryConfig,
-    ToolCall, ToolResult,
+    AnthropicProvider, GeminiProvider, LlmProvider, LlmResponse, Message, MessageRole,
+    MessageSummarizer, MissingApiKey, NaiveSummarizer, OllamaProvider, OpenAIProvider, RetryConfig,
+    SystemPromptStyle, TokenTotals, TokenUsage, ToolCall, ToolResult,
 };
-pub use runtime::Executor;
+pub use runtime::{Executor, RunTimedOut, SESSION_LOCK_STALE_AFTER, TaskRequest};
+#[cfg(feature = "postgres")]
+pub use session::PostgresStorage;
 pub use session::{
-    SessionPhase, SessionState, SessionStatus, SessionSummary, SqliteStorage, Storage,
+    AgentConversation, MemoryStorage, Pipeline, PolicyDenial, RESERVED_METADATA_KEYS,
+    SessionBudget, SessionBusy, SessionEvent, SessionFilter, SessionPhase, SessionState,
+    SessionStatus, SessionSummary, SqliteStorage, Storage,
 };
 pub use tools::{
-    EditFileTool, GlobTool, GrepTool, ReadFileTool, ShellTool, Tool, ToolRegistry, WriteFileTool,
+    AskUserTool, CommandOutput, DiffTool, EditFileTool, FileStatTool, GlobTool, GrepTool,
+    HttpFetchTool, ListDirectoryTool, MoveFileTool, ReadFileTool, ShellTool, Tool, ToolRegistry,
+    TransientToolError, TreeTool, UserInputRequest, WatchFileTool, WriteFileTool,
 };
@@ -1,26 +1,52 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use llm::builder::{LLMBackend, LLMBuilder};
-use llm::chat::{ChatMessage, ChatRole, FunctionTool, MessageType, Tool as LlmTool};
+use llm::chat::{
+    ChatMessage, ChatRole, FunctionTool, MessageType, StructuredOutputFormat, Tool as LlmTool,
+};
+use serde_json::Value;
 use tokio::time::{Duration, timeout};
 use tracing::warn;
 
-use super::{LlmProvider, LlmResponse, Message, MessageRole, ToolCall};
+use super::{
+    LlmProvider, LlmResponse, Message, MessageRole, MissingApiKey, RetryConfig, SystemPromptStyle,
+    TokenUsage, ToolCall, parse_json_response, retry_with_backoff,
+};
 use crate::tools::Tool;
 
 const DEFAULT_MAX_TOKENS: u32 = 8192;
 const API_TIMEOUT_SECS: u64 = 120;
 
 /// Parameters for the shared LLM chat implementation
+#[derive(Clone)]
 struct ChatParams<'a> {
     backend: LLMBackend,
     provider_name: &'a str,
     api_key: &'a str,
+    /// Server to talk to instead of the backend's default endpoint
+    base_url: Option<&'a str>,
     model: &'a str,
     max_tokens: u32,
     system: &'a str,
+    system_prompt_style: SystemPromptStyle,
     messages: &'a [Message],
     tools: &'a [&'a dyn Tool],
+    /// Constrain the response to this JSON schema (JSON mode)
+    json_schema: Option<&'a Value>,
+}
+
+/// Run `chat_impl`, retrying transient failures when a retry policy is set
+async fn chat_with_retry(
+    retry: Option<&RetryConfig>,
+    params: ChatParams<'_>,
+) -> Result<LlmResponse> {
+    match retry {
+        Some(retry) => {
+            let operation = format!("{} chat", params.provider_name);
+            retry_with_backoff(retry, &operation, || chat_impl(params.clone())).await
+        }
+        None => chat_impl(params).await,
+    }
 }
 
 /// Shared implementation for LLM providers backed by the `llm` crate.
@@ -29,11 +55,14 @@ async fn chat_impl(params: ChatParams<'_>) -> Result<LlmResponse> {
         backend,
         provider_name,
         api_key,
+        base_url,
         model,
         max_tokens,
         system,
+        system_prompt_style,
         messages,
         tools,
+        json_schema,
     } = params;
     // Convert tools to llm crate format
     let llm_tools: Vec<LlmTool> = tools
@@ -52,10 +81,26 @@ async fn chat_impl(params: ChatParams<'_>) -> Result<LlmResponse> {
     // tools to be set at build time. This is a known inefficiency for tool-heavy workloads.
     let mut builder = LLMBuilder::new()
         .backend(backend)
-        .api_key(api_key)
         .model(model)
-        .system(system)
         .max_tokens(max_tokens);
+    // Local backends such as Ollama take no key
+    if !api_key.is_empty() {
+        builder = builder.api_key(api_key);
+    }
+    if let Some(base_url) = base_url {
+        builder = builder.base_url(base_url);
+    }
+    if system_prompt_style == SystemPromptStyle::System {
+        builder = builder.system(system);
+    }
+    if let Some(schema) = json_schema {
+        builder = builder.schema(StructuredOutputFormat {
+            name: "response".to_string(),
+            description: None,
+            schema: Some(schema.clone()),
+            strict: Some(true),
+        });
+    }
 
     for tool in &llm_tools {
         builder = builder.function(
@@ -68,7 +113,10 @@ async fn chat_impl(params: ChatParams<'_>) -> Result<LlmResponse> {
     let llm = builder.build().context("failed to build LLM client")?;
 
     // Convert our messages to llm crate format
-    let chat_messages: Vec<ChatMessage> = messages.iter().filter_map(convert_message).collect();
+    let mut chat_messages: Vec<ChatMessage> = messages.iter().filter_map(convert_message).collect();
+    if system_prompt_style == SystemPromptStyle::FirstUserMessage {
+        prepend_system_prompt(system, &mut chat_messages);
+    }
 
     // Call the LLM with timeout
     let api_timeout = Duration::from_secs(API_TIMEOUT_SECS);
@@ -100,19 +148,7 @@ async fn chat_impl(params: ChatParams<'_>) -> Result<LlmResponse> {
             calls
                 .iter()
                 .map(|tc| {
-                    let arguments = match serde_json::from_str(&tc.function.arguments) {
-                        Ok(args) => args,
-                        Err(e) => {
-                            warn!(
-                                tool = %tc.function.name,
-                                error = %e,
-                                "failed to parse tool call arguments as JSON, returning error object"
-                            );
-                            serde_json::json!({
-                                "error": format!("Failed to parse arguments: {}", e)
-                            })
-                        }
-                    };
+                    let arguments = parse_tool_arguments(&tc.function.name, &tc.function.arguments);
                     ToolCall {
                         id: tc.id.clone(),
                         name: tc.function.name.clone(),
@@ -131,12 +167,77 @@ async fn chat_impl(params: ChatParams<'_>) -> Result<LlmResponse> {
         String::new()
     });
 
+    let usage = response.usage().map(|usage| TokenUsage {
+        input_tokens: usage.prompt_tokens,
+        output_tokens: usage.completion_tokens,
+        cache_read_tokens: usage
+            .prompt_tokens_details
+            .and_then(|details| details.cached_tokens),
+    });
     Ok(LlmResponse {
         message: Message::assistant(content),
         tool_calls,
+        usage,
     })
 }
 
+/// Parse the raw JSON arguments of a native tool call.
+///
+/// An empty argument string means the model sent no arguments at all; it is
+/// surfaced as `null` so the runner can report it clearly.
+fn parse_tool_arguments(tool_name: &str, raw: &str) -> serde_json::Value {
+    if raw.trim().is_empty() {
+        return serde_json::Value::Null;
+    }
+
+    match serde_json::from_str(raw) {
+        Ok(args) => args,
+        Err(e) => {
+            warn!(
+                tool = %tool_name,
+                error = %e,
+                "failed to parse tool call arguments as JSON, returning error object"
+            );
+            serde_json::json!({
+                "error": format!("Failed to parse arguments: {}", e)
+            })
+        }
+    }
+}
+
+/// Fold the system prompt into the conversation for models that don't take
+/// a separate one. It is merged into a leading plain-text user message so the
+/// conversation doesn't start with two user turns.
+fn prepend_system_prompt(system: &str, messages: &mut Vec<ChatMessage>) {
+    if system.is_empty() {
+        return;
+    }
+
+    match messages.first_mut() {
+        Some(first)
+            if matches!(first.role, ChatRole::User)
+                && matches!(first.message_type, MessageType::Text) =>
+        {
+            first.content = format!("{}\n\n{}", system, first.content);
+        }
+        _ => messages.insert(
+            0,
+            ChatMessage {
+                role: ChatRole::User,
+                message_type: MessageType::Text,
+                content: system.to_string(),
+            },
+        ),
+    }
+}
+
+/// Whether an OpenAI model is an o-series reasoning model, which handles
+/// system prompts differently from chat models
+fn is_openai_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
 /// Convert our Message to the llm crate's ChatMessage format
 fn convert_message(msg: &Message) -> Option<ChatMessage> {
     match msg.role {
@@ -190,28 +291,73 @@ fn convert_message(msg: &Message) -> Option<ChatMessage> {
     }
 }
 
+/// Read a provider's API key, failing with [`MissingApiKey`] if it is unset
+/// or empty
+fn api_key_from_env(provider: &str, env_var: &str) -> Result<String> {
+    match std::env::var(env_var) {
+        Ok(key) if !key.is_empty() => Ok(key),
+        _ => Err(MissingApiKey {
+            provider: provider.to_string(),
+            env_var: env_var.to_string(),
+        }
+        .into()),
+    }
+}
+
 /// Anthropic LLM provider using the llm crate
 pub struct AnthropicProvider {
     model: String,
     api_key: String,
     max_tokens: u32,
+    retry: Option<RetryConfig>,
 }
 
 impl AnthropicProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "claude-sonnet-4-20250514";
+
     /// Create a new Anthropic provider with the specified model
     pub fn new(model: impl Into<String>) -> Result<Self> {
-        let api_key = std::env::var("ANTHROPIC_API_KEY")
-            .context("ANTHROPIC_API_KEY environment variable not set")?;
+        let api_key = api_key_from_env("anthropic", "ANTHROPIC_API_KEY")?;
         Ok(Self {
             model: model.into(),
             api_key,
             max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
         })
     }
 
+    /// Create a provider with an explicit API key instead of reading
+    /// `ANTHROPIC_API_KEY`
+    pub fn with_api_key(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            api_key: api_key.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
+        }
+    }
+
+    /// Retry transient API failures (timeouts, rate limits, 5xx) with backoff
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Cap the length of each response (default: 8192 tokens)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Most tokens a single response may contain
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+
     /// Create a provider using Claude Sonnet
     pub fn sonnet() -> Result<Self> {
-        Self::new("claude-sonnet-4-20250514")
+        Self::new(Self::DEFAULT_MODEL)
     }
 
     /// Create a provider using Claude Haiku
@@ -232,16 +378,22 @@ impl LlmProvider for AnthropicProvider {
         messages: &[Message],
         tools: &[&dyn Tool],
     ) -> Result<LlmResponse> {
-        chat_impl(ChatParams {
-            backend: LLMBackend::Anthropic,
-            provider_name: "Anthropic",
-            api_key: &self.api_key,
-            model: &self.model,
-            max_tokens: self.max_tokens,
-            system,
-            messages,
-            tools,
-        })
+        chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::Anthropic,
+                provider_name: "Anthropic",
+                api_key: &self.api_key,
+                base_url: None,
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools,
+                json_schema: None,
+            },
+        )
         .await
     }
 }
@@ -251,23 +403,55 @@ pub struct OpenAIProvider {
     model: String,
     api_key: String,
     max_tokens: u32,
+    retry: Option<RetryConfig>,
 }
 
 impl OpenAIProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "gpt-4o";
+
     /// Create a new OpenAI provider with the specified model
     pub fn new(model: impl Into<String>) -> Result<Self> {
-        let api_key = std::env::var("OPENAI_API_KEY")
-            .context("OPENAI_API_KEY environment variable not set")?;
+        let api_key = api_key_from_env("openai", "OPENAI_API_KEY")?;
         Ok(Self {
             model: model.into(),
             api_key,
             max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
         })
     }
 
+    /// Create a provider with an explicit API key instead of reading
+    /// `OPENAI_API_KEY`
+    pub fn with_api_key(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            api_key: api_key.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
+        }
+    }
+
+    /// Retry transient API failures (timeouts, rate limits, 5xx) with backoff
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Cap the length of each response (default: 8192 tokens)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Most tokens a single response may contain
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+
     /// Create a provider using GPT-4o
     pub fn gpt4o() -> Result<Self> {
-        Self::new("gpt-4o")
+        Self::new(Self::DEFAULT_MODEL)
     }
 
     /// Create a provider using GPT-4o-mini
@@ -282,22 +466,373 @@ impl LlmProvider for OpenAIProvider {
         "openai"
     }
 
+    fn system_prompt_style(&self) -> SystemPromptStyle {
+        if is_openai_reasoning_model(&self.model) {
+            SystemPromptStyle::FirstUserMessage
+        } else {
+            SystemPromptStyle::System
+        }
+    }
+
     async fn chat(
         &self,
         system: &str,
         messages: &[Message],
         tools: &[&dyn Tool],
     ) -> Result<LlmResponse> {
-        chat_impl(ChatParams {
-            backend: LLMBackend::OpenAI,
-            provider_name: "OpenAI",
-            api_key: &self.api_key,
-            model: &self.model,
-            max_tokens: self.max_tokens,
-            system,
-            messages,
-            tools,
-        })
+        chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::OpenAI,
+                provider_name: "OpenAI",
+                api_key: &self.api_key,
+                base_url: None,
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools,
+                json_schema: None,
+            },
+        )
         .await
     }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    async fn chat_structured(
+        &self,
+        system: &str,
+        messages: &[Message],
+        schema: &Value,
+    ) -> Result<Value> {
+        let response = chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::OpenAI,
+                provider_name: "OpenAI",
+                api_key: &self.api_key,
+                base_url: None,
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools: &[],
+                json_schema: Some(schema),
+            },
+        )
+        .await?;
+        parse_json_response(&response.message.content)
+    }
+}
+
+/// Google Gemini LLM provider using the llm crate
+pub struct GeminiProvider {
+    model: String,
+    api_key: String,
+    max_tokens: u32,
+    retry: Option<RetryConfig>,
+}
+
+impl GeminiProvider {
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "gemini-1.5-flash";
+
+    /// Create a new Gemini provider with the specified model
+    pub fn new(model: impl Into<String>) -> Result<Self> {
+        let api_key = api_key_from_env("gemini", "GEMINI_API_KEY")?;
+        Ok(Self::with_api_key(api_key, model))
+    }
+
+    /// Create a provider with an explicit API key instead of reading
+    /// `GEMINI_API_KEY`
+    pub fn with_api_key(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            api_key: api_key.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
+        }
+    }
+
+    /// Retry transient API failures (timeouts, rate limits, 5xx) with backoff
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Cap the length of each response (default: 8192 tokens)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Most tokens a single response may contain
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+
+    /// Create a provider using Gemini 1.5 Flash
+    pub fn flash() -> Result<Self> {
+        Self::new(Self::DEFAULT_MODEL)
+    }
+
+    /// Create a provider using Gemini 1.5 Pro
+    pub fn pro() -> Result<Self> {
+        Self::new("gemini-1.5-pro")
+    }
+}
+
+#[async_trait]
+impl LlmProvider for GeminiProvider {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    async fn chat(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[&dyn Tool],
+    ) -> Result<LlmResponse> {
+        chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::Google,
+                provider_name: "Gemini",
+                api_key: &self.api_key,
+                base_url: None,
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools,
+                json_schema: None,
+            },
+        )
+        .await
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    async fn chat_structured(
+        &self,
+        system: &str,
+        messages: &[Message],
+        schema: &Value,
+    ) -> Result<Value> {
+        let response = chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::Google,
+                provider_name: "Gemini",
+                api_key: &self.api_key,
+                base_url: None,
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools: &[],
+                json_schema: Some(schema),
+            },
+        )
+        .await?;
+        parse_json_response(&response.message.content)
+    }
+}
+
+/// Model served locally by Ollama, for offline use without an API key
+pub struct OllamaProvider {
+    base_url: String,
+    model: String,
+    max_tokens: u32,
+    retry: Option<RetryConfig>,
+}
+
+impl OllamaProvider {
+    /// Where `ollama serve` listens by default
+    pub const DEFAULT_BASE_URL: &'static str = "http://localhost:11434";
+
+    /// Model used when none is configured
+    pub const DEFAULT_MODEL: &'static str = "llama3.1";
+
+    /// Create a provider for `model` on the default local server. Unlike the
+    /// cloud providers this reads no environment variables and can't fail.
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            base_url: Self::DEFAULT_BASE_URL.to_string(),
+            model: model.into(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
+        }
+    }
+
+    /// Talk to an Ollama server other than `http://localhost:11434`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Retry transient failures (timeouts, 5xx) with backoff
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Cap the length of each response (default: 8192 tokens)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Most tokens a single response may contain
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    async fn chat(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[&dyn Tool],
+    ) -> Result<LlmResponse> {
+        chat_with_retry(
+            self.retry.as_ref(),
+            ChatParams {
+                backend: LLMBackend::Ollama,
+                provider_name: "Ollama",
+                api_key: "",
+                base_url: Some(&self.base_url),
+                model: &self.model,
+                max_tokens: self.max_tokens,
+                system,
+                system_prompt_style: self.system_prompt_style(),
+                messages,
+                tools,
+                json_schema: None,
+            },
+        )
+        .await
+    }
+
+    // Structured output is left at the default (prompted JSON): support for
+    // JSON mode varies across local models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn openai(model: &str) -> OpenAIProvider {
+        OpenAIProvider {
+            model: model.to_string(),
+            api_key: String::new(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn reasoning_models_fold_system_prompt_into_first_user_message() {
+        assert_eq!(
+            openai("o3-mini").system_prompt_style(),
+            SystemPromptStyle::FirstUserMessage
+        );
+        assert_eq!(
+            openai("gpt-4o").system_prompt_style(),
+            SystemPromptStyle::System
+        );
+
+        let mut messages: Vec<ChatMessage> = [Message::user("fix the bug")]
+            .iter()
+            .filter_map(convert_message)
+            .collect();
+        prepend_system_prompt("You are a coder.", &mut messages);
+
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].role, ChatRole::User));
+        assert_eq!(messages[0].content, "You are a coder.\n\nfix the bug");
+    }
+
+    #[test]
+    fn system_prompt_gets_its_own_message_before_non_text_turns() {
+        let mut messages: Vec<ChatMessage> = [Message::tool_result("call_1", "ok")]
+            .iter()
+            .filter_map(convert_message)
+            .collect();
+        prepend_system_prompt("You are a coder.", &mut messages);
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0].message_type, MessageType::Text));
+        assert_eq!(messages[0].content, "You are a coder.");
+    }
+
+    #[test]
+    fn missing_api_key_is_a_typed_error() {
+        let err = api_key_from_env("anthropic", "DEV_KILLER_TEST_UNSET_API_KEY").unwrap_err();
+
+        let missing = err.downcast_ref::<MissingApiKey>().unwrap();
+        assert_eq!(missing.provider, "anthropic");
+        assert_eq!(missing.env_var, "DEV_KILLER_TEST_UNSET_API_KEY");
+        assert!(
+            err.to_string()
+                .starts_with("DEV_KILLER_TEST_UNSET_API_KEY not set")
+        );
+    }
+
+    #[test]
+    fn gemini_provider_uses_flash_by_default() {
+        let provider = GeminiProvider::with_api_key("key", GeminiProvider::DEFAULT_MODEL);
+        assert_eq!(provider.name(), "gemini");
+        assert_eq!(provider.model, "gemini-1.5-flash");
+    }
+
+    #[test]
+    fn ollama_provider_needs_no_api_key() {
+        // No API key or other env var involved, so construction can't fail
+        let provider = OllamaProvider::new(OllamaProvider::DEFAULT_MODEL);
+        assert_eq!(provider.name(), "ollama");
+        assert_eq!(provider.base_url, "http://localhost:11434");
+
+        let provider = provider.with_base_url("http://gpu-box:11434");
+        assert_eq!(provider.base_url, "http://gpu-box:11434");
+    }
+
+    #[tokio::test]
+    #[ignore = "calls the Gemini API; needs GEMINI_API_KEY"]
+    async fn gemini_live_chat() {
+        let provider = GeminiProvider::flash().unwrap();
+        let response = provider
+            .chat("Reply with one word.", &[Message::user("Say hello")], &[])
+            .await
+            .unwrap();
+        assert!(!response.message.content.trim().is_empty());
+    }
+
+    #[test]
+    fn max_tokens_can_be_overridden() {
+        let provider = AnthropicProvider::with_api_key("key", "model").with_max_tokens(32_000);
+        assert_eq!(provider.max_tokens(), 32_000);
+
+        let provider = OpenAIProvider::with_api_key("key", "model").with_max_tokens(16_000);
+        assert_eq!(provider.max_tokens(), 16_000);
+
+        let provider = OpenAIProvider::with_api_key("key", "model");
+        assert_eq!(provider.max_tokens(), DEFAULT_MAX_TOKENS);
+    }
 }
@@ -0,0 +1,225 @@
+use super::{Message, MessageRole};
+
+/// Heading the summary of pruned messages is filed under in the first message
+const PRUNED_HEADING: &str = "## Earlier conversation (pruned to fit the token budget)";
+
+/// Longest excerpt of an assistant message kept by [`NaiveSummarizer`]
+const MAX_EXCERPT_CHARS: usize = 200;
+
+/// Turns messages pruned from a conversation into a short text that stands
+/// in for them
+pub trait MessageSummarizer: Send + Sync {
+    /// Summarize `messages`, which are whole round-trips in order
+    fn summarize(&self, messages: &[Message]) -> String;
+}
+
+/// Summarizer that keeps what the model said and which tools it called,
+/// and drops the tool output, which is usually the bulk of a conversation
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NaiveSummarizer;
+
+impl MessageSummarizer for NaiveSummarizer {
+    fn summarize(&self, messages: &[Message]) -> String {
+        let mut lines = Vec::new();
+        for message in messages {
+            match message.role {
+                MessageRole::Assistant => {
+                    let text = message.content.trim();
+                    if !text.is_empty() {
+                        lines.push(format!("- You said: {}", excerpt(text)));
+                    }
+                    for call in &message.tool_calls {
+                        lines.push(format!("- You called {} {}", call.name, call.arguments));
+                    }
+                }
+                MessageRole::User => lines.push(format!("- User: {}", excerpt(&message.content))),
+                // Tool output is what gets dropped
+                MessageRole::Tool => {}
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// The first [`MAX_EXCERPT_CHARS`] characters of `text`, on one line
+fn excerpt(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() > MAX_EXCERPT_CHARS {
+        line.chars().take(MAX_EXCERPT_CHARS).collect::<String>() + "..."
+    } else {
+        line
+    }
+}
+
+/// Rough token count of a conversation, at about four characters per token
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    let chars: usize = messages
+        .iter()
+        .map(|m| {
+            m.content.len()
+                + m.tool_calls
+                    .iter()
+                    .map(|c| c.name.len() + c.arguments.to_string().len())
+                    .sum::<usize>()
+                + m.tool_result.as_ref().map_or(0, |r| r.result.len())
+        })
+        .sum();
+    chars.div_ceil(4)
+}
+
+/// Shrink a conversation until its estimated size fits in `budget` tokens,
+/// returning how many messages were removed.
+///
+/// The oldest round-trips (an assistant message and the tool results
+/// answering it) go first, so tool calls stay paired with their results.
+/// The first message (the task) and the most recent round-trip are always
+/// kept. What was removed is summarized into the first message, rather than
+/// a message of its own, so the conversation doesn't open with two user turns.
+pub fn prune_to_token_budget(
+    messages: &mut Vec<Message>,
+    budget: usize,
+    summarizer: &dyn MessageSummarizer,
+) -> usize {
+    if messages.len() < 2 || estimate_tokens(messages) <= budget {
+        return 0;
+    }
+
+    // Start of each round-trip after the first message
+    let starts: Vec<usize> = (1..messages.len())
+        .filter(|&i| messages[i].role != MessageRole::Tool)
+        .collect();
+
+    // Drop whole round-trips, oldest first, keeping the last one, until
+    // what's left fits along with the summary that replaces them
+    let mut end = 1;
+    for window in starts.windows(2) {
+        end = window[1];
+        // Estimated from the kept messages themselves: per-slice estimates
+        // round up, so they can't be subtracted from the total
+        let remaining = estimate_tokens(&messages[..1]) + estimate_tokens(&messages[end..]);
+        let summary = summarizer.summarize(&messages[1..end]);
+        if remaining + (PRUNED_HEADING.len() + summary.len() + 2).div_ceil(4) <= budget {
+            break;
+        }
+    }
+    if end == 1 {
+        return 0;
+    }
+
+    let removed: Vec<Message> = messages.drain(1..end).collect();
+    let summary = summarizer.summarize(&removed);
+    let first = &mut messages[0];
+    if !first.content.contains(PRUNED_HEADING) {
+        first.content = format!("{}\n\n{}", first.content, PRUNED_HEADING);
+    }
+    if !summary.is_empty() {
+        first.content = format!("{}\n{}", first.content, summary);
+    }
+    removed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ToolCall;
+    use serde_json::json;
+
+    /// A round-trip calling `tool` and getting `output` back
+    fn round_trip(id: &str, tool: &str, output: &str) -> [Message; 2] {
+        [
+            Message::assistant_with_tools(
+                "",
+                vec![ToolCall {
+                    id: id.to_string(),
+                    name: tool.to_string(),
+                    arguments: json!({ "path": "src/lib.rs" }),
+                }],
+            ),
+            Message::tool_result(id, output),
+        ]
+    }
+
+    fn long_conversation() -> Vec<Message> {
+        let mut messages = vec![Message::user("fix the bug")];
+        for i in 0..4 {
+            messages.extend(round_trip(&i.to_string(), "read_file", &"x".repeat(400)));
+        }
+        messages
+    }
+
+    #[test]
+    fn conversation_within_budget_is_untouched() {
+        let mut messages = long_conversation();
+        let before = messages.len();
+
+        assert_eq!(
+            prune_to_token_budget(&mut messages, 10_000, &NaiveSummarizer),
+            0
+        );
+        assert_eq!(messages.len(), before);
+    }
+
+    #[test]
+    fn oldest_round_trips_are_pruned_first() {
+        let mut messages = long_conversation();
+
+        // Each round-trip is ~110 tokens; the task, the last two and the
+        // summary of the rest fit
+        let removed = prune_to_token_budget(&mut messages, 300, &NaiveSummarizer);
+
+        assert_eq!(removed, 4);
+        assert_eq!(messages.len(), 5);
+        assert!(estimate_tokens(&messages) <= 300);
+        assert_eq!(messages[1].tool_calls[0].id, "2");
+        assert_eq!(messages[2].tool_result.as_ref().unwrap().tool_call_id, "2");
+        assert!(
+            messages[0]
+                .content
+                .starts_with("fix the bug\n\n## Earlier conversation")
+        );
+        assert!(messages[0].content.contains("You called read_file"));
+        assert!(!messages[0].content.contains("xxxx"));
+    }
+
+    #[test]
+    fn most_recent_round_trip_is_always_kept() {
+        let mut messages = long_conversation();
+
+        prune_to_token_budget(&mut messages, 1, &NaiveSummarizer);
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].tool_calls[0].id, "3");
+    }
+
+    #[test]
+    fn tiny_messages_and_budget_do_not_underflow() {
+        let mut messages = vec![Message::user("")];
+        for i in 0..5 {
+            let id = i.to_string();
+            messages.push(Message::assistant_with_tools(
+                "",
+                vec![ToolCall {
+                    id: id.clone(),
+                    name: "ls".to_string(),
+                    arguments: json!({}),
+                }],
+            ));
+            messages.push(Message::tool_result(&id, "x"));
+        }
+
+        let removed = prune_to_token_budget(&mut messages, 1, &NaiveSummarizer);
+        assert_eq!(removed, 8);
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn pruning_again_extends_the_existing_summary() {
+        let mut messages = long_conversation();
+        prune_to_token_budget(&mut messages, 300, &NaiveSummarizer);
+        messages.extend(round_trip("4", "grep", "match"));
+        prune_to_token_budget(&mut messages, 1, &NaiveSummarizer);
+
+        assert_eq!(messages[0].content.matches(PRUNED_HEADING).count(), 1);
+        assert_eq!(messages[0].content.matches("You called").count(), 4);
+    }
+}
@@ -0,0 +1,20 @@
+/// A provider's API key environment variable is not set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingApiKey {
+    /// Provider name (e.g. "anthropic")
+    pub provider: String,
+    /// Environment variable the key is read from
+    pub env_var: String,
+}
+
+impl std::fmt::Display for MissingApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} not set; export it or set `{}` under [api_keys] in the global config",
+            self.env_var, self.provider
+        )
+    }
+}
+
+impl std::error::Error for MissingApiKey {}
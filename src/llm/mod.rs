@@ -1,9 +1,16 @@
 mod anthropic;
+mod budget;
+mod error;
 mod message;
 mod provider;
 mod retry;
 
-pub use anthropic::{AnthropicProvider, OpenAIProvider};
+pub use anthropic::{AnthropicProvider, GeminiProvider, OllamaProvider, OpenAIProvider};
+pub use budget::{MessageSummarizer, NaiveSummarizer, estimate_tokens, prune_to_token_budget};
+pub use error::MissingApiKey;
 pub use message::{Message, MessageRole, ToolCall, ToolResult};
-pub use provider::{LlmProvider, LlmResponse};
+pub use provider::{
+    LlmProvider, LlmResponse, SystemPromptStyle, TokenTotals, TokenUsage, fallback_chat_structured,
+    parse_json_response,
+};
 pub use retry::{RetryConfig, is_retryable_error, retry_with_backoff};
@@ -1,5 +1,7 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::{Message, ToolCall};
 use crate::tools::Tool;
@@ -11,6 +13,55 @@ pub struct LlmResponse {
     pub message: Message,
     /// Tool calls requested by the LLM
     pub tool_calls: Vec<ToolCall>,
+    /// Tokens the call consumed, if the API reported usage
+    pub usage: Option<TokenUsage>,
+}
+
+/// Tokens consumed by one LLM call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens in the request (the prompt)
+    pub input_tokens: u32,
+    /// Tokens in the response
+    pub output_tokens: u32,
+    /// Input tokens served from the API's prompt cache, if it reports them
+    #[serde(default)]
+    pub cache_read_tokens: Option<u32>,
+}
+
+/// Tokens consumed by many LLM calls, summed in `u64` so long-running
+/// sessions can't overflow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenTotals {
+    /// Tokens in the requests
+    pub input_tokens: u64,
+    /// Tokens in the responses
+    pub output_tokens: u64,
+    /// Input tokens served from the API's prompt cache, if it reports them
+    #[serde(default)]
+    pub cache_read_tokens: Option<u64>,
+}
+
+impl TokenTotals {
+    /// Add one call's usage to the totals
+    pub fn add(&mut self, usage: TokenUsage) {
+        self.input_tokens += u64::from(usage.input_tokens);
+        self.output_tokens += u64::from(usage.output_tokens);
+        if let Some(cached) = usage.cache_read_tokens {
+            *self.cache_read_tokens.get_or_insert(0) += u64::from(cached);
+        }
+    }
+}
+
+/// Where a provider expects the system prompt to be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SystemPromptStyle {
+    /// Sent as the API's native system prompt
+    #[default]
+    System,
+    /// Folded into the first user message, for models that ignore or reject
+    /// a separate system prompt (e.g. OpenAI o-series reasoning models)
+    FirstUserMessage,
 }
 
 /// Trait for LLM providers
@@ -26,4 +77,144 @@ pub trait LlmProvider: Send + Sync {
 
     /// Get the provider name
     fn name(&self) -> &str;
+
+    /// How this provider's model wants the system prompt delivered
+    fn system_prompt_style(&self) -> SystemPromptStyle {
+        SystemPromptStyle::System
+    }
+
+    /// Whether the API can constrain responses to a JSON schema (JSON mode)
+    fn supports_structured_output(&self) -> bool {
+        false
+    }
+
+    /// Ask for a JSON response matching `schema`.
+    ///
+    /// The default is [`fallback_chat_structured`], for providers without
+    /// JSON mode. The result is not validated against the schema, so callers
+    /// should deserialize it into a concrete type.
+    async fn chat_structured(
+        &self,
+        system: &str,
+        messages: &[Message],
+        schema: &Value,
+    ) -> Result<Value> {
+        fallback_chat_structured(self, system, messages, schema).await
+    }
+}
+
+/// Structured output without JSON mode: describe the schema in the system
+/// prompt and parse the reply best-effort with [`parse_json_response`]
+pub async fn fallback_chat_structured<P: LlmProvider + ?Sized>(
+    provider: &P,
+    system: &str,
+    messages: &[Message],
+    schema: &Value,
+) -> Result<Value> {
+    let system = format!(
+        "{system}\n\nRespond with a single JSON value matching this JSON schema, \
+         with no other text:\n{}",
+        serde_json::to_string_pretty(schema)?
+    );
+    let response = provider.chat(&system, messages, &[]).await?;
+    parse_json_response(&response.message.content)
+}
+
+/// Extract a JSON value from a model reply.
+///
+/// Accepts bare JSON, JSON inside a Markdown code fence, or JSON surrounded
+/// by prose (the outermost `{...}` is used).
+pub fn parse_json_response(text: &str) -> Result<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Ok(value);
+    }
+
+    if let Some(fenced) = text
+        .split_once("```")
+        .and_then(|(_, rest)| rest.split_once("```"))
+        .map(|(inner, _)| inner.trim_start_matches("json").trim())
+        && let Ok(value) = serde_json::from_str(fenced)
+    {
+        return Ok(value);
+    }
+
+    let object = text
+        .find('{')
+        .zip(text.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &text[start..=end])
+        .context("response does not contain a JSON object")?;
+    serde_json::from_str(object).context("response contains malformed JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockLlmProvider, text_response};
+    use serde_json::json;
+
+    #[test]
+    fn parses_bare_fenced_and_embedded_json() {
+        let expected = json!({ "ok": true });
+        assert_eq!(parse_json_response(r#"{"ok": true}"#).unwrap(), expected);
+        assert_eq!(
+            parse_json_response("```json\n{\"ok\": true}\n```").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_json_response("Here you go: {\"ok\": true} Hope that helps.").unwrap(),
+            expected
+        );
+        assert!(parse_json_response("no json here").is_err());
+    }
+
+    #[test]
+    fn token_totals_grow_past_u32() {
+        let usage = TokenUsage {
+            input_tokens: u32::MAX,
+            output_tokens: 1,
+            cache_read_tokens: Some(u32::MAX),
+        };
+        let mut totals = TokenTotals::default();
+        totals.add(usage);
+        totals.add(usage);
+
+        assert_eq!(totals.input_tokens, 2 * u64::from(u32::MAX));
+        assert_eq!(totals.output_tokens, 2);
+        assert_eq!(totals.cache_read_tokens, Some(2 * u64::from(u32::MAX)));
+    }
+
+    #[tokio::test]
+    async fn structured_chat_falls_back_to_prompting() {
+        let provider = MockLlmProvider::with_responses(vec![text_response(
+            "Sure!\n```json\n{\"steps\": [\"a\"]}\n```",
+        )]);
+        assert!(!provider.supports_structured_output());
+
+        let value = provider
+            .chat_structured(
+                "system",
+                &[Message::user("plan")],
+                &json!({"type": "object"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value, json!({ "steps": ["a"] }));
+    }
+
+    #[tokio::test]
+    async fn structured_chat_uses_json_mode_when_supported() {
+        let provider = MockLlmProvider::with_responses(vec![text_response(r#"{"steps": ["a"]}"#)])
+            .with_structured_output();
+        assert!(provider.supports_structured_output());
+
+        let schema = json!({"type": "object"});
+        let value = provider
+            .chat_structured("system", &[Message::user("plan")], &schema)
+            .await
+            .unwrap();
+        assert_eq!(value, json!({ "steps": ["a"] }));
+        assert_eq!(provider.schemas(), vec![schema]);
+    }
 }
@@ -97,7 +97,9 @@ where
 
 /// Check if an error is retryable (transient errors)
 pub fn is_retryable_error(error: &anyhow::Error) -> bool {
-    let error_str = error.to_string().to_lowercase();
+    // Include the whole context chain: the underlying HTTP status is usually
+    // wrapped in a generic "failed to call ... API" context
+    let error_str = format!("{:#}", error).to_lowercase();
 
     // Network/connection errors
     if error_str.contains("connection")
@@ -172,7 +174,50 @@ mod tests {
         )));
         assert!(is_retryable_error(&anyhow::anyhow!("API overloaded")));
 
+        assert!(is_retryable_error(
+            &anyhow::anyhow!("HTTP 503 Service Unavailable")
+                .context("failed to call Anthropic API")
+        ));
+
         assert!(!is_retryable_error(&anyhow::anyhow!("invalid api key")));
         assert!(!is_retryable_error(&anyhow::anyhow!("model not found")));
     }
+
+    #[tokio::test]
+    async fn retries_wrapped_transient_errors_until_success() {
+        let config = RetryConfig::new(3, 1);
+        let mut attempts = 0;
+
+        let result = retry_with_backoff(&config, "test", || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    Err(anyhow::anyhow!("503 Service Unavailable")
+                        .context("failed to call Anthropic API"))
+                } else {
+                    Ok("done")
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_errors_fail_on_the_first_attempt() {
+        let config = RetryConfig::new(3, 1);
+        let mut attempts = 0;
+
+        let result: Result<()> = retry_with_backoff(&config, "test", || {
+            attempts += 1;
+            async { Err(anyhow::anyhow!("401 Unauthorized: invalid api key")) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
 }
@@ -1,12 +1,23 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use tracing::{debug, error, info};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
+#[cfg(feature = "postgres")]
+use dev_killer::PostgresStorage;
+
 use dev_killer::{
-    AnthropicProvider, CoderAgent, EditFileTool, Executor, GlobTool, GrepTool, LlmProvider,
-    OpenAIProvider, OrchestratorAgent, Policy, ProjectConfig, ReadFileTool, SessionState,
-    SessionStatus, ShellTool, SqliteStorage, Storage, ToolRegistry, WriteFileTool,
+    Agent, AnthropicProvider, AskUserTool, CoderAgent, Executor, GeminiProvider, InvalidConfig,
+    LlmProvider, OllamaProvider, OpenAIProvider, OrchestratorAgent, Pipeline, Policy,
+    ProjectConfig, RESERVED_METADATA_KEYS, RetryConfig, RunCancelled, RunTimedOut,
+    SESSION_LOCK_STALE_AFTER, SessionEvent, SessionFilter, SessionState, SessionStatus,
+    SqliteStorage, Storage, TokenTotals, ToolRegistry, UserInputRequest,
 };
 
 #[derive(Parser)]
@@ -17,7 +28,7 @@ struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
-    /// LLM provider to use (anthropic, openai)
+    /// LLM provider to use (anthropic, openai, gemini, ollama)
     #[arg(long)]
     provider: Option<String>,
 
@@ -25,6 +36,11 @@ struct Cli {
     #[arg(long)]
     model: Option<String>,
 
+    /// How run, resume and sessions print their results; logs go to stderr
+    /// in the JSON formats
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    output_format: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -43,16 +59,45 @@ enum Commands {
         /// Save session for later resume (enables persistence)
         #[arg(long)]
         save_session: bool,
+
+        /// Tag the saved session with metadata (repeatable, overrides config)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+
+        /// Return the earlier result instead of re-running if a session with
+        /// this key already completed (implies --save-session)
+        #[arg(long, value_name = "KEY")]
+        idempotency_key: Option<String>,
+
+        /// Use this id for the new session instead of a random one, e.g. an
+        /// external ticket id; it must not be in use (implies --save-session)
+        #[arg(long, value_name = "ID")]
+        session_id: Option<String>,
+
+        /// Resume the latest resumable session with the same task text and
+        /// working directory instead of starting a new one (implies --save-session)
+        #[arg(long)]
+        resume_if_exists: bool,
+
+        /// Preview file writes and edits as diffs without changing any files
+        #[arg(long)]
+        dry_run: bool,
     },
 
-    /// Resume a previously interrupted session
+    /// Resume a previously interrupted or failed session
     Resume {
         /// Session ID to resume
         session_id: String,
 
-        /// Use simple mode (single coder agent)
+        /// Continue in simple mode (single coder agent)
         #[arg(long)]
         simple: bool,
+
+        /// Continue under the full orchestrator, e.g. for a session started
+        /// in simple mode. Without --simple or --full, the session keeps the
+        /// pipeline it was started with.
+        #[arg(long, conflicts_with = "simple")]
+        full: bool,
     },
 
     /// List saved sessions
@@ -60,6 +105,22 @@ enum Commands {
         /// Show only sessions with this status (pending, in_progress, completed, failed, interrupted)
         #[arg(long)]
         status: Option<String>,
+
+        /// Show only sessions whose task contains this text
+        #[arg(long)]
+        task: Option<String>,
+
+        /// Show only sessions whose task or error contains this text, ignoring case
+        #[arg(long)]
+        search: Option<String>,
+
+        /// Page to show, starting at 1
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+
+        /// Sessions per page
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
     },
 
     /// Delete a session
@@ -67,66 +128,365 @@ enum Commands {
         /// Session ID to delete
         session_id: String,
     },
+
+    /// Delete sessions that haven't been updated in a while
+    Prune {
+        /// Delete sessions last updated more than this many days ago
+        #[arg(long)]
+        older_than_days: u32,
+
+        /// Delete only sessions with this status (pending, in_progress, completed, failed, interrupted)
+        #[arg(long)]
+        status: Option<String>,
+    },
+
+    /// Export a session as a readable Markdown report
+    ExportMd {
+        /// Session ID to export
+        session_id: String,
+
+        /// Write the report to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
 }
 
-fn init_logging(verbose: bool) {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Readable text
+    Human,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line: session events as they happen, then the
+    /// result
+    JsonLines,
+}
+
+/// The result of `run` or `resume` in the JSON output formats
+#[derive(Serialize)]
+struct RunReport {
+    /// "finished" in json-lines output, where it follows the session events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'static str>,
+    session_id: Option<String>,
+    /// "completed", "failed" or "interrupted"
+    status: &'static str,
+    output: Option<String>,
+    error: Option<String>,
+    usage: Option<TokenTotals>,
+}
+
+/// Print a run's result in `format`. A failed run fails the command.
+fn report_result(
+    format: OutputFormat,
+    action: &str,
+    result: Result<String>,
+    session_id: Option<String>,
+    usage: Option<TokenTotals>,
+) -> Result<()> {
+    let error = match &result {
+        Ok(output) if format == OutputFormat::Human => {
+            println!("\n{}", output);
+            return Ok(());
+        }
+        Ok(_) => None,
+        Err(e) => {
+            error!(error = %e, "{} failed", action);
+            Some(e.to_string())
+        }
+    };
+
+    if format != OutputFormat::Human {
+        let status = match &result {
+            Ok(_) => "completed",
+            Err(e) if is_interruption(e) => "interrupted",
+            Err(_) => "failed",
+        };
+        let report = RunReport {
+            event: (format == OutputFormat::JsonLines).then_some("finished"),
+            session_id,
+            status,
+            output: result.ok(),
+            error: error.clone(),
+            usage,
+        };
+        print_json(format, &report)?;
+    }
+
+    match error {
+        Some(e) => anyhow::bail!("{} failed: {}", action, e),
+        None => Ok(()),
+    }
+}
+
+/// Print a value as pretty JSON, or compactly on one line for json-lines
+fn print_json(format: OutputFormat, value: &impl Serialize) -> Result<()> {
+    let json = if format == OutputFormat::JsonLines {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+/// Streams session events to stdout as JSON lines while a run is in progress
+struct EventStream {
+    done: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl EventStream {
+    /// Start streaming for json-lines output, if the storage backend
+    /// supports watching
+    fn start(format: OutputFormat, storage: &dyn Storage) -> Option<Self> {
+        if format != OutputFormat::JsonLines {
+            return None;
+        }
+        let mut events = storage.watch().ok()?;
+        let (done, mut stop) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) => print_event(&event),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!(skipped, "session event stream fell behind");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    },
+                    _ = &mut stop => break,
+                }
+            }
+            // Flush what was sent before the run finished
+            while let Ok(event) = events.try_recv() {
+                print_event(&event);
+            }
+        });
+        Some(Self { done, task })
+    }
+
+    /// Stop once the events already sent have been printed
+    async fn finish(self) {
+        let _ = self.done.send(());
+        let _ = self.task.await;
+    }
+}
+
+fn print_event(event: &SessionEvent) {
+    if let Err(e) = print_json(OutputFormat::JsonLines, event) {
+        warn!(error = %e, "failed to print session event");
+    }
+}
+
+/// Parse a `KEY=VALUE` session tag
+fn parse_tag(s: &str) -> Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .with_context(|| format!("invalid tag '{}' (expected KEY=VALUE)", s))?;
+    if key.is_empty() {
+        anyhow::bail!("invalid tag '{}' (key must not be empty)", s);
+    }
+    if RESERVED_METADATA_KEYS.contains(&key) {
+        anyhow::bail!("invalid tag '{}' ('{}' is a reserved key)", s, key);
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Set up logging; `to_stderr` keeps stdout clean for JSON output
+fn init_logging(verbose: bool, to_stderr: bool) {
     let filter = if verbose {
         EnvFilter::new("debug")
     } else {
         EnvFilter::from_default_env().add_directive("info".parse().expect("valid log directive"))
     };
 
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if to_stderr {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
 }
 
-fn create_provider(provider: &str, model: Option<&str>) -> Result<Box<dyn LlmProvider>> {
+/// Create the LLM provider. `api_key` comes from the environment or the
+/// global config; without one the provider reports which variable to set.
+/// Transient API failures are retried per `retry`.
+fn create_provider(
+    provider: &str,
+    model: Option<&str>,
+    api_key: Option<String>,
+    max_tokens: Option<u32>,
+    retry: RetryConfig,
+) -> Result<Box<dyn LlmProvider>> {
     match provider {
         "anthropic" => {
-            let p = if let Some(m) = model {
-                AnthropicProvider::new(m)?
-            } else {
-                AnthropicProvider::sonnet()?
+            let model = model.unwrap_or(AnthropicProvider::DEFAULT_MODEL);
+            let mut p = match api_key {
+                Some(key) => AnthropicProvider::with_api_key(key, model),
+                None => AnthropicProvider::new(model)?,
             };
-            Ok(Box::new(p))
+            if let Some(max_tokens) = max_tokens {
+                p = p.with_max_tokens(max_tokens);
+            }
+            Ok(Box::new(p.with_retry(retry)))
         }
         "openai" => {
-            let p = if let Some(m) = model {
-                OpenAIProvider::new(m)?
-            } else {
-                OpenAIProvider::gpt4o()?
+            let model = model.unwrap_or(OpenAIProvider::DEFAULT_MODEL);
+            let mut p = match api_key {
+                Some(key) => OpenAIProvider::with_api_key(key, model),
+                None => OpenAIProvider::new(model)?,
+            };
+            if let Some(max_tokens) = max_tokens {
+                p = p.with_max_tokens(max_tokens);
+            }
+            Ok(Box::new(p.with_retry(retry)))
+        }
+        "gemini" => {
+            let model = model.unwrap_or(GeminiProvider::DEFAULT_MODEL);
+            let mut p = match api_key {
+                Some(key) => GeminiProvider::with_api_key(key, model),
+                None => GeminiProvider::new(model)?,
             };
-            Ok(Box::new(p))
+            if let Some(max_tokens) = max_tokens {
+                p = p.with_max_tokens(max_tokens);
+            }
+            Ok(Box::new(p.with_retry(retry)))
+        }
+        "ollama" => {
+            let model = model.unwrap_or(OllamaProvider::DEFAULT_MODEL);
+            let mut p = OllamaProvider::new(model);
+            // Same variable the ollama CLI honors, e.g. "http://gpu-box:11434"
+            if let Ok(host) = std::env::var("OLLAMA_HOST") {
+                p = p.with_base_url(ollama_base_url(&host));
+            }
+            if let Some(max_tokens) = max_tokens {
+                p = p.with_max_tokens(max_tokens);
+            }
+            Ok(Box::new(p.with_retry(retry)))
         }
         _ => anyhow::bail!("unknown provider: {}", provider),
     }
 }
 
-fn create_tool_registry(policy: &Policy) -> ToolRegistry {
-    let mut registry = ToolRegistry::new();
-    // File tools
-    registry.register(ReadFileTool {
-        policy: policy.clone(),
-    });
-    registry.register(WriteFileTool {
-        policy: policy.clone(),
-    });
-    registry.register(EditFileTool {
-        policy: policy.clone(),
-    });
-    // Shell tool
-    registry.register(ShellTool {
-        policy: policy.clone(),
-    });
-    // Search tools
-    registry.register(GlobTool {
-        policy: policy.clone(),
-    });
-    registry.register(GrepTool {
-        policy: policy.clone(),
+/// `OLLAMA_HOST` may omit the scheme ("127.0.0.1:11434"); default it to http
+fn ollama_base_url(host: &str) -> String {
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("http://{}", host)
+    }
+}
+
+/// Open session storage: the PostgreSQL database at `DEV_KILLER_DATABASE_URL`
+/// (or a PostgreSQL `DATABASE_URL`) when built with the `postgres` feature
+/// and one is set, otherwise the local SQLite database
+async fn open_storage() -> Result<Box<dyn Storage>> {
+    #[cfg(feature = "postgres")]
+    if let Some(url) = PostgresStorage::database_url_from_env() {
+        let storage = PostgresStorage::connect(&url)
+            .await
+            .context("failed to initialize session storage")?;
+        return Ok(Box::new(storage));
+    }
+
+    let storage =
+        SqliteStorage::default_location().context("failed to initialize session storage")?;
+    Ok(Box::new(storage))
+}
+
+/// Cancel runs gracefully on the first Ctrl-C (sessions are saved as
+/// interrupted); a second Ctrl-C exits immediately
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!("interrupted, stopping after the current step (Ctrl-C again to exit now)");
+        cancel.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
     });
+    token
+}
+
+/// Apply the policy's run timeout, if it sets one
+fn with_run_timeout(executor: Executor, policy: &Policy) -> Executor {
+    match policy.run_timeout() {
+        Some(timeout) => executor.with_run_timeout(timeout),
+        None => executor,
+    }
+}
+
+/// Whether a run stopped early but left its session resumable
+fn is_interruption(error: &anyhow::Error) -> bool {
+    error.is::<RunCancelled>() || error.is::<RunTimedOut>()
+}
+
+fn create_tool_registry(policy: &Policy) -> ToolRegistry {
+    let mut registry = ToolRegistry::with_default_tools(policy);
+    register_user_input(&mut registry);
     registry
 }
 
+/// Let agents ask clarifying questions on stdin when running interactively
+fn register_user_input(registry: &mut ToolRegistry) {
+    if !std::io::stdin().is_terminal() {
+        return;
+    }
+
+    let (tx, mut rx) = mpsc::channel::<UserInputRequest>(1);
+    registry.register(AskUserTool::new(tx));
+
+    tokio::spawn(async move {
+        while let Some(request) = rx.recv().await {
+            let prompt = request.prompt;
+            let answer = tokio::task::spawn_blocking(move || {
+                eprintln!("\n[agent question] {}", prompt);
+                eprint!("> ");
+                std::io::stderr().flush()?;
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                Ok::<_, std::io::Error>(line.trim_end().to_string())
+            })
+            .await;
+
+            match answer {
+                Ok(Ok(answer)) => {
+                    // The tool may have timed out and stopped listening
+                    let _ = request.response.send(answer);
+                }
+                Ok(Err(e)) => warn!(error = %e, "failed to read user input"),
+                Err(e) => warn!(error = %e, "user input task failed"),
+            }
+        }
+    });
+}
+
+/// Create the orchestrator, applying project-level agent settings
+fn create_orchestrator(config: &ProjectConfig) -> OrchestratorAgent {
+    let mut orchestrator = OrchestratorAgent::new().with_loop_config(config.agent_loop_config());
+    if !config.test_commands.is_empty() {
+        orchestrator =
+            orchestrator.with_test_commands(config.test_commands.clone(), config.test_concurrency);
+    }
+    if config.summarize.unwrap_or(false) {
+        orchestrator = orchestrator.with_summary();
+    }
+    if config.security_audit.unwrap_or(false) {
+        orchestrator = orchestrator.with_security_audit();
+    }
+    orchestrator
+}
+
+/// Create the single coder agent used in simple mode
+fn create_coder(config: &ProjectConfig) -> CoderAgent {
+    CoderAgent::new().with_loop_config(config.agent_loop_config())
+}
+
 /// Resolve which provider name to use.
 /// CLI argument takes highest precedence, then config file, then default.
 fn resolve_provider<'a>(
@@ -139,132 +499,226 @@ fn resolve_provider<'a>(
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    init_logging(cli.verbose);
+    let format = cli.output_format;
+    init_logging(cli.verbose, format != OutputFormat::Human);
 
     // Load configuration with precedence: CLI > env > project > global > defaults
-    let config = ProjectConfig::load().unwrap_or_else(|e| {
-        debug!(error = %e, "failed to load config, using defaults");
-        ProjectConfig::default()
-    });
+    let config =
+        ProjectConfig::load()?.apply_cli_overrides(cli.provider.as_deref(), cli.model.as_deref());
+    config.validate().map_err(InvalidConfig)?;
 
     match cli.command {
         Commands::Run {
             task,
             simple,
             save_session,
+            tags,
+            idempotency_key,
+            session_id,
+            resume_if_exists,
+            dry_run,
         } => {
             // Apply config defaults - CLI flags override config
             let use_simple = simple || config.is_simple_mode();
-            let use_save_session = save_session || config.is_save_sessions();
+            let use_save_session = save_session
+                || idempotency_key.is_some()
+                || session_id.is_some()
+                || resume_if_exists
+                || config.is_save_sessions();
             let provider_name =
                 resolve_provider(cli.provider.as_deref(), config.provider.as_deref());
             let model_name = cli.model.as_deref().or(config.model.as_deref());
 
             info!(provider = %provider_name, simple = use_simple, save_session = use_save_session, "starting task");
 
-            let provider = create_provider(provider_name, model_name)
-                .context("failed to create LLM provider")?;
+            // Not wrapped in extra context: the error (e.g. a missing API key) says what to fix
+            let provider = create_provider(
+                provider_name,
+                model_name,
+                config.api_key(provider_name),
+                config.max_tokens,
+                config.retry_config(),
+            )?;
 
-            let tools = create_tool_registry(&config.policy);
+            let mut policy = config.policy.clone();
+            policy.dry_run |= dry_run;
+            let tools = create_tool_registry(&policy);
 
-            let result = if use_save_session {
+            let (result, session_id, usage) = if use_save_session {
                 // Run with session tracking
-                let storage = SqliteStorage::default_location()
-                    .context("failed to initialize session storage")?;
-                let executor = Executor::with_storage(tools, Box::new(storage));
+                let storage = open_storage().await?;
+                let events = EventStream::start(format, storage.as_ref());
+                let executor = with_run_timeout(
+                    Executor::with_storage(tools, storage)
+                        .with_session_budget(policy.session_budget())
+                        .with_cancellation(cancel_on_ctrl_c()),
+                    &policy,
+                );
 
                 let working_dir = std::env::current_dir()
                     .context("failed to get current directory")?
                     .to_string_lossy()
                     .to_string();
 
-                let mut session = SessionState::new(&task, working_dir);
+                let mut session = match session_id {
+                    Some(id) => {
+                        executor.check_session_id_available(&id).await?;
+                        SessionState::with_id(id, &task, working_dir)
+                    }
+                    None => SessionState::new(&task, working_dir),
+                };
+                config.apply_session_defaults(&mut session);
+                for (key, value) in tags {
+                    session.set_metadata(key, value);
+                }
+                if let Some(key) = idempotency_key {
+                    session.set_idempotency_key(key);
+                }
                 info!(session_id = %session.id, "created new session");
 
-                if use_simple {
+                let agent: Box<dyn Agent> = if use_simple {
                     info!("using simple mode (single coder agent)");
-                    let agent = CoderAgent::new();
+                    Box::new(create_coder(&config))
+                } else {
+                    info!("using orchestrator mode (planner -> coder -> tester -> reviewer)");
+                    Box::new(create_orchestrator(&config))
+                };
+
+                let result = if resume_if_exists {
                     executor
-                        .run_with_session(&agent, &mut session, provider.as_ref())
+                        .resume_or_start(agent.as_ref(), &mut session, provider.as_ref())
                         .await
                 } else {
-                    info!("using orchestrator mode (planner -> coder -> tester -> reviewer)");
-                    let agent = OrchestratorAgent::new();
                     executor
-                        .run_with_session(&agent, &mut session, provider.as_ref())
+                        .run_with_session(agent.as_ref(), &mut session, provider.as_ref())
                         .await
+                };
+                if let Some(events) = events {
+                    events.finish().await;
                 }
+                if format == OutputFormat::Human && result.as_ref().is_err_and(is_interruption) {
+                    println!(
+                        "Session {} interrupted; continue it with: dev-killer resume {}",
+                        session.id, session.id
+                    );
+                }
+                (result, Some(session.id), session.total_usage)
             } else {
                 // Run without session tracking
-                let executor = Executor::new(tools);
+                let executor = with_run_timeout(
+                    Executor::new(tools).with_cancellation(cancel_on_ctrl_c()),
+                    &policy,
+                );
 
-                if use_simple {
+                let result = if use_simple {
                     info!("using simple mode (single coder agent)");
-                    let agent = CoderAgent::new();
+                    let agent = create_coder(&config);
                     executor.run(&agent, &task, provider.as_ref()).await
                 } else {
                     info!("using orchestrator mode (planner -> coder -> tester -> reviewer)");
-                    let agent = OrchestratorAgent::new();
+                    let agent = create_orchestrator(&config);
                     executor.run(&agent, &task, provider.as_ref()).await
-                }
+                };
+                (result, None, None)
             };
 
-            match result {
-                Ok(output) => {
-                    println!("\n{}", output);
-                }
-                Err(e) => {
-                    error!(error = %e, "task failed");
-                    anyhow::bail!("task failed: {}", e);
-                }
-            }
+            report_result(format, "task", result, session_id, usage)?;
         }
 
-        Commands::Resume { session_id, simple } => {
-            // Apply config defaults - CLI flags override config
-            let use_simple = simple || config.is_simple_mode();
+        Commands::Resume {
+            session_id,
+            simple,
+            full,
+        } => {
             let provider_name =
                 resolve_provider(cli.provider.as_deref(), config.provider.as_deref());
             let model_name = cli.model.as_deref().or(config.model.as_deref());
 
             info!(session_id = %session_id, "resuming session");
 
-            let provider = create_provider(provider_name, model_name)
-                .context("failed to create LLM provider")?;
+            let provider = create_provider(
+                provider_name,
+                model_name,
+                config.api_key(provider_name),
+                config.max_tokens,
+                config.retry_config(),
+            )?;
 
             let tools = create_tool_registry(&config.policy);
-            let storage = SqliteStorage::default_location()
-                .context("failed to initialize session storage")?;
-            let executor = Executor::with_storage(tools, Box::new(storage));
-
-            let result = if use_simple {
-                let agent = CoderAgent::new();
-                executor
-                    .resume_session(&session_id, &agent, provider.as_ref())
-                    .await
+            let storage = open_storage().await?;
+
+            // Flags override the session's own pipeline, which overrides config
+            let recorded = storage
+                .load(&session_id)
+                .await?
+                .and_then(|session| session.pipeline);
+            let pipeline = if simple {
+                Pipeline::Simple
+            } else if full {
+                Pipeline::Orchestrated
+            } else if let Some(pipeline) = recorded {
+                pipeline
+            } else if config.is_simple_mode() {
+                Pipeline::Simple
             } else {
-                let agent = OrchestratorAgent::new();
-                executor
-                    .resume_session(&session_id, &agent, provider.as_ref())
-                    .await
+                Pipeline::Orchestrated
             };
 
-            match result {
-                Ok(output) => {
-                    println!("\n{}", output);
+            let events = EventStream::start(format, storage.as_ref());
+            let executor = with_run_timeout(
+                Executor::with_storage(tools, storage)
+                    .with_session_budget(config.policy.session_budget())
+                    .with_cancellation(cancel_on_ctrl_c()),
+                &config.policy,
+            );
+
+            let result = match pipeline {
+                Pipeline::Simple => {
+                    let agent = create_coder(&config);
+                    executor
+                        .resume_session_with_pipeline(
+                            &session_id,
+                            pipeline,
+                            &agent,
+                            provider.as_ref(),
+                        )
+                        .await
                 }
-                Err(e) => {
-                    error!(error = %e, "resume failed");
-                    anyhow::bail!("resume failed: {}", e);
+                Pipeline::Orchestrated => {
+                    let agent = create_orchestrator(&config);
+                    executor
+                        .resume_session_with_pipeline(
+                            &session_id,
+                            pipeline,
+                            &agent,
+                            provider.as_ref(),
+                        )
+                        .await
                 }
+            };
+
+            if let Some(events) = events {
+                events.finish().await;
             }
-        }
 
-        Commands::Sessions { status } => {
-            let storage = SqliteStorage::default_location()
-                .context("failed to initialize session storage")?;
+            let usage = match (format, executor.storage()) {
+                (OutputFormat::Human, _) | (_, None) => None,
+                (_, Some(storage)) => storage
+                    .load(&session_id)
+                    .await?
+                    .and_then(|session| session.total_usage),
+            };
+            report_result(format, "resume", result, Some(session_id), usage)?;
+        }
 
-            let sessions = storage.list().await?;
+        Commands::Sessions {
+            status,
+            task,
+            search,
+            page,
+            limit,
+        } => {
+            let storage = open_storage().await?;
 
             // Parse status filter if provided
             let status_filter = if let Some(ref s) = status {
@@ -275,9 +729,34 @@ async fn main() -> Result<()> {
             } else {
                 None
             };
+            let filter = SessionFilter {
+                status: status_filter,
+                task_contains: task,
+                search,
+                ..SessionFilter::default()
+            };
+
+            let page = page.max(1);
+            let limit = limit.max(1);
+            let (sessions, total) = storage.list_page(&filter, page, limit).await?;
+
+            match format {
+                OutputFormat::Human => {}
+                OutputFormat::Json => return print_json(format, &sessions),
+                OutputFormat::JsonLines => {
+                    for session in &sessions {
+                        print_json(format, session)?;
+                    }
+                    return Ok(());
+                }
+            }
 
             if sessions.is_empty() {
-                println!("No sessions found.");
+                if total == 0 {
+                    println!("No sessions found.");
+                } else {
+                    println!("No sessions on page {} ({} total).", page, total);
+                }
                 return Ok(());
             }
 
@@ -285,24 +764,65 @@ async fn main() -> Result<()> {
             println!("{}", "-".repeat(70));
 
             for session in sessions {
-                // Filter by status if specified
-                if let Some(filter_status) = status_filter {
-                    if session.status != filter_status {
-                        continue;
-                    }
-                }
-
                 println!("{}", session);
             }
+
+            println!(
+                "\nPage {} of {} ({} sessions)",
+                page,
+                total.div_ceil(limit),
+                total
+            );
         }
 
         Commands::DeleteSession { session_id } => {
-            let storage = SqliteStorage::default_location()
-                .context("failed to initialize session storage")?;
+            let storage = open_storage().await?;
 
             storage.delete(&session_id).await?;
             println!("Deleted session: {}", session_id);
         }
+
+        Commands::Prune {
+            older_than_days,
+            status,
+        } => {
+            let storage = open_storage().await?;
+
+            let status = status
+                .map(|s| {
+                    s.parse::<SessionStatus>()
+                        .with_context(|| format!("invalid status filter: {}", s))
+                })
+                .transpose()?;
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(i64::from(older_than_days));
+
+            let deleted = storage
+                .delete_older_than(cutoff, status, SESSION_LOCK_STALE_AFTER)
+                .await?;
+            println!(
+                "Deleted {} session(s) not updated in the last {} days.",
+                deleted, older_than_days
+            );
+        }
+
+        Commands::ExportMd { session_id, output } => {
+            let storage = open_storage().await?;
+
+            let session = storage
+                .load(&session_id)
+                .await?
+                .with_context(|| format!("session not found: {}", session_id))?;
+            let markdown = session.to_markdown();
+
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, markdown)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("Exported session {} to {}", session_id, path.display());
+                }
+                None => print!("{}", markdown),
+            }
+        }
     }
 
     Ok(())
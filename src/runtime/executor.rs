@@ -1,15 +1,162 @@
 use anyhow::{Context, Result};
-use tracing::{error, info};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
-use crate::agents::Agent;
+use crate::agents::{Agent, RunCancelled, RunContext, SessionHandle};
 use crate::llm::LlmProvider;
-use crate::session::{SessionPhase, SessionState, SessionStatus, Storage};
+use crate::session::{
+    IDEMPOTENCY_KEY, MemoryStorage, Pipeline, SessionBudget, SessionBusy, SessionPhase,
+    SessionState, SessionStatus, Storage,
+};
 use crate::tools::ToolRegistry;
 
+use super::TaskRequest;
+
+/// How long a session lock survives without a heartbeat before another
+/// process may take it over (covers runs that crashed without unlocking)
+pub const SESSION_LOCK_STALE_AFTER: Duration = Duration::from_secs(300);
+
+/// How long a timed-out run gets to reach its next checkpoint and stop
+/// before it is abandoned
+const TIMEOUT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Holds a session's advisory lock for the duration of a run.
+///
+/// A background heartbeat keeps the lock fresh while the run is alive. If
+/// a refresh finds another process has taken the lock over, the heartbeat
+/// stops and cancels the run, so two runs never work on one session.
+/// Dropping the guard without calling `release` (an early return or a panic)
+/// still releases the lock in the background; if the whole process dies, the
+/// lock goes stale and can be taken over.
+struct SessionLock {
+    storage: Arc<dyn Storage>,
+    session_id: String,
+    owner: String,
+    heartbeat: JoinHandle<()>,
+    /// Set once the heartbeat finds the lock held by someone else
+    lost: Arc<AtomicBool>,
+    released: bool,
+}
+
+impl SessionLock {
+    /// Lock the session or fail with [`SessionBusy`]. `run` is cancelled if
+    /// the lock is lost.
+    async fn acquire(
+        storage: &Arc<dyn Storage>,
+        session_id: &str,
+        stale_after: Duration,
+        run: CancellationToken,
+    ) -> Result<Self> {
+        let owner = Uuid::new_v4().to_string();
+        if !storage.try_lock(session_id, &owner, stale_after).await? {
+            return Err(SessionBusy {
+                session_id: session_id.to_string(),
+            }
+            .into());
+        }
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let heartbeat = tokio::spawn({
+            let storage = Arc::clone(storage);
+            let session_id = session_id.to_string();
+            let owner = owner.clone();
+            let lost = Arc::clone(&lost);
+            async move {
+                let period = (stale_after / 3).max(Duration::from_millis(1));
+                let mut ticks = tokio::time::interval(period);
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    match storage.try_lock(&session_id, &owner, stale_after).await {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            warn!(%session_id, "session lock was taken over by another process, stopping the run");
+                            lost.store(true, Ordering::SeqCst);
+                            run.cancel();
+                            break;
+                        }
+                        Err(e) => warn!(%session_id, error = %e, "failed to refresh session lock"),
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            storage: Arc::clone(storage),
+            session_id: session_id.to_string(),
+            owner,
+            heartbeat,
+            lost,
+            released: false,
+        })
+    }
+
+    /// Whether another process took the lock over during the run
+    fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::SeqCst)
+    }
+
+    /// Stop the heartbeat and release the lock
+    async fn release(mut self) {
+        self.heartbeat.abort();
+        self.released = true;
+        if let Err(e) = self.storage.unlock(&self.session_id, &self.owner).await {
+            warn!(session_id = %self.session_id, error = %e, "failed to release session lock");
+        }
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        self.heartbeat.abort();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let storage = Arc::clone(&self.storage);
+            let session_id = std::mem::take(&mut self.session_id);
+            let owner = std::mem::take(&mut self.owner);
+            runtime.spawn(async move {
+                let _ = storage.unlock(&session_id, &owner).await;
+            });
+        }
+    }
+}
+
+/// A run took longer than the executor's run timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunTimedOut {
+    pub after: Duration,
+}
+
+impl std::fmt::Display for RunTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "run timed out after {}s", self.after.as_secs_f64())
+    }
+}
+
+impl std::error::Error for RunTimedOut {}
+
 /// Executor for running agents with optional session persistence
 pub struct Executor {
     tools: ToolRegistry,
-    storage: Option<Box<dyn Storage>>,
+    storage: Option<Arc<dyn Storage>>,
+    /// Bounds how many runs execute at once (unbounded if `None`)
+    run_slots: Option<Semaphore>,
+    /// How long an unrefreshed session lock is honored
+    session_lock_timeout: Duration,
+    /// Cumulative limits a session must be within to run
+    session_budget: SessionBudget,
+    /// Cancels this executor's runs
+    cancellation: CancellationToken,
+    /// Wall-clock limit for a whole run (unlimited if `None`)
+    run_timeout: Option<Duration>,
 }
 
 impl Executor {
@@ -18,6 +165,11 @@ impl Executor {
         Self {
             tools,
             storage: None,
+            run_slots: None,
+            session_lock_timeout: SESSION_LOCK_STALE_AFTER,
+            session_budget: SessionBudget::default(),
+            cancellation: CancellationToken::new(),
+            run_timeout: None,
         }
     }
 
@@ -25,59 +177,430 @@ impl Executor {
     pub fn with_storage(tools: ToolRegistry, storage: Box<dyn Storage>) -> Self {
         Self {
             tools,
-            storage: Some(storage),
+            storage: Some(Arc::from(storage)),
+            run_slots: None,
+            session_lock_timeout: SESSION_LOCK_STALE_AFTER,
+            session_budget: SessionBudget::default(),
+            cancellation: CancellationToken::new(),
+            run_timeout: None,
+        }
+    }
+
+    /// Create an executor whose sessions live only in memory.
+    ///
+    /// Session tracking, resume and export all work, but nothing outlives
+    /// the process. Useful for tests and one-off runs.
+    pub fn with_memory_storage(tools: ToolRegistry) -> Self {
+        Self::with_storage(tools, Box::new(MemoryStorage::new()))
+    }
+
+    /// Limit how many runs execute concurrently on this executor.
+    ///
+    /// Runs beyond the limit wait (inside `run`/`run_with_session`) until a
+    /// slot frees up. A limit of zero is treated as one.
+    pub fn with_max_concurrent_runs(mut self, max_runs: usize) -> Self {
+        self.run_slots = Some(Semaphore::new(max_runs.max(1)));
+        self
+    }
+
+    /// How long a session lock left behind by a crashed process blocks
+    /// other runs of that session (default: 5 minutes). Live runs refresh
+    /// their lock well within this window.
+    pub fn with_session_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.session_lock_timeout = timeout;
+        self
+    }
+
+    /// Refuse to run or resume sessions that have used up this budget.
+    ///
+    /// Runtime and LLM round-trips are summed over every run of a session;
+    /// the budget is checked before each run starts.
+    pub fn with_session_budget(mut self, budget: SessionBudget) -> Self {
+        self.session_budget = budget;
+        self
+    }
+
+    /// Cancel runs through `token` (e.g. one shared with a signal handler)
+    /// instead of only through [`Executor::cancel`]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Stop any run that takes longer than `timeout` in total, across all
+    /// of its LLM calls, tool calls and review iterations. At the deadline
+    /// the run is cancelled, so a tool call in progress finishes and is
+    /// saved first. The run fails with [`RunTimedOut`], and a tracked
+    /// session is saved as interrupted so it can be resumed. Unlimited by
+    /// default.
+    pub fn with_run_timeout(mut self, timeout: Duration) -> Self {
+        self.run_timeout = Some(timeout);
+        self
+    }
+
+    /// Run `run` under the run timeout, if one is set.
+    ///
+    /// At the deadline the run is cancelled through `token` rather than
+    /// dropped, so tool calls that already ran are checkpointed instead of
+    /// being repeated on resume. It gets [`TIMEOUT_GRACE_PERIOD`] to stop.
+    async fn time_limited<T>(
+        &self,
+        token: &CancellationToken,
+        run: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Some(limit) = self.run_timeout else {
+            return run.await;
+        };
+        let mut run = std::pin::pin!(run);
+        if let Ok(result) = tokio::time::timeout(limit, &mut run).await {
+            return result;
+        }
+
+        warn!(after = ?limit, "run timed out, stopping it at its next checkpoint");
+        token.cancel();
+        if tokio::time::timeout(TIMEOUT_GRACE_PERIOD, run)
+            .await
+            .is_err()
+        {
+            warn!("timed-out run did not stop within the grace period");
+        }
+        Err(RunTimedOut { after: limit }.into())
+    }
+
+    /// Gracefully stop this executor's runs: agents finish the tool call in
+    /// progress and fail with [`RunCancelled`], and tracked sessions are
+    /// saved as interrupted so they can be resumed. Runs started after
+    /// cancelling stop immediately.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Keep only the named tools, e.g.
+    /// `only_tools(&["read_file", "glob", "grep"])` for a read-only assistant.
+    ///
+    /// Applies to whatever registry the executor was built with, custom
+    /// tools included, so register custom tools before restricting. Names
+    /// that aren't registered are ignored with a warning.
+    pub fn only_tools(mut self, names: &[&str]) -> Self {
+        for name in names.iter().filter(|name| self.tools.get(name).is_none()) {
+            warn!(tool = %name, "only_tools names a tool that is not registered");
         }
+        self.tools.retain(|name| names.contains(&name));
+        self
+    }
+
+    /// Remove the named tools (e.g. `without_tools(&["shell"])`). Like
+    /// [`Executor::only_tools`], it applies to custom tools too.
+    pub fn without_tools(mut self, names: &[&str]) -> Self {
+        self.tools.retain(|name| !names.contains(&name));
+        self
+    }
+
+    /// Names of the tools runs can use, sorted
+    pub fn tool_names(&self) -> Vec<&str> {
+        self.tools.list_names()
     }
 
-    /// Run an agent with a task (no session tracking)
+    /// Wait for a run slot if concurrency is limited
+    async fn acquire_run_slot(&self) -> Result<Option<SemaphorePermit<'_>>> {
+        match &self.run_slots {
+            Some(slots) => {
+                debug!(
+                    available = slots.available_permits(),
+                    "waiting for run slot"
+                );
+                Ok(Some(slots.acquire().await.context("run slots closed")?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Run an agent with a task (no session tracking).
+    ///
+    /// Accepts a plain task string or a [`TaskRequest`]. Labels and the
+    /// recorded working directory only apply to tracked runs (see
+    /// [`Self::run_request`]), so they are ignored here with a warning. A
+    /// pre-computed plan is refused unless the agent
+    /// [accepts one](Agent::accepts_plan).
     pub async fn run(
         &self,
         agent: &dyn Agent,
-        task: &str,
+        task: impl Into<TaskRequest>,
         provider: &dyn LlmProvider,
     ) -> Result<String> {
-        info!(task, "starting agent execution");
-        let result = agent.run(task, provider, &self.tools).await?;
+        let request = task.into();
+        if !request.labels.is_empty() || request.recorded_working_dir.is_some() {
+            warn!(
+                "labels and working directories are only recorded on tracked runs; use run_request"
+            );
+        }
+        let token = self.cancellation.child_token();
+        let ctx = request_context(agent, &request)?.with_cancellation(token.clone());
+        let _slot = self.acquire_run_slot().await?;
+        info!(task = %request.task, "starting agent execution");
+        let result = self
+            .time_limited(
+                &token,
+                agent.run_with_context(&request.task, provider, &self.tools, &ctx),
+            )
+            .await?;
         info!("agent execution completed");
         Ok(result)
     }
 
-    /// Run an agent with session tracking
+    /// Run an agent with session tracking.
+    ///
+    /// If the session carries an idempotency key and another session with the
+    /// same key already completed, its output is returned (and `session` is
+    /// replaced with it) instead of running again. A matching session that
+    /// another process is still running is an error; one left in progress by
+    /// a process that died (its lock went stale) doesn't block the key. The
+    /// key is claimed while checking, so concurrent submits can't both run.
+    ///
+    /// The session is locked for the duration of the run, so running or
+    /// resuming a session that another process is already running fails
+    /// with [`SessionBusy`].
     pub async fn run_with_session(
         &self,
         agent: &dyn Agent,
         session: &mut SessionState,
         provider: &dyn LlmProvider,
+    ) -> Result<String> {
+        self.run_tracked(agent, session, provider, RunContext::new())
+            .await
+    }
+
+    /// Run a [`TaskRequest`] as a new tracked session.
+    ///
+    /// The session records the request's labels and working directory; a
+    /// pre-computed plan is recorded as the planning output in place of
+    /// running the planner.
+    pub async fn run_request(
+        &self,
+        agent: &dyn Agent,
+        request: impl Into<TaskRequest>,
+        provider: &dyn LlmProvider,
+    ) -> Result<String> {
+        let request = request.into();
+        let ctx = request_context(agent, &request)?;
+        let mut session = request.new_session()?;
+        self.run_tracked(agent, &mut session, provider, ctx).await
+    }
+
+    async fn run_tracked(
+        &self,
+        agent: &dyn Agent,
+        session: &mut SessionState,
+        provider: &dyn LlmProvider,
+        ctx: RunContext,
     ) -> Result<String> {
         let storage = self
             .storage
             .as_ref()
             .context("storage not configured for session tracking")?;
 
+        let _slot = self.acquire_run_slot().await?;
+
+        if let Some(reason) = self.session_budget.exceeded_by(session) {
+            anyhow::bail!(
+                "session {} has exhausted its budget: it {}",
+                session.id,
+                reason
+            );
+        }
+
+        let token = self.cancellation.child_token();
+        let lock = SessionLock::acquire(
+            storage,
+            &session.id,
+            self.session_lock_timeout,
+            token.clone(),
+        )
+        .await?;
+        let started = Instant::now();
+
         info!(session_id = %session.id, task = %session.task, "starting session");
 
-        // Mark session as in progress
+        // Mark session as in progress; resumed sessions keep their phase
         session.set_status(SessionStatus::InProgress);
-        session.set_phase(SessionPhase::Planning);
-        storage.save(session).await?;
+        session.set_pipeline(agent.pipeline());
+        if session.phase == SessionPhase::NotStarted {
+            session.set_phase(agent.pipeline().phases()[0]);
+        }
+
+        // Dedupe accidental double-submits that share an idempotency key
+        if let Some(existing) = self.save_unless_duplicate(storage, session).await? {
+            lock.release().await;
+            info!(
+                session_id = %existing.id,
+                idempotency_key = existing.idempotency_key().unwrap_or_default(),
+                "returning result of completed session with matching idempotency key"
+            );
+            let output = existing.output.clone().unwrap_or_default();
+            *session = existing;
+            return Ok(output);
+        }
+
+        // Run the agent, letting it persist progress through the session handle
+        let handle = SessionHandle::new(Arc::clone(storage), session.clone());
+        let ctx = ctx
+            .with_session(handle.clone())
+            .with_cancellation(token.clone());
+        let result = self
+            .time_limited(
+                &token,
+                agent.run_with_context(&session.task, provider, &self.tools, &ctx),
+            )
+            .await;
+        *session = handle.snapshot().await;
+        session.add_runtime(started.elapsed());
+
+        // The session now belongs to the process that took the lock; saving
+        // our final state would overwrite its progress
+        if lock.is_lost() {
+            lock.release().await;
+            warn!(session_id = %session.id, "run stopped after losing the session lock");
+            return Err(anyhow::Error::new(SessionBusy {
+                session_id: session.id.clone(),
+            })
+            .context("lost the session lock to another process; the run was stopped"));
+        }
 
-        // Run the agent
-        match agent.run(&session.task, provider, &self.tools).await {
+        match result {
             Ok(output) => {
-                session.complete();
+                session.complete(&output);
                 storage.save(session).await?;
+                lock.release().await;
                 info!(session_id = %session.id, "session completed successfully");
                 Ok(output)
             }
+            Err(e) if e.is::<RunTimedOut>() => {
+                session.set_status(SessionStatus::Interrupted);
+                storage.save(session).await?;
+                lock.release().await;
+                warn!(session_id = %session.id, error = %e, "session timed out");
+                Err(e)
+            }
+            Err(e) if e.is::<RunCancelled>() => {
+                session.set_status(SessionStatus::Interrupted);
+                storage.save(session).await?;
+                lock.release().await;
+                info!(session_id = %session.id, "session cancelled");
+                Err(e)
+            }
             Err(e) => {
                 session.set_error(e.to_string());
                 storage.save(session).await?;
+                lock.release().await;
                 error!(session_id = %session.id, error = %e, "session failed");
                 Err(e)
             }
         }
     }
 
+    /// Save `session` as started, unless another session with its
+    /// idempotency key already completed (returned instead) or is still
+    /// running (an error). The key is locked while checking and saving, so
+    /// two submits with one key can't both pass the check.
+    async fn save_unless_duplicate(
+        &self,
+        storage: &Arc<dyn Storage>,
+        session: &SessionState,
+    ) -> Result<Option<SessionState>> {
+        let Some(key) = session.idempotency_key() else {
+            storage.save(session).await?;
+            return Ok(None);
+        };
+
+        let claim = format!("{}:{}", IDEMPOTENCY_KEY, key);
+        let owner = Uuid::new_v4().to_string();
+        if !storage
+            .try_lock(&claim, &owner, self.session_lock_timeout)
+            .await?
+        {
+            anyhow::bail!(
+                "a run with idempotency key '{}' is already in progress",
+                key
+            );
+        }
+        let result = self.dedupe(storage, session, key).await;
+        storage.unlock(&claim, &owner).await?;
+        result
+    }
+
+    async fn dedupe(
+        &self,
+        storage: &Arc<dyn Storage>,
+        session: &SessionState,
+        key: &str,
+    ) -> Result<Option<SessionState>> {
+        let mut matching = storage.find_by_metadata(IDEMPOTENCY_KEY, key).await?;
+        matching.retain(|s| s.id != session.id);
+
+        if let Some(index) = matching
+            .iter()
+            .position(|s| s.status == SessionStatus::Completed)
+        {
+            return Ok(Some(matching.swap_remove(index)));
+        }
+        for existing in &matching {
+            if existing.status == SessionStatus::InProgress
+                && !self.lock_is_stale(storage, &existing.id).await?
+            {
+                anyhow::bail!(
+                    "a run with idempotency key '{}' is already in progress (session {})",
+                    key,
+                    existing.id
+                );
+            }
+        }
+
+        storage.save(session).await?;
+        Ok(None)
+    }
+
+    /// Run an agent as a new session with a caller-chosen id, so it can be
+    /// correlated with external systems. The session's working directory is
+    /// the current directory. Fails if the id is already in use.
+    pub async fn run_with_session_id(
+        &self,
+        agent: &dyn Agent,
+        session_id: &str,
+        task: &str,
+        provider: &dyn LlmProvider,
+    ) -> Result<String> {
+        self.check_session_id_available(session_id).await?;
+
+        let working_dir = std::env::current_dir()
+            .context("failed to get current directory")?
+            .to_string_lossy()
+            .to_string();
+        let mut session = SessionState::with_id(session_id, task, working_dir);
+        self.run_with_session(agent, &mut session, provider).await
+    }
+
+    /// Fail unless `session_id` is a usable id that no stored session has.
+    ///
+    /// The check is not atomic: two callers racing with the same id can
+    /// both pass it.
+    pub async fn check_session_id_available(&self, session_id: &str) -> Result<()> {
+        let storage = self
+            .storage
+            .as_ref()
+            .context("storage not configured for session tracking")?;
+
+        if session_id.is_empty() || session_id.chars().any(char::is_whitespace) {
+            anyhow::bail!(
+                "invalid session id '{}': it must be non-empty and contain no whitespace",
+                session_id
+            );
+        }
+        if storage.load(session_id).await?.is_some() {
+            anyhow::bail!("session id '{}' is already in use", session_id);
+        }
+        Ok(())
+    }
+
     /// Resume a session from storage
     pub async fn resume_session(
         &self,
@@ -85,12 +608,54 @@ impl Executor {
         agent: &dyn Agent,
         provider: &dyn LlmProvider,
     ) -> Result<String> {
+        let mut session = self.load_resumable(session_id).await?;
+        self.resume(&mut session, agent, provider).await
+    }
+
+    /// Resume a session under `pipeline`, which may differ from the one it
+    /// was started with (e.g. a simple-mode task that turned out to need
+    /// the full orchestrator). `agent` must implement that pipeline.
+    ///
+    /// When the pipeline changes, the session's phase is mapped onto the
+    /// new pipeline with [`Pipeline::resume_phase`]. A session with no
+    /// recorded pipeline is resumed where it stopped.
+    pub async fn resume_session_with_pipeline(
+        &self,
+        session_id: &str,
+        pipeline: Pipeline,
+        agent: &dyn Agent,
+        provider: &dyn LlmProvider,
+    ) -> Result<String> {
+        let mut session = self.load_resumable(session_id).await?;
+
+        if session
+            .pipeline
+            .is_some_and(|recorded| recorded != pipeline)
+        {
+            let phase = pipeline.resume_phase(session.phase);
+            info!(
+                session_id = %session.id,
+                from = %session.phase,
+                to = %phase,
+                %pipeline,
+                "switching session pipeline"
+            );
+            session.set_phase(phase);
+            session.set_review_iteration(0);
+            session.set_pipeline(pipeline);
+        }
+
+        self.resume(&mut session, agent, provider).await
+    }
+
+    /// Load a session that is allowed to resume
+    async fn load_resumable(&self, session_id: &str) -> Result<SessionState> {
         let storage = self
             .storage
             .as_ref()
             .context("storage not configured for session tracking")?;
 
-        let mut session = storage
+        let session = storage
             .load(session_id)
             .await?
             .context(format!("session not found: {}", session_id))?;
@@ -98,7 +663,15 @@ impl Executor {
         if !session.can_resume() {
             anyhow::bail!("session cannot be resumed (status: {})", session.status);
         }
+        Ok(session)
+    }
 
+    async fn resume(
+        &self,
+        session: &mut SessionState,
+        agent: &dyn Agent,
+        provider: &dyn LlmProvider,
+    ) -> Result<String> {
         info!(
             session_id = %session.id,
             task = %session.task,
@@ -106,11 +679,1138 @@ impl Executor {
             "resuming session"
         );
 
-        self.run_with_session(agent, &mut session, provider).await
+        self.run_with_session(agent, session, provider).await
+    }
+
+    /// Find the most recently updated resumable session with exactly this
+    /// task text and working directory: one whose status
+    /// [can resume](SessionStatus::can_resume), except sessions left in
+    /// progress that another process is still running (their lock isn't
+    /// stale).
+    pub async fn find_resumable(
+        &self,
+        task: &str,
+        working_dir: &str,
+    ) -> Result<Option<SessionState>> {
+        let storage = self
+            .storage
+            .as_ref()
+            .context("storage not configured for session tracking")?;
+
+        // Summaries are listed most recently updated first
+        let candidates = storage
+            .list()
+            .await?
+            .into_iter()
+            .filter(|s| s.task == task && s.working_dir == working_dir);
+        for summary in candidates {
+            let resumable = match summary.status {
+                SessionStatus::InProgress => self.lock_is_stale(storage, &summary.id).await?,
+                status => status.can_resume(),
+            };
+            if resumable {
+                return storage.load(&summary.id).await;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether no live run holds session `id`'s lock, checked by briefly
+    /// taking the lock ourselves
+    async fn lock_is_stale(&self, storage: &Arc<dyn Storage>, id: &str) -> Result<bool> {
+        let probe = Uuid::new_v4().to_string();
+        if !storage
+            .try_lock(id, &probe, self.session_lock_timeout)
+            .await?
+        {
+            return Ok(false);
+        }
+        storage.unlock(id, &probe).await?;
+        Ok(true)
+    }
+
+    /// Resume the session matching `session`'s task and working directory
+    /// if one is resumable (see [`Executor::find_resumable`]), otherwise run
+    /// `session` as a new session. When resuming, `session` is replaced by
+    /// the stored one, keeping the metadata tags set on `session`.
+    pub async fn resume_or_start(
+        &self,
+        agent: &dyn Agent,
+        session: &mut SessionState,
+        provider: &dyn LlmProvider,
+    ) -> Result<String> {
+        if let Some(existing) = self
+            .find_resumable(&session.task, &session.working_dir)
+            .await?
+        {
+            if existing.id != session.id {
+                info!(
+                    session_id = %existing.id,
+                    phase = %existing.phase,
+                    "resuming existing session for the same task"
+                );
+                let metadata = std::mem::take(&mut session.metadata);
+                *session = existing;
+                for (key, value) in metadata {
+                    session.set_metadata(key, value);
+                }
+            }
+        }
+
+        self.run_with_session(agent, session, provider).await
+    }
+
+    /// Render a stored session as a shareable Markdown report (see
+    /// [`SessionState::to_markdown`])
+    pub async fn export_markdown(&self, session_id: &str) -> Result<String> {
+        let storage = self
+            .storage
+            .as_ref()
+            .context("storage not configured for session tracking")?;
+
+        let session = storage
+            .load(session_id)
+            .await?
+            .context(format!("session not found: {}", session_id))?;
+
+        Ok(session.to_markdown())
     }
 
     /// Get storage reference for direct operations
     pub fn storage(&self) -> Option<&dyn Storage> {
-        self.storage.as_ref().map(|s| s.as_ref())
+        self.storage.as_deref()
+    }
+}
+
+/// A run context carrying the request's pre-computed plan, if any. Fails
+/// if the agent wouldn't use the plan.
+fn request_context(agent: &dyn Agent, request: &TaskRequest) -> Result<RunContext> {
+    match &request.pre_computed_plan {
+        Some(_) if !agent.accepts_plan() => {
+            anyhow::bail!("this agent does not take a pre-computed plan; use the orchestrator")
+        }
+        Some(plan) => Ok(RunContext::new().with_plan(plan)),
+        None => Ok(RunContext::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{AgentLoopConfig, CoderAgent, OrchestratorAgent};
+    use crate::config::Policy;
+    use crate::session::SqliteStorage;
+    use crate::testing::{MockLlmProvider, text_response};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+
+    /// Agent that counts how many times it was run
+    struct CountingAgent {
+        runs: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Agent for CountingAgent {
+        fn system_prompt(&self) -> String {
+            String::new()
+        }
+
+        async fn run(
+            &self,
+            _task: &str,
+            _provider: &dyn LlmProvider,
+            _tools: &ToolRegistry,
+        ) -> Result<String> {
+            let run = self.runs.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!("run {}", run))
+        }
+    }
+
+    /// Agent that records the peak number of concurrent runs
+    struct SlowAgent {
+        active: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Agent for SlowAgent {
+        fn system_prompt(&self) -> String {
+            String::new()
+        }
+
+        async fn run(
+            &self,
+            _task: &str,
+            _provider: &dyn LlmProvider,
+            _tools: &ToolRegistry,
+        ) -> Result<String> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(String::new())
+        }
+    }
+
+    fn slow_agent() -> SlowAgent {
+        SlowAgent {
+            active: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn run_limit_of_one_serializes_runs() {
+        let executor = Executor::new(ToolRegistry::new()).with_max_concurrent_runs(1);
+        let agent = slow_agent();
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let (a, b) = tokio::join!(
+            executor.run(&agent, "first", &provider),
+            executor.run(&agent, "second", &provider)
+        );
+
+        a.unwrap();
+        b.unwrap();
+        assert_eq!(agent.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn unlimited_executor_runs_concurrently() {
+        let executor = Executor::new(ToolRegistry::new());
+        let agent = slow_agent();
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let (a, b) = tokio::join!(
+            executor.run(&agent, "first", &provider),
+            executor.run(&agent, "second", &provider)
+        );
+
+        a.unwrap();
+        b.unwrap();
+        assert_eq!(agent.peak.load(Ordering::SeqCst), 2);
+    }
+
+    fn executor_with(storage: &MemoryStorage) -> Executor {
+        Executor::with_storage(ToolRegistry::new(), Box::new(storage.clone()))
+    }
+
+    fn memory_executor() -> Executor {
+        Executor::with_memory_storage(ToolRegistry::new())
+    }
+
+    #[tokio::test]
+    async fn same_idempotency_key_runs_once() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut first = SessionState::new("task", ".");
+        first.set_idempotency_key("req-42");
+        let first_output = executor
+            .run_with_session(&agent, &mut first, &provider)
+            .await
+            .unwrap();
+
+        let mut second = SessionState::new("task", ".");
+        second.set_idempotency_key("req-42");
+        let second_output = executor
+            .run_with_session(&agent, &mut second, &provider)
+            .await
+            .unwrap();
+
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 1);
+        assert_eq!(second_output, first_output);
+        assert_eq!(second.id, first.id);
+        assert_eq!(executor.storage().unwrap().list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_of_a_live_run_is_refused() {
+        let executor = memory_executor();
+        let storage = executor.storage().unwrap();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut running = SessionState::new("task", ".");
+        running.set_idempotency_key("req-42");
+        running.set_status(SessionStatus::InProgress);
+        storage.save(&running).await.unwrap();
+        assert!(
+            storage
+                .try_lock(&running.id, "other process", SESSION_LOCK_STALE_AFTER)
+                .await
+                .unwrap()
+        );
+
+        let mut session = SessionState::new("task", ".");
+        session.set_idempotency_key("req-42");
+        let err = executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("already in progress"));
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_of_a_crashed_run_can_be_reused() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        // Left in progress by a process that died, so nothing holds its lock
+        let mut crashed = SessionState::new("task", ".");
+        crashed.set_idempotency_key("req-42");
+        crashed.set_status(SessionStatus::InProgress);
+        executor.storage().unwrap().save(&crashed).await.unwrap();
+
+        let mut session = SessionState::new("task", ".");
+        session.set_idempotency_key("req-42");
+        executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 1);
+        assert_ne!(session.id, crashed.id);
+    }
+
+    #[tokio::test]
+    async fn concurrent_submits_with_one_idempotency_key_run_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+        let executor = Executor::with_storage(ToolRegistry::new(), Box::new(storage));
+        let agent = slow_agent();
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut first = SessionState::new("task", ".");
+        first.set_idempotency_key("req-42");
+        let mut second = SessionState::new("task", ".");
+        second.set_idempotency_key("req-42");
+        let (a, b) = tokio::join!(
+            executor.run_with_session(&agent, &mut first, &provider),
+            executor.run_with_session(&agent, &mut second, &provider)
+        );
+
+        // Either the second submit saw the first still running and was
+        // refused, or it got the first's result; it never ran alongside it
+        assert!(a.is_ok() || b.is_ok());
+        assert_eq!(agent.peak.load(Ordering::SeqCst), 1);
+        let started = executor.storage().unwrap().list().await.unwrap();
+        assert_eq!(started.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn different_idempotency_keys_run_separately() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        for key in ["a", "b"] {
+            let mut session = SessionState::new("task", ".");
+            session.set_idempotency_key(key);
+            executor
+                .run_with_session(&agent, &mut session, &provider)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn resume_or_start_reuses_interrupted_session_for_same_task() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        // A previous invocation that was killed part way through
+        let mut interrupted = SessionState::new("fix the build", "/repo");
+        interrupted.set_status(SessionStatus::Interrupted);
+        executor
+            .storage()
+            .unwrap()
+            .save(&interrupted)
+            .await
+            .unwrap();
+
+        let mut session = SessionState::new("fix the build", "/repo");
+        executor
+            .resume_or_start(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+
+        assert_eq!(session.id, interrupted.id);
+        assert_eq!(session.status, SessionStatus::Completed);
+        assert_eq!(executor.storage().unwrap().list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_session_can_be_resumed_and_its_error_is_cleared() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut failed = SessionState::new("fix the build", "/repo");
+        failed.set_error("rate limited");
+        executor.storage().unwrap().save(&failed).await.unwrap();
+
+        executor
+            .resume_session(&failed.id, &agent, &provider)
+            .await
+            .unwrap();
+
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&failed.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.status, SessionStatus::Completed);
+        assert!(saved.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn resume_or_start_skips_sessions_still_running_elsewhere() {
+        let executor = memory_executor();
+        let storage = executor.storage().unwrap();
+
+        // One run is alive and holding its lock; another died without
+        // unlocking, so its lock went stale
+        let mut running = SessionState::new("fix the build", "/repo");
+        running.set_status(SessionStatus::InProgress);
+        storage.save(&running).await.unwrap();
+        assert!(
+            storage
+                .try_lock(&running.id, "other process", SESSION_LOCK_STALE_AFTER)
+                .await
+                .unwrap()
+        );
+        assert!(
+            executor
+                .find_resumable("fix the build", "/repo")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mut crashed = SessionState::new("fix the build", "/repo");
+        crashed.set_status(SessionStatus::InProgress);
+        storage.save(&crashed).await.unwrap();
+        let found = executor
+            .find_resumable("fix the build", "/repo")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, crashed.id);
+
+        // Completed sessions are never picked up
+        storage.delete(&crashed.id).await.unwrap();
+        let mut completed = SessionState::new("fix the build", "/repo");
+        completed.complete("done");
+        storage.save(&completed).await.unwrap();
+        assert!(
+            executor
+                .find_resumable("fix the build", "/repo")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_or_start_keeps_the_new_sessions_tags() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut failed = SessionState::new("fix the build", "/repo");
+        failed.set_metadata("team", "core");
+        failed.set_metadata("ticket", "OLD-1");
+        failed.set_error("provider unavailable");
+        executor.storage().unwrap().save(&failed).await.unwrap();
+
+        let mut session = SessionState::new("fix the build", "/repo");
+        session.set_metadata("ticket", "NEW-2");
+        executor
+            .resume_or_start(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+
+        assert_eq!(session.id, failed.id);
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&failed.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.metadata["team"], "core");
+        assert_eq!(saved.metadata["ticket"], "NEW-2");
+    }
+
+    #[tokio::test]
+    async fn resume_or_start_starts_fresh_for_other_working_dir() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut interrupted = SessionState::new("fix the build", "/repo");
+        interrupted.set_status(SessionStatus::Interrupted);
+        executor
+            .storage()
+            .unwrap()
+            .save(&interrupted)
+            .await
+            .unwrap();
+
+        let mut session = SessionState::new("fix the build", "/other");
+        let new_id = session.id.clone();
+        executor
+            .resume_or_start(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+
+        assert_eq!(session.id, new_id);
+        assert_eq!(executor.storage().unwrap().list().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn exported_markdown_has_task_and_a_section_per_phase() {
+        let executor = Executor::with_storage(
+            ToolRegistry::with_default_tools(&Policy::default()),
+            Box::new(MemoryStorage::new()),
+        );
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("PLAN-OUTPUT"),
+            text_response("IMPL-OUTPUT"),
+            text_response("TEST-OUTPUT"),
+            text_response("REVIEW-OUTPUT\nVERDICT: APPROVED"),
+        ]);
+
+        let mut session = SessionState::new("add a greeting", "/repo");
+        executor
+            .run_with_session(&OrchestratorAgent::new(), &mut session, &provider)
+            .await
+            .unwrap();
+
+        let markdown = executor.export_markdown(&session.id).await.unwrap();
+        let positions: Vec<usize> = [
+            "## Task\n\nadd a greeting",
+            "## Planning\n\nPLAN-OUTPUT",
+            "## Implementing\n\nIMPL-OUTPUT",
+            "## Testing\n\nTEST-OUTPUT",
+            "## Reviewing\n\nREVIEW-OUTPUT",
+            "## Result\n\n# Task Completed",
+        ]
+        .iter()
+        .map(|section| {
+            markdown
+                .find(section)
+                .unwrap_or_else(|| panic!("missing {:?} in:\n{}", section, markdown))
+        })
+        .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(markdown.contains("- **Status:** completed"));
+    }
+
+    #[tokio::test]
+    async fn exporting_unknown_session_fails() {
+        let executor = memory_executor();
+
+        let err = executor.export_markdown("missing").await.unwrap_err();
+        assert_eq!(err.to_string(), "session not found: missing");
+    }
+
+    /// Agent that signals when it starts and then waits to be released
+    struct GatedAgent {
+        started: Notify,
+        release: Notify,
+    }
+
+    #[async_trait]
+    impl Agent for GatedAgent {
+        fn system_prompt(&self) -> String {
+            String::new()
+        }
+
+        async fn run(
+            &self,
+            _task: &str,
+            _provider: &dyn LlmProvider,
+            _tools: &ToolRegistry,
+        ) -> Result<String> {
+            self.started.notify_one();
+            self.release.notified().await;
+            Ok("done".to_string())
+        }
+    }
+
+    /// Agent that runs until its run is cancelled
+    struct UntilCancelledAgent;
+
+    #[async_trait]
+    impl Agent for UntilCancelledAgent {
+        fn system_prompt(&self) -> String {
+            String::new()
+        }
+
+        async fn run(
+            &self,
+            _task: &str,
+            _provider: &dyn LlmProvider,
+            _tools: &ToolRegistry,
+        ) -> Result<String> {
+            unreachable!("run through run_with_context")
+        }
+
+        async fn run_with_context(
+            &self,
+            _task: &str,
+            _provider: &dyn LlmProvider,
+            _tools: &ToolRegistry,
+            ctx: &RunContext,
+        ) -> Result<String> {
+            ctx.cancellation().cancelled().await;
+            Err(RunCancelled.into())
+        }
+    }
+
+    #[tokio::test]
+    async fn run_is_stopped_when_its_lock_is_taken_over() {
+        let storage = MemoryStorage::new();
+        let executor = executor_with(&storage).with_session_lock_timeout(Duration::from_millis(60));
+        let provider = MockLlmProvider::with_responses(Vec::new());
+        let mut session = SessionState::new("fix the build", "/repo");
+        let id = session.id.clone();
+
+        let (result, ()) = tokio::join!(
+            executor.run_with_session(&UntilCancelledAgent, &mut session, &provider),
+            async {
+                // Another process decides the lock is stale and takes it
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                assert!(
+                    storage
+                        .try_lock(&id, "intruder", Duration::ZERO)
+                        .await
+                        .unwrap()
+                );
+            }
+        );
+
+        let err = result.unwrap_err();
+        assert!(err.is::<SessionBusy>());
+        // The intruder keeps the lock and the stored session isn't overwritten
+        assert!(
+            !storage
+                .try_lock(&id, "third", Duration::from_secs(3600))
+                .await
+                .unwrap()
+        );
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SessionStatus::InProgress);
+    }
+
+    #[tokio::test]
+    async fn concurrent_resume_of_same_session_is_rejected() {
+        // Separate executors on one store stand in for separate processes
+        let storage = MemoryStorage::new();
+        let first = executor_with(&storage);
+        let second = executor_with(&storage);
+        let agent = GatedAgent {
+            started: Notify::new(),
+            release: Notify::new(),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut session = SessionState::new("fix the build", "/repo");
+        session.set_status(SessionStatus::Interrupted);
+        first.storage().unwrap().save(&session).await.unwrap();
+
+        let (a, b) = tokio::join!(
+            first.resume_session(&session.id, &agent, &provider),
+            async {
+                agent.started.notified().await;
+                let result = second.resume_session(&session.id, &agent, &provider).await;
+                agent.release.notify_one();
+                result
+            }
+        );
+
+        assert_eq!(a.unwrap(), "done");
+        let busy = b.unwrap_err();
+        assert_eq!(
+            busy.downcast_ref::<SessionBusy>(),
+            Some(&SessionBusy {
+                session_id: session.id.clone()
+            })
+        );
+
+        // The lock is released once the run finishes
+        let hour = Duration::from_secs(3600);
+        assert!(
+            first
+                .storage()
+                .unwrap()
+                .try_lock(&session.id, "someone-else", hour)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_over_runtime_budget_is_refused_on_resume() {
+        let storage = MemoryStorage::new();
+        let mut session = SessionState::new("task", ".");
+        session.set_status(SessionStatus::Interrupted);
+        session.total_runtime_ms = 3_600_000;
+        storage.save(&session).await.unwrap();
+
+        let executor = executor_with(&storage).with_session_budget(SessionBudget {
+            max_runtime: Some(Duration::from_secs(600)),
+            max_iterations: None,
+        });
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let err = executor
+            .resume_session(&session.id, &agent, &provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("3600s of its 600s runtime budget"));
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn sub_second_runs_count_toward_the_runtime_budget() {
+        let budget = SessionBudget {
+            max_runtime: Some(Duration::from_secs(1)),
+            max_iterations: None,
+        };
+        let mut session = SessionState::new("task", ".");
+        for _ in 0..3 {
+            session.add_runtime(Duration::from_millis(400));
+        }
+
+        assert_eq!(session.total_runtime(), Duration::from_millis(1200));
+        assert!(budget.exceeded_by(&session).is_some());
+    }
+
+    #[tokio::test]
+    async fn llm_round_trips_accumulate_across_runs() {
+        let storage = MemoryStorage::new();
+        let executor = executor_with(&storage).with_session_budget(SessionBudget {
+            max_runtime: None,
+            max_iterations: Some(2),
+        });
+        let agent = CoderAgent::new();
+        let provider = MockLlmProvider::with_responses(vec![text_response("first")]);
+
+        let mut session = SessionState::new("task", ".");
+        executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+        assert_eq!(session.total_iterations, 1);
+
+        // Still within budget: one more run is allowed, then it is spent
+        session.set_status(SessionStatus::Interrupted);
+        let provider = MockLlmProvider::with_responses(vec![text_response("second")]);
+        executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+        assert_eq!(session.total_iterations, 2);
+
+        let err = executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("2 of its 2 allowed LLM round-trips")
+        );
+    }
+
+    #[tokio::test]
+    async fn run_exceeding_the_timeout_is_interrupted() {
+        let executor = memory_executor().with_run_timeout(Duration::from_millis(50));
+        let provider = MockLlmProvider::single_response("done").with_delay(Duration::from_secs(5));
+
+        let mut session = SessionState::new("task", ".");
+        let started = Instant::now();
+        let err = executor
+            .run_with_session(&CoderAgent::new(), &mut session, &provider)
+            .await
+            .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            err.downcast_ref::<RunTimedOut>(),
+            Some(&RunTimedOut {
+                after: Duration::from_millis(50)
+            })
+        );
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.status, SessionStatus::Interrupted);
+        assert!(saved.can_resume());
+    }
+
+    /// Tool that takes a while, standing in for a long build
+    struct SlowTool;
+
+    #[async_trait]
+    impl crate::tools::Tool for SlowTool {
+        fn name(&self) -> &str {
+            "slow"
+        }
+        fn description(&self) -> &str {
+            "slow tool"
+        }
+        fn schema(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object" })
+        }
+        async fn execute(&self, _params: serde_json::Value) -> Result<String> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok("built".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn timed_out_run_checkpoints_the_tool_call_in_progress() {
+        let mut tools = ToolRegistry::new();
+        tools.register(SlowTool);
+        let executor =
+            Executor::with_memory_storage(tools).with_run_timeout(Duration::from_millis(50));
+        let provider = MockLlmProvider::with_responses(vec![
+            crate::testing::tool_call_response("1", "slow", serde_json::json!({})),
+            text_response("done"),
+        ]);
+
+        let mut session = SessionState::new("task", ".");
+        let err = executor
+            .run_with_session(&CoderAgent::new(), &mut session, &provider)
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<RunTimedOut>());
+        // The tool finished and its result was saved, so resume won't rerun it
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let result = saved.messages.last().unwrap().tool_result.as_ref().unwrap();
+        assert_eq!(result.result, "built");
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_within_the_timeout_completes() {
+        let executor = Executor::new(ToolRegistry::new()).with_run_timeout(Duration::from_secs(5));
+        let provider = MockLlmProvider::single_response("done");
+
+        let output = executor
+            .run(&CoderAgent::new(), "task", &provider)
+            .await
+            .unwrap();
+        assert_eq!(output, "done");
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_a_long_run_promptly() {
+        let executor = memory_executor();
+        let provider = MockLlmProvider::single_response("done").with_delay(Duration::from_secs(30));
+        let agent = CoderAgent::new();
+        let mut session = SessionState::new("task", ".");
+
+        let started = Instant::now();
+        let (result, ()) = tokio::join!(
+            executor.run_with_session(&agent, &mut session, &provider),
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                executor.cancel();
+            }
+        );
+
+        assert!(result.unwrap_err().is::<RunCancelled>());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.status, SessionStatus::Interrupted);
+    }
+
+    #[tokio::test]
+    async fn cancelled_session_is_saved_as_interrupted() {
+        let executor = memory_executor();
+        let provider = MockLlmProvider::with_responses(vec![text_response("done")]);
+        executor.cancel();
+
+        let mut session = SessionState::new("task", ".");
+        let err = executor
+            .run_with_session(&CoderAgent::new(), &mut session, &provider)
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<RunCancelled>());
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load(&session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.status, SessionStatus::Interrupted);
+        assert!(saved.error.is_none());
+        assert!(saved.can_resume());
+    }
+
+    #[test]
+    fn only_tools_keeps_just_the_named_tools() {
+        let executor = Executor::new(ToolRegistry::with_default_tools(&Policy::default()))
+            .only_tools(&["read_file", "glob", "grep"]);
+
+        assert_eq!(executor.tool_names(), ["glob", "grep", "read_file"]);
+        assert!(!executor.tool_names().contains(&"shell"));
+    }
+
+    #[test]
+    fn without_tools_removes_the_named_tools() {
+        let executor = Executor::new(ToolRegistry::with_default_tools(&Policy::default()))
+            .without_tools(&["shell", "write_file"]);
+
+        let names = executor.tool_names();
+        assert!(!names.contains(&"shell"));
+        assert!(!names.contains(&"write_file"));
+        assert!(names.contains(&"read_file"));
+    }
+
+    #[tokio::test]
+    async fn simple_session_resumes_under_orchestrator() {
+        let storage = MemoryStorage::new();
+        let mut session = SessionState::new("task", ".");
+        session.set_pipeline(Pipeline::Simple);
+        session.set_phase(SessionPhase::Implementing);
+        session.set_status(SessionStatus::Interrupted);
+        storage.save(&session).await.unwrap();
+
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("plan"),
+            text_response("implementation"),
+            text_response("tests pass"),
+            text_response("VERDICT: APPROVED"),
+        ]);
+        let agent = OrchestratorAgent::new().with_loop_config(AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        });
+
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+        let output = Executor::with_storage(tools, Box::new(storage.clone()))
+            .resume_session_with_pipeline(&session.id, Pipeline::Orchestrated, &agent, &provider)
+            .await
+            .unwrap();
+
+        assert!(output.contains("Status: SUCCESS"));
+        let saved = storage.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(saved.status, SessionStatus::Completed);
+        assert_eq!(saved.pipeline, Some(Pipeline::Orchestrated));
+        // A simple session has no plan, so the orchestrator plans first
+        assert_eq!(saved.phase_outputs[0].phase, SessionPhase::Planning);
+        assert_eq!(provider.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn session_without_a_recorded_pipeline_resumes_where_it_stopped() {
+        let storage = MemoryStorage::new();
+        let mut session = SessionState::new("task", ".");
+        session.set_phase(SessionPhase::Planning);
+        session.record_phase_output("plan");
+        session.set_phase(SessionPhase::Implementing);
+        session.record_phase_output("implementation");
+        session.set_phase(SessionPhase::Testing);
+        session.set_status(SessionStatus::Interrupted);
+        storage.save(&session).await.unwrap();
+
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("tests pass"),
+            text_response("VERDICT: APPROVED"),
+        ]);
+        let agent = OrchestratorAgent::new().with_loop_config(AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        });
+
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+        Executor::with_storage(tools, Box::new(storage.clone()))
+            .resume_session_with_pipeline(&session.id, Pipeline::Orchestrated, &agent, &provider)
+            .await
+            .unwrap();
+
+        // Only testing and review were left to run
+        assert_eq!(provider.requests().len(), 2);
+        let saved = storage.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(saved.pipeline, Some(Pipeline::Orchestrated));
+    }
+
+    #[test]
+    fn phases_map_to_nearest_earlier_phase_of_new_pipeline() {
+        use SessionPhase::*;
+
+        // Orchestrator phases simple mode doesn't have fall back to implementing
+        assert_eq!(Pipeline::Simple.resume_phase(Testing), Implementing);
+        assert_eq!(Pipeline::Simple.resume_phase(Reviewing), Implementing);
+        // ...or start it, when there is no earlier phase
+        assert_eq!(Pipeline::Simple.resume_phase(Planning), Implementing);
+        // Shared phases are kept
+        assert_eq!(
+            Pipeline::Orchestrated.resume_phase(Implementing),
+            Implementing
+        );
+        assert_eq!(Pipeline::Orchestrated.resume_phase(NotStarted), Planning);
+        assert_eq!(Pipeline::Simple.resume_phase(Completed), Completed);
+    }
+
+    #[tokio::test]
+    async fn request_with_a_plan_skips_the_planner() {
+        let storage = MemoryStorage::new();
+        let provider = MockLlmProvider::with_responses(vec![
+            text_response("implementation"),
+            text_response("tests pass"),
+            text_response("VERDICT: APPROVED"),
+        ]);
+        let agent = OrchestratorAgent::new().with_loop_config(AgentLoopConfig {
+            inter_request_delay: Duration::ZERO,
+            ..Default::default()
+        });
+        let request = TaskRequest::new("task")
+            .with_label("backend")
+            .with_plan("1. do the thing")
+            .with_recorded_working_dir("/tmp/project");
+
+        let tools = ToolRegistry::with_default_tools(&Policy::default());
+        let output = Executor::with_storage(tools, Box::new(storage.clone()))
+            .run_request(&agent, request, &provider)
+            .await
+            .unwrap();
+
+        assert!(output.contains("Status: SUCCESS"));
+        assert_eq!(provider.requests().len(), 3);
+        let id = storage.list().await.unwrap()[0].id.clone();
+        let saved = storage.load(&id).await.unwrap().unwrap();
+        assert_eq!(saved.labels(), vec!["backend"]);
+        assert_eq!(saved.working_dir, "/tmp/project");
+        assert_eq!(saved.phase_outputs[0].phase, SessionPhase::Planning);
+        assert_eq!(saved.phase_outputs[0].output, "1. do the thing");
+    }
+
+    #[tokio::test]
+    async fn plan_for_an_agent_that_cannot_use_it_is_refused() {
+        let executor = memory_executor();
+        let provider = MockLlmProvider::single_response("done");
+        let request = TaskRequest::new("task").with_plan("1. do it");
+
+        let err = executor
+            .run(&CoderAgent::new(), request.clone(), &provider)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("does not take a pre-computed plan")
+        );
+        assert!(
+            executor
+                .run_request(&CoderAgent::new(), request, &provider)
+                .await
+                .is_err()
+        );
+        assert!(provider.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn session_with_supplied_id_can_be_loaded_by_it() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        executor
+            .run_with_session_id(&agent, "TICKET-123", "task", &provider)
+            .await
+            .unwrap();
+
+        let saved = executor
+            .storage()
+            .unwrap()
+            .load("TICKET-123")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.task, "task");
+        assert_eq!(saved.status, SessionStatus::Completed);
+
+        let err = executor
+            .run_with_session_id(&agent, "TICKET-123", "other task", &provider)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already in use"));
+        assert_eq!(agent.runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn memory_storage_supports_run_list_and_export() {
+        let executor = memory_executor();
+        let agent = CountingAgent {
+            runs: AtomicUsize::new(0),
+        };
+        let provider = MockLlmProvider::with_responses(Vec::new());
+
+        let mut session = SessionState::new("in-memory task", ".");
+        executor
+            .run_with_session(&agent, &mut session, &provider)
+            .await
+            .unwrap();
+
+        let summaries = executor.storage().unwrap().list().await.unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].id, session.id);
+        let markdown = executor.export_markdown(&session.id).await.unwrap();
+        assert!(markdown.contains("in-memory task"));
     }
 }
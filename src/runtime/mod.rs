@@ -1,3 +1,5 @@
 mod executor;
+mod task;
 
-pub use executor::Executor;
+pub use executor::{Executor, RunTimedOut, SESSION_LOCK_STALE_AFTER};
+pub use task::TaskRequest;
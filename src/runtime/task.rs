@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::session::SessionState;
+
+/// A unit of work submitted to the [`Executor`](super::Executor).
+///
+/// Plain strings convert into a request with no labels, plan or working
+/// directory, so `executor.run(&agent, "task", &provider)` keeps working.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskRequest {
+    /// What the agent should do
+    pub task: String,
+
+    /// Free-form labels recorded on the session for filtering
+    pub labels: Vec<String>,
+
+    /// A plan computed ahead of time; the orchestrator uses it instead of
+    /// running the planner
+    pub pre_computed_plan: Option<String>,
+
+    /// Directory recorded as the session's working directory (defaults to
+    /// the current one). Only recorded: tools still run in, and resolve
+    /// paths against, the process's current directory.
+    pub recorded_working_dir: Option<PathBuf>,
+}
+
+impl TaskRequest {
+    pub fn new(task: impl Into<String>) -> Self {
+        Self {
+            task: task.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Add a label
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.labels.push(label.into());
+        self
+    }
+
+    /// Add several labels
+    pub fn with_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.labels.extend(labels.into_iter().map(Into::into));
+        self
+    }
+
+    /// Skip planning and use `plan` instead
+    pub fn with_plan(mut self, plan: impl Into<String>) -> Self {
+        self.pre_computed_plan = Some(plan.into());
+        self
+    }
+
+    /// Record the session against `dir` instead of the current directory.
+    /// This doesn't change where tools run.
+    pub fn with_recorded_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.recorded_working_dir = Some(dir.into());
+        self
+    }
+
+    /// A new session for this request, carrying its labels
+    pub fn new_session(&self) -> Result<SessionState> {
+        let working_dir = match &self.recorded_working_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir().context("failed to get current directory")?,
+        };
+        let mut session = SessionState::new(&self.task, working_dir.to_string_lossy());
+        if !self.labels.is_empty() {
+            session.set_labels(&self.labels);
+        }
+        Ok(session)
+    }
+}
+
+impl From<&str> for TaskRequest {
+    fn from(task: &str) -> Self {
+        Self::new(task)
+    }
+}
+
+impl From<String> for TaskRequest {
+    fn from(task: String) -> Self {
+        Self::new(task)
+    }
+}
+
+impl From<&String> for TaskRequest {
+    fn from(task: &String) -> Self {
+        Self::new(task.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_session_carries_labels_and_working_dir() {
+        let request = TaskRequest::new("add a flag")
+            .with_label("cli")
+            .with_labels(["small", "ui"])
+            .with_recorded_working_dir("/tmp/project")
+            .with_label("a,b");
+
+        let session = request.new_session().unwrap();
+        assert_eq!(session.task, "add a flag");
+        assert_eq!(session.working_dir, "/tmp/project");
+        assert_eq!(session.labels(), vec!["cli", "small", "ui", "a,b"]);
+    }
+
+    #[test]
+    fn plain_strings_convert_to_bare_requests() {
+        let request = TaskRequest::from("fix the bug");
+        assert_eq!(request, TaskRequest::new("fix the bug"));
+        assert!(request.pre_computed_plan.is_none());
+    }
+}
@@ -0,0 +1,18 @@
+/// Another run currently holds the session's lock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionBusy {
+    /// The session that is already running
+    pub session_id: String,
+}
+
+impl std::fmt::Display for SessionBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "session {} is already running in another process",
+            self.session_id
+        )
+    }
+}
+
+impl std::error::Error for SessionBusy {}
@@ -0,0 +1,307 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, broadcast};
+
+use super::state::SessionSummary;
+use super::{SessionEvent, SessionState, SessionStatus, Storage};
+
+/// How many unread events a slow watcher can fall behind by
+const EVENT_CAPACITY: usize = 64;
+
+/// In-memory session storage for tests and in-process use.
+///
+/// Nothing is persisted. Clones share the same sessions, locks and watchers,
+/// so several executors can use one store as if it were a shared database.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    sessions: Arc<Mutex<HashMap<String, SessionState>>>,
+    /// Session id -> (owner, last refreshed)
+    locks: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl MemoryStorage {
+    /// Create an empty store
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            locks: Arc::new(Mutex::new(HashMap::new())),
+            events,
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Summarize a session the way the database backends do
+fn summarize(session: &SessionState) -> SessionSummary {
+    SessionSummary {
+        id: session.id.clone(),
+        task: session.task.clone(),
+        status: session.status,
+        phase: session.phase,
+        working_dir: session.working_dir.clone(),
+        created_at: session.created_at.to_rfc3339(),
+        updated_at: session.updated_at.to_rfc3339(),
+        error: session.error.clone(),
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn save(&self, session: &SessionState) -> Result<()> {
+        self.sessions
+            .lock()
+            .await
+            .insert(session.id.clone(), session.clone());
+
+        // No receivers is fine
+        let _ = self.events.send(SessionEvent::Saved {
+            id: session.id.clone(),
+            status: session.status,
+            phase: session.phase,
+        });
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Option<SessionState>> {
+        Ok(self.sessions.lock().await.get(id).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<SessionSummary>> {
+        let sessions = self.sessions.lock().await;
+        let mut sorted: Vec<&SessionState> = sessions.values().collect();
+        sorted.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(sorted.into_iter().map(summarize).collect())
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        if self.sessions.lock().await.remove(id).is_none() {
+            anyhow::bail!("session '{}' not found", id);
+        }
+        self.locks.lock().await.remove(id);
+
+        let _ = self
+            .events
+            .send(SessionEvent::Deleted { id: id.to_string() });
+        Ok(())
+    }
+
+    async fn delete_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        only_status: Option<SessionStatus>,
+        stale_after: Duration,
+    ) -> Result<usize> {
+        let mut sessions = self.sessions.lock().await;
+        let mut locks = self.locks.lock().await;
+        let now = Instant::now();
+        let live =
+            |(_, refreshed): &(String, Instant)| now.duration_since(*refreshed) <= stale_after;
+        let expired: Vec<String> = sessions
+            .values()
+            .filter(|s| {
+                s.updated_at < cutoff
+                    && only_status.is_none_or(|status| s.status == status)
+                    && !locks.get(&s.id).is_some_and(live)
+            })
+            .map(|s| s.id.clone())
+            .collect();
+        for id in &expired {
+            sessions.remove(id);
+        }
+        locks.retain(|id, lock| sessions.contains_key(id) || live(lock));
+
+        for id in &expired {
+            let _ = self.events.send(SessionEvent::Deleted { id: id.clone() });
+        }
+        Ok(expired.len())
+    }
+
+    async fn find_by_metadata(&self, key: &str, value: &str) -> Result<Vec<SessionState>> {
+        let sessions = self.sessions.lock().await;
+        let mut matches: Vec<SessionState> = sessions
+            .values()
+            .filter(|s| s.metadata.get(key).map(String::as_str) == Some(value))
+            .cloned()
+            .collect();
+        matches.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(matches)
+    }
+
+    async fn try_lock(&self, id: &str, owner: &str, stale_after: Duration) -> Result<bool> {
+        let mut locks = self.locks.lock().await;
+        let now = Instant::now();
+        let available = locks.get(id).is_none_or(|(holder, refreshed)| {
+            holder == owner || now.duration_since(*refreshed) > stale_after
+        });
+        if available {
+            locks.insert(id.to_string(), (owner.to_string(), now));
+        }
+        Ok(available)
+    }
+
+    async fn unlock(&self, id: &str, owner: &str) -> Result<()> {
+        let mut locks = self.locks.lock().await;
+        if locks.get(id).is_some_and(|(holder, _)| holder == owner) {
+            locks.remove(id);
+        }
+        Ok(())
+    }
+
+    fn watch(&self) -> Result<broadcast::Receiver<SessionEvent>> {
+        Ok(self.events.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn list_is_most_recently_updated_first() {
+        let storage = MemoryStorage::new();
+        let mut older = SessionState::new("older", ".");
+        let newer = SessionState::new("newer", ".");
+        older.updated_at = newer.updated_at - chrono::Duration::seconds(10);
+        storage.save(&newer).await.unwrap();
+        storage.save(&older).await.unwrap();
+
+        let tasks: Vec<String> = storage
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.task)
+            .collect();
+        assert_eq!(tasks, ["newer", "older"]);
+    }
+
+    #[tokio::test]
+    async fn delete_older_than_skips_only_sessions_with_a_live_lock() {
+        let storage = MemoryStorage::new();
+        let old = chrono::Utc::now() - chrono::Duration::days(40);
+        let mut ids = Vec::new();
+        for status in [
+            SessionStatus::InProgress,
+            SessionStatus::InProgress,
+            SessionStatus::Completed,
+        ] {
+            let mut session = SessionState::new("task", ".");
+            session.set_status(status);
+            session.updated_at = old;
+            storage.save(&session).await.unwrap();
+            ids.push(session.id);
+        }
+        // The first run is alive; the second crashed without unlocking
+        let stale_after = Duration::from_millis(50);
+        let hour = Duration::from_secs(3600);
+        assert!(storage.try_lock(&ids[1], "crashed", hour).await.unwrap());
+        tokio::time::sleep(stale_after * 2).await;
+        assert!(storage.try_lock(&ids[0], "runner", hour).await.unwrap());
+
+        let deleted = storage
+            .delete_older_than(chrono::Utc::now(), None, stale_after)
+            .await;
+        assert_eq!(deleted.unwrap(), 2);
+        assert!(storage.load(&ids[0]).await.unwrap().is_some());
+        assert_eq!(storage.locks.lock().await.len(), 1);
+
+        // The lock goes with the session
+        storage.delete(&ids[0]).await.unwrap();
+        assert!(storage.locks.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_load_and_delete_round_trip() {
+        let storage = MemoryStorage::new();
+        let mut session = SessionState::new("task", "/work");
+        session.add_message(crate::llm::Message::user("hello"));
+        storage.save(&session).await.unwrap();
+
+        let loaded = storage.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(loaded.task, "task");
+        assert_eq!(loaded.working_dir, "/work");
+        assert_eq!(loaded.messages.len(), 1);
+
+        storage.delete(&session.id).await.unwrap();
+        assert!(storage.load(&session.id).await.unwrap().is_none());
+        assert!(storage.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn saving_again_replaces_the_session() {
+        let storage = MemoryStorage::new();
+        let mut session = SessionState::new("task", ".");
+        storage.save(&session).await.unwrap();
+        session.set_status(SessionStatus::Completed);
+        storage.save(&session).await.unwrap();
+
+        let summaries = storage.list().await.unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].status, SessionStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn clones_share_sessions() {
+        let storage = MemoryStorage::new();
+        let session = SessionState::new("task", ".");
+        storage.clone().save(&session).await.unwrap();
+
+        assert!(storage.load(&session.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn default_search_filters_in_memory() {
+        let storage = MemoryStorage::new();
+        for task in ["Fix login bug", "Add dark mode", "fix typo"] {
+            storage.save(&SessionState::new(task, ".")).await.unwrap();
+        }
+
+        let mut found: Vec<String> = storage
+            .search("FIX")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.task)
+            .collect();
+        found.sort();
+        assert_eq!(found, ["Fix login bug", "fix typo"]);
+    }
+
+    #[tokio::test]
+    async fn default_delete_older_than_filters_by_age_and_status() {
+        let storage = MemoryStorage::new();
+        let now = chrono::Utc::now();
+        for (days_old, status) in [
+            (10, SessionStatus::Completed),
+            (10, SessionStatus::Failed),
+            (1, SessionStatus::Completed),
+        ] {
+            let mut session = SessionState::new("task", ".");
+            session.set_status(status);
+            session.updated_at = now - chrono::Duration::days(days_old);
+            storage.save(&session).await.unwrap();
+        }
+
+        let cutoff = now - chrono::Duration::days(7);
+        let hour = Duration::from_secs(3600);
+        let deleted = storage
+            .delete_older_than(cutoff, Some(SessionStatus::Completed), hour)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        let deleted = storage.delete_older_than(cutoff, None, hour).await;
+        assert_eq!(deleted.unwrap(), 1);
+        assert_eq!(storage.list().await.unwrap().len(), 1);
+    }
+}
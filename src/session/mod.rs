@@ -1,7 +1,19 @@
+mod error;
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod sqlite;
 mod state;
 mod storage;
 
+pub use error::SessionBusy;
+pub use memory::MemoryStorage;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
 pub use sqlite::SqliteStorage;
-pub use state::{SessionPhase, SessionState, SessionStatus, SessionSummary};
-pub use storage::Storage;
+pub use state::{
+    AgentConversation, IDEMPOTENCY_KEY, LABELS_KEY, PhaseOutput, Pipeline, PolicyDenial,
+    RESERVED_METADATA_KEYS, SessionBudget, SessionPhase, SessionState, SessionStatus,
+    SessionSummary,
+};
+pub use storage::{SessionEvent, SessionFilter, Storage};
@@ -1,17 +1,26 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use rusqlite::Connection;
 use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::broadcast;
 use tokio::task;
 use tracing::debug;
 
 use super::state::SessionSummary;
-use super::{SessionPhase, SessionState, SessionStatus, Storage};
+use super::storage::contains_pattern;
+use super::{SessionEvent, SessionFilter, SessionPhase, SessionState, SessionStatus, Storage};
+
+/// How many unread events a slow watcher can fall behind by
+const EVENT_CAPACITY: usize = 64;
 
 /// SQLite-based session storage
 pub struct SqliteStorage {
     /// Path to the SQLite database file
     db_path: PathBuf,
+    /// Notifies watchers after successful writes
+    events: broadcast::Sender<SessionEvent>,
 }
 
 impl SqliteStorage {
@@ -25,7 +34,8 @@ impl SqliteStorage {
                 .with_context(|| format!("failed to create directory: {}", parent.display()))?;
         }
 
-        let storage = Self { db_path };
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let storage = Self { db_path, events };
         storage.init_schema()?;
 
         Ok(storage)
@@ -77,15 +87,51 @@ impl SqliteStorage {
         )
         .context("failed to create updated_at index")?;
 
+        // Advisory locks held by running sessions; acquired_at is refreshed
+        // by the holder so abandoned locks can be detected
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS session_locks (
+                session_id TEXT PRIMARY KEY,
+                owner TEXT NOT NULL,
+                acquired_at INTEGER NOT NULL
+            )",
+            [],
+        )
+        .context("failed to create session_locks table")?;
+
         debug!(path = %self.db_path.display(), "initialized SQLite storage");
 
         Ok(())
     }
 }
 
+/// Columns read by [`summary_from_row`], in order
+const SUMMARY_COLUMNS: &str = "id, task, status, phase, working_dir, created_at, updated_at, error";
+
+/// Build a summary from a row selecting [`SUMMARY_COLUMNS`]
+fn summary_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SessionSummary> {
+    let status: String = row.get(2)?;
+    let phase: String = row.get(3)?;
+    Ok(SessionSummary {
+        id: row.get(0)?,
+        task: row.get(1)?,
+        status: status.parse().unwrap_or(SessionStatus::Pending),
+        phase: phase.parse().unwrap_or(SessionPhase::NotStarted),
+        working_dir: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        error: row.get(7)?,
+    })
+}
+
 #[async_trait]
 impl Storage for SqliteStorage {
     async fn save(&self, session: &SessionState) -> Result<()> {
+        let event = SessionEvent::Saved {
+            id: session.id.clone(),
+            status: session.status,
+            phase: session.phase,
+        };
         let session = session.clone();
         let db_path = self.db_path.clone();
 
@@ -118,6 +164,8 @@ impl Storage for SqliteStorage {
         .await
         .context("spawn_blocking failed")??;
 
+        // No receivers is fine
+        let _ = self.events.send(event);
         Ok(())
     }
 
@@ -152,77 +200,612 @@ impl Storage for SqliteStorage {
     async fn list(&self) -> Result<Vec<SessionSummary>> {
         let db_path = self.db_path.clone();
 
+        task::spawn_blocking(move || {
+            let conn = Connection::open(&db_path)?;
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {SUMMARY_COLUMNS} FROM sessions ORDER BY updated_at DESC"
+            ))?;
+
+            let sessions = stmt
+                .query_map([], summary_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(sessions)
+        })
+        .await
+        .context("spawn_blocking failed")?
+    }
+
+    async fn list_page(
+        &self,
+        filter: &SessionFilter,
+        page: usize,
+        page_size: usize,
+    ) -> Result<(Vec<SessionSummary>, usize)> {
+        let filter = filter.clone();
+        let db_path = self.db_path.clone();
+
+        task::spawn_blocking(move || {
+            let conn = Connection::open(&db_path)?;
+
+            let mut clauses: Vec<&str> = Vec::new();
+            let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            if let Some(status) = filter.status {
+                clauses.push("status = ?");
+                params.push(Box::new(status.to_string()));
+            }
+            if let Some(phase) = filter.phase {
+                clauses.push("phase = ?");
+                params.push(Box::new(phase.to_string()));
+            }
+            // Timestamps are stored as UTC RFC 3339, which sorts as text
+            if let Some(after) = filter.created_after {
+                clauses.push("created_at >= ?");
+                params.push(Box::new(after.to_rfc3339()));
+            }
+            if let Some(before) = filter.created_before {
+                clauses.push("created_at < ?");
+                params.push(Box::new(before.to_rfc3339()));
+            }
+            if let Some(text) = filter.task_contains {
+                clauses.push("instr(task, ?) > 0");
+                params.push(Box::new(text));
+            }
+            // LIKE ignores case (for ASCII) in SQLite
+            if let Some(query) = filter.search {
+                clauses.push("(task LIKE ? ESCAPE '\\' OR error LIKE ? ESCAPE '\\')");
+                let pattern = contains_pattern(&query);
+                params.push(Box::new(pattern.clone()));
+                params.push(Box::new(pattern));
+            }
+            let where_clause = if clauses.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", clauses.join(" AND "))
+            };
+            let filter_params = rusqlite::params_from_iter(params.iter());
+
+            let total: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM sessions {where_clause}"),
+                filter_params,
+                |row| row.get(0),
+            )?;
+
+            let limit = i64::try_from(page_size).unwrap_or(i64::MAX);
+            let offset = page
+                .max(1)
+                .saturating_sub(1)
+                .saturating_mul(page_size)
+                .try_into()
+                .unwrap_or(i64::MAX);
+            params.push(Box::new(limit));
+            params.push(Box::new(offset));
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {SUMMARY_COLUMNS} FROM sessions {where_clause}
+                 ORDER BY updated_at DESC LIMIT ? OFFSET ?"
+            ))?;
+            let sessions = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), summary_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok((sessions, usize::try_from(total).unwrap_or_default()))
+        })
+        .await
+        .context("spawn_blocking failed")?
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SessionSummary>> {
+        let pattern = contains_pattern(query);
+        let db_path = self.db_path.clone();
+
+        task::spawn_blocking(move || {
+            let conn = Connection::open(&db_path)?;
+
+            // LIKE ignores case (for ASCII) in SQLite
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {SUMMARY_COLUMNS} FROM sessions
+                 WHERE task LIKE ?1 ESCAPE '\\' OR error LIKE ?1 ESCAPE '\\'
+                 ORDER BY updated_at DESC"
+            ))?;
+            let sessions = stmt
+                .query_map([pattern], summary_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(sessions)
+        })
+        .await
+        .context("spawn_blocking failed")?
+    }
+
+    async fn find_by_metadata(&self, key: &str, value: &str) -> Result<Vec<SessionState>> {
+        let path = format!("$.metadata.\"{}\"", key.replace('"', "\\\""));
+        let value = value.to_string();
+        let db_path = self.db_path.clone();
+
         task::spawn_blocking(move || {
             let conn = Connection::open(&db_path)?;
 
             let mut stmt = conn.prepare(
-                "SELECT id, task, status, phase, working_dir, created_at, updated_at, error
-                 FROM sessions
+                "SELECT data FROM sessions
+                 WHERE json_extract(data, ?1) = ?2
                  ORDER BY updated_at DESC",
             )?;
 
-            let sessions = stmt
-                .query_map([], |row| {
-                    let status_str: String = row.get(2)?;
-                    let phase_str: String = row.get(3)?;
-                    Ok((
-                        row.get::<_, String>(0)?,
-                        row.get::<_, String>(1)?,
-                        status_str,
-                        phase_str,
-                        row.get::<_, String>(4)?,
-                        row.get::<_, String>(5)?,
-                        row.get::<_, String>(6)?,
-                        row.get::<_, Option<String>>(7)?,
-                    ))
+            let rows = stmt
+                .query_map(rusqlite::params![path, value], |row| {
+                    row.get::<_, String>(0)
                 })?
                 .collect::<Result<Vec<_>, _>>()?;
 
-            let mut result = Vec::with_capacity(sessions.len());
-            for (id, task, status_str, phase_str, working_dir, created_at, updated_at, error) in
-                sessions
-            {
-                let status = status_str
-                    .parse::<SessionStatus>()
-                    .unwrap_or(SessionStatus::Pending);
-                let phase = phase_str
-                    .parse::<SessionPhase>()
-                    .unwrap_or(SessionPhase::NotStarted);
-                result.push(SessionSummary {
-                    id,
-                    task,
-                    status,
-                    phase,
-                    working_dir,
-                    created_at,
-                    updated_at,
-                    error,
-                });
-            }
-
-            Ok(result)
+            rows.iter()
+                .map(|data| serde_json::from_str(data).map_err(Into::into))
+                .collect()
         })
         .await
         .context("spawn_blocking failed")?
     }
 
     async fn delete(&self, id: &str) -> Result<()> {
+        let event = SessionEvent::Deleted { id: id.to_string() };
         let id = id.to_string();
         let db_path = self.db_path.clone();
 
         task::spawn_blocking(move || {
-            let conn = Connection::open(&db_path)?;
-            conn.execute("DELETE FROM sessions WHERE id = ?1", [&id])?;
-            let changes = conn.changes();
-            if changes == 0 {
+            let mut conn = Connection::open(&db_path)?;
+            let tx = conn.transaction()?;
+            if tx.execute("DELETE FROM sessions WHERE id = ?1", [&id])? == 0 {
                 anyhow::bail!("session '{}' not found", id);
             }
+            tx.execute("DELETE FROM session_locks WHERE session_id = ?1", [&id])?;
+            tx.commit()?;
             debug!(id = %id, "deleted session");
             Ok::<_, anyhow::Error>(())
         })
         .await
         .context("spawn_blocking failed")??;
 
+        let _ = self.events.send(event);
         Ok(())
     }
+
+    async fn delete_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        only_status: Option<SessionStatus>,
+        stale_after: Duration,
+    ) -> Result<usize> {
+        let cutoff = cutoff.to_rfc3339();
+        let status = only_status.map(|s| s.to_string());
+        let db_path = self.db_path.clone();
+
+        let ids = task::spawn_blocking(move || {
+            let mut conn = Connection::open(&db_path)?;
+            let tx = conn.transaction()?;
+            let live_since = chrono::Utc::now().timestamp_millis()
+                - i64::try_from(stale_after.as_millis()).unwrap_or(i64::MAX);
+
+            // Compare as instants: the stored RFC 3339 text has varying
+            // fractional-second precision, so it doesn't always sort as text
+            let ids = tx
+                .prepare(
+                    "DELETE FROM sessions
+                     WHERE julianday(updated_at) < julianday(?1)
+                       AND (?2 IS NULL OR status = ?2)
+                       AND id NOT IN
+                           (SELECT session_id FROM session_locks WHERE acquired_at >= ?3)
+                     RETURNING id",
+                )?
+                .query_map(rusqlite::params![cutoff, status, live_since], |row| {
+                    row.get(0)
+                })?
+                .collect::<Result<Vec<String>, _>>()?;
+            // Stale locks of the deleted sessions, and of sessions deleted
+            // before locks were cleaned up
+            tx.execute(
+                "DELETE FROM session_locks
+                 WHERE session_id NOT IN (SELECT id FROM sessions) AND acquired_at < ?1",
+                [live_since],
+            )?;
+            tx.commit()?;
+            debug!(count = ids.len(), "deleted old sessions");
+            Ok::<_, anyhow::Error>(ids)
+        })
+        .await
+        .context("spawn_blocking failed")??;
+
+        let deleted = ids.len();
+        for id in ids {
+            let _ = self.events.send(SessionEvent::Deleted { id });
+        }
+        Ok(deleted)
+    }
+
+    async fn try_lock(&self, id: &str, owner: &str, stale_after: Duration) -> Result<bool> {
+        let id = id.to_string();
+        let owner = owner.to_string();
+        let db_path = self.db_path.clone();
+
+        task::spawn_blocking(move || {
+            let mut conn = Connection::open(&db_path)?;
+            let now = chrono::Utc::now().timestamp_millis();
+            let cutoff = now - i64::try_from(stale_after.as_millis()).unwrap_or(i64::MAX);
+
+            // Immediate transaction so two processes can't both see the
+            // lock as free
+            let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+            tx.execute(
+                "DELETE FROM session_locks
+                 WHERE session_id = ?1 AND (owner = ?2 OR acquired_at < ?3)",
+                rusqlite::params![id, owner, cutoff],
+            )?;
+            let acquired = tx.execute(
+                "INSERT OR IGNORE INTO session_locks (session_id, owner, acquired_at)
+                 VALUES (?1, ?2, ?3)",
+                rusqlite::params![id, owner, now],
+            )? == 1;
+            tx.commit()?;
+
+            debug!(id = %id, acquired, "session lock attempt");
+            Ok(acquired)
+        })
+        .await
+        .context("spawn_blocking failed")?
+    }
+
+    async fn unlock(&self, id: &str, owner: &str) -> Result<()> {
+        let id = id.to_string();
+        let owner = owner.to_string();
+        let db_path = self.db_path.clone();
+
+        task::spawn_blocking(move || {
+            let conn = Connection::open(&db_path)?;
+            conn.execute(
+                "DELETE FROM session_locks WHERE session_id = ?1 AND owner = ?2",
+                [&id, &owner],
+            )?;
+            debug!(id = %id, "released session lock");
+            Ok(())
+        })
+        .await
+        .context("spawn_blocking failed")?
+    }
+
+    fn watch(&self) -> Result<broadcast::Receiver<SessionEvent>> {
+        Ok(self.events.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[tokio::test]
+    async fn save_and_delete_notify_watchers() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+        let mut events = storage.watch().unwrap();
+
+        let session = SessionState::new("task", ".");
+        storage.save(&session).await.unwrap();
+        storage.delete(&session.id).await.unwrap();
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            SessionEvent::Saved {
+                id: session.id.clone(),
+                status: SessionStatus::Pending,
+                phase: SessionPhase::NotStarted,
+            }
+        );
+        assert_eq!(
+            events.recv().await.unwrap(),
+            SessionEvent::Deleted { id: session.id }
+        );
+    }
+
+    #[tokio::test]
+    async fn events_and_summaries_serialize_with_cli_spellings() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+        let mut events = storage.watch().unwrap();
+
+        let mut session = SessionState::with_id("s1", "task", ".");
+        session.set_status(SessionStatus::InProgress);
+        storage.save(&session).await.unwrap();
+
+        assert_eq!(
+            serde_json::to_value(events.recv().await.unwrap()).unwrap(),
+            serde_json::json!({
+                "event": "saved",
+                "id": "s1",
+                "status": "in_progress",
+                "phase": "not_started",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(SessionEvent::Deleted { id: "s1".into() }).unwrap(),
+            serde_json::json!({ "event": "deleted", "id": "s1" })
+        );
+
+        let summary = serde_json::to_value(&storage.list().await.unwrap()[0]).unwrap();
+        assert_eq!(summary["status"], "in_progress");
+        assert_eq!(summary["task"], "task");
+    }
+
+    #[tokio::test]
+    async fn session_lock_excludes_other_owners_until_released_or_stale() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+        let hour = Duration::from_secs(3600);
+
+        assert!(storage.try_lock("s1", "a", hour).await.unwrap());
+        assert!(!storage.try_lock("s1", "b", hour).await.unwrap());
+        // Re-locking as the holder refreshes the lock
+        assert!(storage.try_lock("s1", "a", hour).await.unwrap());
+        // Other sessions are unaffected
+        assert!(storage.try_lock("s2", "b", hour).await.unwrap());
+
+        // Only the holder can release it
+        storage.unlock("s1", "b").await.unwrap();
+        assert!(!storage.try_lock("s1", "b", hour).await.unwrap());
+        storage.unlock("s1", "a").await.unwrap();
+        assert!(storage.try_lock("s1", "b", hour).await.unwrap());
+
+        // An abandoned lock is taken over once stale
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(
+            storage
+                .try_lock("s1", "c", Duration::from_millis(10))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn list_page_filters_and_paginates() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+
+        let start = chrono::Utc::now();
+        for i in 0..5 {
+            let mut session = SessionState::new(format!("fix bug {i}"), ".");
+            session.created_at = start + chrono::Duration::seconds(i);
+            session.updated_at = session.created_at;
+            if i % 2 == 0 {
+                session.set_status(SessionStatus::Completed);
+                session.updated_at = session.created_at;
+            }
+            storage.save(&session).await.unwrap();
+        }
+        storage
+            .save(&SessionState::new("write docs", "."))
+            .await
+            .unwrap();
+
+        let bugs = SessionFilter {
+            task_contains: Some("bug".to_string()),
+            ..SessionFilter::default()
+        };
+        let (first, total) = storage.list_page(&bugs, 1, 2).await.unwrap();
+        assert_eq!(total, 5);
+        let tasks: Vec<&str> = first.iter().map(|s| s.task.as_str()).collect();
+        assert_eq!(tasks, ["fix bug 4", "fix bug 3"]);
+        let (last, _) = storage.list_page(&bugs, 3, 2).await.unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].task, "fix bug 0");
+
+        let completed_recent = SessionFilter {
+            status: Some(SessionStatus::Completed),
+            created_after: Some(start + chrono::Duration::seconds(1)),
+            task_contains: Some("bug".to_string()),
+            ..SessionFilter::default()
+        };
+        let (page, total) = storage.list_page(&completed_recent, 1, 10).await.unwrap();
+        assert_eq!(total, 2);
+        let tasks: Vec<&str> = page.iter().map(|s| s.task.as_str()).collect();
+        assert_eq!(tasks, ["fix bug 4", "fix bug 2"]);
+
+        // The in-memory default agrees with the SQL implementation
+        let all = storage.list().await.unwrap();
+        let expected: Vec<_> = all
+            .into_iter()
+            .filter(|s| completed_recent.matches(s))
+            .collect();
+        assert_eq!(page, expected);
+    }
+
+    #[tokio::test]
+    async fn search_matches_task_or_error_ignoring_case() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+
+        let start = chrono::Utc::now();
+        let tasks = [
+            "Fix login bug",
+            "Add dark mode",
+            "fix typo in README",
+            "Reach 100% coverage",
+        ];
+        for (i, task) in tasks.into_iter().enumerate() {
+            let mut session = SessionState::new(task, ".");
+            session.updated_at = start + chrono::Duration::seconds(i as i64);
+            storage.save(&session).await.unwrap();
+        }
+        let mut failed = SessionState::new("Upgrade deps", ".");
+        failed.set_error("Login service unreachable");
+        failed.updated_at = start + chrono::Duration::seconds(10);
+        storage.save(&failed).await.unwrap();
+
+        let found: Vec<String> = storage
+            .search("FIX")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.task)
+            .collect();
+        assert_eq!(found, ["fix typo in README", "Fix login bug"]);
+
+        let found = storage.search("login").await.unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].task, "Upgrade deps");
+
+        // Wildcards in the query are matched literally
+        let found = storage.search("100%").await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(storage.search("%").await.unwrap().len() == 1);
+        assert!(
+            storage
+                .search("nothing like this")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // The same search pages in SQL alongside the other filters
+        let filter = SessionFilter {
+            search: Some("login".to_string()),
+            ..SessionFilter::default()
+        };
+        let (page, total) = storage.list_page(&filter, 2, 1).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].task, "Fix login bug");
+        let filter = SessionFilter {
+            search: Some("FIX".to_string()),
+            task_contains: Some("typo".to_string()),
+            ..SessionFilter::default()
+        };
+        let (page, total) = storage.list_page(&filter, 1, 10).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(page[0].task, "fix typo in README");
+
+        // The in-memory default agrees with the SQL implementation
+        let expected: Vec<_> = storage
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|s| filter.matches(s))
+            .collect();
+        assert_eq!(page, expected);
+    }
+
+    #[tokio::test]
+    async fn delete_older_than_removes_backdated_sessions() {
+        let dir = tempdir().unwrap();
+        let storage = SqliteStorage::new(dir.path().join("sessions.db")).unwrap();
+        let mut events = storage.watch().unwrap();
+
+        let now = chrono::Utc::now();
+        let mut ids = Vec::new();
+        for (days_old, status) in [
+            (40, SessionStatus::Completed),
+            (35, SessionStatus::Failed),
+            (31, SessionStatus::Completed),
+            (5, SessionStatus::Completed),
+            (0, SessionStatus::InProgress),
+        ] {
+            let mut session = SessionState::new(format!("{days_old} days old"), ".");
+            session.set_status(status);
+            // Sub-second precision must not break the comparison
+            session.updated_at =
+                now - chrono::Duration::days(days_old) + chrono::Duration::nanoseconds(123_456_789);
+            storage.save(&session).await.unwrap();
+            ids.push(session.id);
+        }
+        for _ in &ids {
+            events.recv().await.unwrap();
+        }
+
+        let cutoff = now - chrono::Duration::days(30);
+        let deleted = storage
+            .delete_older_than(cutoff, Some(SessionStatus::Completed), HOUR)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        assert!(storage.load(&ids[0]).await.unwrap().is_none());
+        assert!(storage.load(&ids[1]).await.unwrap().is_some());
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            SessionEvent::Deleted { .. }
+        ));
+
+        let deleted = storage.delete_older_than(cutoff, None, HOUR).await.unwrap();
+        assert_eq!(deleted, 1);
+        let remaining: Vec<String> = storage
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.task)
+            .collect();
+        assert_eq!(remaining, ["0 days old", "5 days old"]);
+    }
+
+    #[tokio::test]
+    async fn delete_older_than_keeps_live_sessions_and_clears_stale_locks() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("sessions.db");
+        let storage = SqliteStorage::new(&db_path).unwrap();
+
+        let old = chrono::Utc::now() - chrono::Duration::days(40);
+        let mut sessions = Vec::new();
+        for status in [
+            SessionStatus::InProgress,
+            SessionStatus::InProgress,
+            SessionStatus::Interrupted,
+            SessionStatus::Completed,
+        ] {
+            let mut session = SessionState::new(status.to_string(), ".");
+            session.set_status(status);
+            session.updated_at = old;
+            storage.save(&session).await.unwrap();
+            sessions.push(session.id);
+        }
+        // The first in-progress run crashed without unlocking, the second
+        // without ever taking its lock, and a live run is finishing the
+        // interrupted session. A deleted session's lock was left behind,
+        // and a live lock is held on a key that is no session.
+        let stale_lock = |id: &str| {
+            Connection::open(&db_path)
+                .unwrap()
+                .execute(
+                    "INSERT INTO session_locks (session_id, owner, acquired_at)
+                     VALUES (?1, 'crashed', 0)",
+                    [id],
+                )
+                .unwrap();
+        };
+        stale_lock(&sessions[0]);
+        stale_lock("gone");
+        assert!(
+            storage
+                .try_lock(&sessions[2], "runner", HOUR)
+                .await
+                .unwrap()
+        );
+        assert!(storage.try_lock("claim", "submitter", HOUR).await.unwrap());
+
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(30);
+        let deleted = storage.delete_older_than(cutoff, None, HOUR).await.unwrap();
+        assert_eq!(deleted, 3);
+        assert!(storage.load(&sessions[2]).await.unwrap().is_some());
+
+        let lock_count = || -> i64 {
+            Connection::open(&db_path)
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM session_locks", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(lock_count(), 2);
+
+        // Deleting a session takes its lock with it
+        storage.delete(&sessions[2]).await.unwrap();
+        assert_eq!(lock_count(), 1);
+    }
 }
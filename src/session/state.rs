@@ -1,9 +1,22 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::llm::Message;
+use crate::llm::{Message, MessageSummarizer, TokenTotals};
+
+/// Metadata key holding a caller-supplied idempotency key
+pub const IDEMPOTENCY_KEY: &str = "idempotency_key";
+
+/// Metadata key holding a session's labels, as a JSON array
+pub const LABELS_KEY: &str = "labels";
+
+/// Metadata keys managed through their own setters, which user tags must
+/// not overwrite
+pub const RESERVED_METADATA_KEYS: &[&str] = &[IDEMPOTENCY_KEY, LABELS_KEY];
 
 /// Session state for persistence
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +47,118 @@ pub struct SessionState {
 
     /// Any error message if the session failed
     pub error: Option<String>,
+
+    /// The pipeline the session last ran under (`None` for sessions saved
+    /// before it was recorded)
+    #[serde(default)]
+    pub pipeline: Option<Pipeline>,
+
+    /// Free-form key/value tags (e.g. `team=platform`)
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+
+    /// Final agent output once the session completed
+    #[serde(default)]
+    pub output: Option<String>,
+
+    /// Number of review-driven fix iterations so far (0 on the first pass)
+    #[serde(default)]
+    pub review_iteration: u32,
+
+    /// Output of each orchestration phase, in the order the phases ran
+    #[serde(default)]
+    pub phase_outputs: Vec<PhaseOutput>,
+
+    /// Wall-clock milliseconds spent running this session, summed over all
+    /// resumes
+    #[serde(default)]
+    pub total_runtime_ms: u64,
+
+    /// LLM round-trips made for this session, summed over all resumes
+    #[serde(default)]
+    pub total_iterations: u64,
+
+    /// Tokens the LLM API reported for this session, summed over all
+    /// resumes (`None` until a round-trip reports usage)
+    #[serde(default)]
+    pub total_usage: Option<TokenTotals>,
+
+    /// Tool calls the security policy blocked, in order
+    #[serde(default)]
+    pub policy_denials: Vec<PolicyDenial>,
+
+    /// Conversations of agents that haven't finished yet, so a resumed run
+    /// continues them instead of starting over
+    #[serde(default)]
+    pub conversations: Vec<AgentConversation>,
+}
+
+/// A tool call the security policy blocked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDenial {
+    /// The tool the model called
+    pub tool: String,
+    /// Why the policy blocked it, as reported to the model
+    pub reason: String,
+}
+
+/// What one orchestration phase produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseOutput {
+    /// The phase that produced the output
+    pub phase: SessionPhase,
+    /// Review-driven fix iteration the phase ran in (0 on the first pass)
+    pub review_iteration: u32,
+    /// The agent's output for the phase
+    pub output: String,
+}
+
+/// The conversation so far of one agent in one phase and review iteration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentConversation {
+    /// The agent holding the conversation
+    pub agent: String,
+    /// The phase the agent is running in
+    pub phase: SessionPhase,
+    /// Review-driven fix iteration the agent is running in
+    pub review_iteration: u32,
+    /// Messages exchanged so far, starting with the agent's first prompt
+    pub messages: Vec<Message>,
+}
+
+/// Cumulative limits on a session across all of its runs and resumes, so a
+/// session that keeps getting resumed can't run up cost indefinitely
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionBudget {
+    /// Maximum total wall-clock runtime (unlimited if `None`)
+    pub max_runtime: Option<Duration>,
+    /// Maximum total LLM round-trips (unlimited if `None`)
+    pub max_iterations: Option<u64>,
+}
+
+impl SessionBudget {
+    /// Describe how `session` has exhausted this budget, or `None` if it
+    /// still has budget left
+    pub fn exceeded_by(&self, session: &SessionState) -> Option<String> {
+        if let Some(max) = self.max_runtime {
+            if session.total_runtime() >= max {
+                return Some(format!(
+                    "ran for {}s of its {}s runtime budget",
+                    session.total_runtime().as_secs(),
+                    max.as_secs()
+                ));
+            }
+        }
+        if let Some(max) = self.max_iterations {
+            if session.total_iterations >= max {
+                return Some(format!(
+                    "made {} of its {} allowed LLM round-trips",
+                    session.total_iterations, max
+                ));
+            }
+        }
+        None
+    }
 }
 
 impl SessionState {
@@ -50,6 +175,29 @@ impl SessionState {
             updated_at: now,
             working_dir: working_dir.into(),
             error: None,
+            pipeline: None,
+            metadata: BTreeMap::new(),
+            output: None,
+            review_iteration: 0,
+            phase_outputs: Vec::new(),
+            total_runtime_ms: 0,
+            total_iterations: 0,
+            total_usage: None,
+            policy_denials: Vec::new(),
+            conversations: Vec::new(),
+        }
+    }
+
+    /// Create a new session with a caller-chosen id (e.g. an external
+    /// ticket id) instead of a random UUID
+    pub fn with_id(
+        id: impl Into<String>,
+        task: impl Into<String>,
+        working_dir: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            ..Self::new(task, working_dir)
         }
     }
 
@@ -65,12 +213,141 @@ impl SessionState {
         self.updated_at = Utc::now();
     }
 
+    /// Record which review-driven fix iteration the session is on
+    pub fn set_review_iteration(&mut self, iteration: u32) {
+        self.review_iteration = iteration;
+        self.updated_at = Utc::now();
+    }
+
+    /// Record the output of the current phase and review iteration
+    pub fn record_phase_output(&mut self, output: impl Into<String>) {
+        self.phase_outputs.push(PhaseOutput {
+            phase: self.phase,
+            review_iteration: self.review_iteration,
+            output: output.into(),
+        });
+        self.updated_at = Utc::now();
+    }
+
+    /// The unfinished conversation of `agent` in the current phase and
+    /// review iteration, if one was saved
+    pub fn conversation(&self, agent: &str) -> Option<&[Message]> {
+        self.conversations
+            .iter()
+            .find(|c| self.is_current(c, agent))
+            .map(|c| c.messages.as_slice())
+    }
+
+    /// Save the conversation of `agent` in the current phase and review
+    /// iteration, replacing any earlier copy
+    pub fn save_conversation(&mut self, agent: &str, messages: Vec<Message>) {
+        match self
+            .conversations
+            .iter()
+            .position(|c| self.is_current(c, agent))
+        {
+            Some(index) => self.conversations[index].messages = messages,
+            None => self.conversations.push(AgentConversation {
+                agent: agent.to_string(),
+                phase: self.phase,
+                review_iteration: self.review_iteration,
+                messages,
+            }),
+        }
+        self.updated_at = Utc::now();
+    }
+
+    /// Drop the conversation of `agent` in the current phase and review
+    /// iteration once the agent has finished
+    pub fn finish_conversation(&mut self, agent: &str) {
+        let (phase, review_iteration) = (self.phase, self.review_iteration);
+        self.conversations.retain(|c| {
+            !(c.agent == agent && c.phase == phase && c.review_iteration == review_iteration)
+        });
+        self.updated_at = Utc::now();
+    }
+
+    fn is_current(&self, conversation: &AgentConversation, agent: &str) -> bool {
+        conversation.agent == agent
+            && conversation.phase == self.phase
+            && conversation.review_iteration == self.review_iteration
+    }
+
+    /// Wall-clock time spent running this session, summed over all resumes
+    pub fn total_runtime(&self) -> Duration {
+        Duration::from_millis(self.total_runtime_ms)
+    }
+
+    /// Add the duration of one run to the session's total runtime
+    pub fn add_runtime(&mut self, elapsed: Duration) {
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        self.total_runtime_ms = self.total_runtime_ms.saturating_add(elapsed_ms);
+    }
+
+    /// Set a metadata tag, replacing any existing value for the key.
+    ///
+    /// Callers taking keys from users should refuse
+    /// [`RESERVED_METADATA_KEYS`] first.
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata.insert(key.into(), value.into());
+        self.updated_at = Utc::now();
+    }
+
+    /// The idempotency key used to dedupe repeated submissions, if any
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.metadata.get(IDEMPOTENCY_KEY).map(String::as_str)
+    }
+
+    /// Set the idempotency key used to dedupe repeated submissions
+    pub fn set_idempotency_key(&mut self, key: impl Into<String>) {
+        self.set_metadata(IDEMPOTENCY_KEY, key);
+    }
+
+    /// Record the pipeline this session runs under
+    pub fn set_pipeline(&mut self, pipeline: Pipeline) {
+        self.pipeline = Some(pipeline);
+        self.updated_at = Utc::now();
+    }
+
+    /// Labels attached when the session was submitted
+    pub fn labels(&self) -> Vec<String> {
+        self.metadata
+            .get(LABELS_KEY)
+            .and_then(|labels| serde_json::from_str(labels).ok())
+            .unwrap_or_default()
+    }
+
+    /// Attach labels to the session, replacing any it already has
+    pub fn set_labels(&mut self, labels: &[String]) {
+        let labels = serde_json::to_string(labels).expect("labels serialize to JSON");
+        self.set_metadata(LABELS_KEY, labels);
+    }
+
     /// Add a message to the conversation history
     pub fn add_message(&mut self, message: Message) {
         self.messages.push(message);
         self.updated_at = Utc::now();
     }
 
+    /// Shrink the saved message log and each unfinished agent conversation
+    /// to fit `budget` tokens, summarizing the oldest round-trips into the
+    /// first message. Returns how many messages were removed.
+    pub fn prune_to_token_budget(
+        &mut self,
+        budget: usize,
+        summarizer: &dyn MessageSummarizer,
+    ) -> usize {
+        let mut removed = crate::llm::prune_to_token_budget(&mut self.messages, budget, summarizer);
+        for conversation in &mut self.conversations {
+            removed +=
+                crate::llm::prune_to_token_budget(&mut conversation.messages, budget, summarizer);
+        }
+        if removed > 0 {
+            self.updated_at = Utc::now();
+        }
+        removed
+    }
+
     /// Set an error and mark as failed
     pub fn set_error(&mut self, error: impl Into<String>) {
         self.error = Some(error.into());
@@ -78,8 +355,10 @@ impl SessionState {
         self.updated_at = Utc::now();
     }
 
-    /// Mark the session as completed
-    pub fn complete(&mut self) {
+    /// Mark the session as completed with the agent's final output
+    pub fn complete(&mut self, output: impl Into<String>) {
+        self.output = Some(output.into());
+        self.error = None;
         self.status = SessionStatus::Completed;
         self.phase = SessionPhase::Completed;
         self.updated_at = Utc::now();
@@ -87,10 +366,66 @@ impl SessionState {
 
     /// Check if the session can be resumed
     pub fn can_resume(&self) -> bool {
-        matches!(
-            self.status,
-            SessionStatus::Pending | SessionStatus::InProgress | SessionStatus::Interrupted
-        )
+        self.status.can_resume()
+    }
+
+    /// Render the session as a human-readable Markdown report: the task,
+    /// each phase's output, the tool calls made and the final result
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Session Report\n\n");
+        let _ = writeln!(out, "- **ID:** `{}`", self.id);
+        let _ = writeln!(out, "- **Status:** {}", self.status);
+        let _ = writeln!(out, "- **Phase:** {}", self.phase);
+        let _ = writeln!(out, "- **Working directory:** `{}`", self.working_dir);
+        let _ = writeln!(out, "- **Created:** {}", self.created_at.to_rfc3339());
+        let _ = writeln!(out, "- **Updated:** {}", self.updated_at.to_rfc3339());
+        if let Some(usage) = &self.total_usage {
+            let _ = write!(
+                out,
+                "- **Tokens:** {} input, {} output",
+                usage.input_tokens, usage.output_tokens
+            );
+            if let Some(cached) = usage.cache_read_tokens {
+                let _ = write!(out, " ({} input from cache)", cached);
+            }
+            out.push('\n');
+        }
+        for (key, value) in &self.metadata {
+            let _ = writeln!(out, "- **{}:** {}", key, value);
+        }
+
+        let _ = write!(out, "\n## Task\n\n{}\n", self.task.trim());
+
+        for phase in &self.phase_outputs {
+            let _ = write!(out, "\n## {}", phase.phase.title());
+            if phase.review_iteration > 0 {
+                let _ = write!(out, " (fix iteration {})", phase.review_iteration);
+            }
+            let _ = write!(out, "\n\n{}\n", phase.output.trim());
+        }
+
+        let tool_calls: Vec<_> = self.messages.iter().flat_map(|m| &m.tool_calls).collect();
+        if !tool_calls.is_empty() {
+            out.push_str("\n## Tool Calls\n\n");
+            for (i, call) in tool_calls.iter().enumerate() {
+                let _ = writeln!(out, "{}. `{}` `{}`", i + 1, call.name, call.arguments);
+            }
+        }
+
+        if !self.policy_denials.is_empty() {
+            out.push_str("\n## Policy Denials\n\n");
+            for denial in &self.policy_denials {
+                let _ = writeln!(out, "- `{}`: {}", denial.tool, denial.reason);
+            }
+        }
+
+        if let Some(output) = &self.output {
+            let _ = write!(out, "\n## Result\n\n{}\n", output.trim());
+        }
+        if let Some(error) = &self.error {
+            let _ = write!(out, "\n## Error\n\n{}\n", error.trim());
+        }
+        out
     }
 }
 
@@ -116,8 +451,10 @@ pub enum SessionStatus {
     Interrupted,
 }
 
-/// Phase in the orchestration workflow
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+/// Phase in the orchestration workflow, in workflow order
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
 pub enum SessionPhase {
     /// Session not started
     #[default]
@@ -128,12 +465,24 @@ pub enum SessionPhase {
     Implementing,
     /// Testing phase
     Testing,
+    /// Security audit phase (only when the orchestrator has it enabled)
+    Auditing,
     /// Review phase
     Reviewing,
     /// Session completed
     Completed,
 }
 
+/// Serialize a status or phase as its CLI spelling ("in_progress") rather
+/// than the variant name stored in session data
+pub(super) fn serialize_display<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: std::fmt::Display,
+    S: serde::Serializer,
+{
+    serializer.collect_str(value)
+}
+
 impl std::fmt::Display for SessionStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -146,6 +495,19 @@ impl std::fmt::Display for SessionStatus {
     }
 }
 
+impl SessionStatus {
+    /// Whether a session in this status can be resumed: one that hasn't
+    /// finished, or one that failed and can be retried from where it
+    /// stopped. An in-progress session may still be running elsewhere;
+    /// its lock decides that.
+    pub fn can_resume(&self) -> bool {
+        matches!(
+            self,
+            Self::Pending | Self::InProgress | Self::Interrupted | Self::Failed
+        )
+    }
+}
+
 impl FromStr for SessionStatus {
     type Err = anyhow::Error;
 
@@ -164,6 +526,21 @@ impl FromStr for SessionStatus {
     }
 }
 
+impl SessionPhase {
+    /// Heading-style name of the phase (e.g. "Planning")
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::NotStarted => "Not Started",
+            Self::Planning => "Planning",
+            Self::Implementing => "Implementing",
+            Self::Testing => "Testing",
+            Self::Auditing => "Security Audit",
+            Self::Reviewing => "Reviewing",
+            Self::Completed => "Completed",
+        }
+    }
+}
+
 impl std::fmt::Display for SessionPhase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -171,6 +548,7 @@ impl std::fmt::Display for SessionPhase {
             Self::Planning => write!(f, "planning"),
             Self::Implementing => write!(f, "implementing"),
             Self::Testing => write!(f, "testing"),
+            Self::Auditing => write!(f, "auditing"),
             Self::Reviewing => write!(f, "reviewing"),
             Self::Completed => write!(f, "completed"),
         }
@@ -186,22 +564,95 @@ impl FromStr for SessionPhase {
             "planning" => Ok(Self::Planning),
             "implementing" => Ok(Self::Implementing),
             "testing" => Ok(Self::Testing),
+            "auditing" => Ok(Self::Auditing),
             "reviewing" => Ok(Self::Reviewing),
             "completed" => Ok(Self::Completed),
             _ => anyhow::bail!(
-                "invalid session phase '{}' (expected: not_started, planning, implementing, testing, reviewing, completed)",
+                "invalid session phase '{}' (expected: not_started, planning, implementing, testing, auditing, reviewing, completed)",
                 s
             ),
         }
     }
 }
 
+/// The set of agents a session runs through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Pipeline {
+    /// A single coder agent
+    #[serde(rename = "simple")]
+    Simple,
+    /// Planner -> coder -> tester -> (security audit) -> reviewer
+    #[default]
+    #[serde(rename = "orchestrator")]
+    Orchestrated,
+}
+
+impl Pipeline {
+    /// The phases this pipeline goes through, in order
+    pub fn phases(&self) -> &'static [SessionPhase] {
+        match self {
+            Self::Simple => &[SessionPhase::Implementing],
+            Self::Orchestrated => &[
+                SessionPhase::Planning,
+                SessionPhase::Implementing,
+                SessionPhase::Testing,
+                SessionPhase::Auditing,
+                SessionPhase::Reviewing,
+            ],
+        }
+    }
+
+    /// Where a session stopped at `phase` (possibly under another pipeline)
+    /// picks up in this one:
+    ///
+    /// - a phase this pipeline also has is kept
+    /// - otherwise the nearest earlier phase this pipeline has is used
+    /// - with no earlier phase (or a session that hadn't started), it
+    ///   restarts from this pipeline's first phase
+    /// - `Completed` stays `Completed`
+    pub fn resume_phase(&self, phase: SessionPhase) -> SessionPhase {
+        if phase == SessionPhase::Completed {
+            return phase;
+        }
+        let phases = self.phases();
+        phases
+            .iter()
+            .rev()
+            .find(|candidate| **candidate <= phase)
+            .copied()
+            .unwrap_or(phases[0])
+    }
+}
+
+impl std::fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Simple => write!(f, "simple"),
+            Self::Orchestrated => write!(f, "orchestrator"),
+        }
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "simple" => Ok(Self::Simple),
+            "orchestrator" | "orchestrated" => Ok(Self::Orchestrated),
+            _ => anyhow::bail!("invalid pipeline '{}' (expected: simple, orchestrator)", s),
+        }
+    }
+}
+
 /// Summary of a session for listing (without full message history)
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct SessionSummary {
     pub id: String,
     pub task: String,
+    #[serde(serialize_with = "serialize_display")]
     pub status: SessionStatus,
+    #[serde(serialize_with = "serialize_display")]
     pub phase: SessionPhase,
     pub working_dir: String,
     pub created_at: String,
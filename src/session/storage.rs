@@ -1,8 +1,82 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::broadcast;
 
-use super::SessionState;
-use super::state::SessionSummary;
+use super::state::{SessionSummary, serialize_display};
+use super::{SessionPhase, SessionState, SessionStatus};
+
+/// A change to a stored session, delivered to `Storage::watch` subscribers.
+/// Serializes as `{"event": "saved", "id": ..., "status": ..., "phase": ...}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// A session was created or updated
+    Saved {
+        id: String,
+        #[serde(serialize_with = "serialize_display")]
+        status: SessionStatus,
+        #[serde(serialize_with = "serialize_display")]
+        phase: SessionPhase,
+    },
+    /// A session was deleted
+    Deleted { id: String },
+}
+
+/// Criteria for [`Storage::list_page`]; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionFilter {
+    pub status: Option<SessionStatus>,
+    pub phase: Option<SessionPhase>,
+    /// Only sessions created at or after this time
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only sessions created before this time
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only sessions whose task contains this text (case-sensitive)
+    pub task_contains: Option<String>,
+    /// Only sessions whose task or error contains this text, ignoring case
+    /// (as in [`Storage::search`])
+    pub search: Option<String>,
+}
+
+impl SessionFilter {
+    /// Whether a session summary passes the filter
+    pub fn matches(&self, summary: &SessionSummary) -> bool {
+        let created_at = DateTime::parse_from_rfc3339(&summary.created_at).ok();
+        self.status.is_none_or(|status| summary.status == status)
+            && self.phase.is_none_or(|phase| summary.phase == phase)
+            && self
+                .created_after
+                .is_none_or(|after| created_at.is_some_and(|t| t >= after))
+            && self
+                .created_before
+                .is_none_or(|before| created_at.is_some_and(|t| t < before))
+            && self
+                .task_contains
+                .as_deref()
+                .is_none_or(|text| summary.task.contains(text))
+            && self.search.as_deref().is_none_or(|query| {
+                let query = query.to_lowercase();
+                summary.task.to_lowercase().contains(&query)
+                    || summary
+                        .error
+                        .as_deref()
+                        .is_some_and(|e| e.to_lowercase().contains(&query))
+            })
+    }
+}
+
+/// A `LIKE` pattern matching `query` anywhere in a column, with the
+/// wildcards in `query` itself escaped (use with `ESCAPE '\'`)
+pub(super) fn contains_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
 
 /// Storage backend for sessions
 #[async_trait]
@@ -16,6 +90,121 @@ pub trait Storage: Send + Sync {
     /// List all sessions (returns summaries, not full data)
     async fn list(&self) -> Result<Vec<SessionSummary>>;
 
-    /// Delete a session
+    /// Delete a session, along with its lock
     async fn delete(&self, id: &str) -> Result<()>;
+
+    /// List one page of the sessions matching `filter`, most recently
+    /// updated first, along with the total number of matches.
+    ///
+    /// `page` is 1-based (0 is treated as 1). The default implementation
+    /// filters [`Storage::list`] in memory; backends should override it with
+    /// a query.
+    async fn list_page(
+        &self,
+        filter: &SessionFilter,
+        page: usize,
+        page_size: usize,
+    ) -> Result<(Vec<SessionSummary>, usize)> {
+        let matches: Vec<SessionSummary> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|s| filter.matches(s))
+            .collect();
+        let total = matches.len();
+        let offset = page.max(1).saturating_sub(1).saturating_mul(page_size);
+        let page = matches.into_iter().skip(offset).take(page_size).collect();
+        Ok((page, total))
+    }
+
+    /// Delete sessions last updated before `cutoff`, optionally only those
+    /// with `only_status`, returning how many were deleted. Sessions whose
+    /// lock a live run refreshed within `stale_after` are never deleted;
+    /// ones left in progress by a run that died are.
+    ///
+    /// The default implementation deletes matches from [`Storage::list`]
+    /// one at a time, probing each lock with [`Storage::try_lock`]; backends
+    /// should override it with a single query.
+    async fn delete_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        only_status: Option<SessionStatus>,
+        stale_after: Duration,
+    ) -> Result<usize> {
+        let probe = uuid::Uuid::new_v4().to_string();
+        let mut deleted = 0;
+        for summary in self.list().await? {
+            let updated_at = DateTime::parse_from_rfc3339(&summary.updated_at).ok();
+            if updated_at.is_some_and(|t| t < cutoff)
+                && only_status.is_none_or(|status| summary.status == status)
+                && self.try_lock(&summary.id, &probe, stale_after).await?
+            {
+                // Takes our probe lock with it
+                self.delete(&summary.id).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Find sessions whose task or error contains `query`, ignoring case,
+    /// most recently updated first.
+    ///
+    /// The default implementation filters [`Storage::list`] in memory;
+    /// backends should override it with a query.
+    async fn search(&self, query: &str) -> Result<Vec<SessionSummary>> {
+        let query = query.to_lowercase();
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|s| {
+                s.task.to_lowercase().contains(&query)
+                    || s.error
+                        .as_deref()
+                        .is_some_and(|e| e.to_lowercase().contains(&query))
+            })
+            .collect())
+    }
+
+    /// Find sessions whose metadata has `key` set to `value`, most recently
+    /// updated first.
+    ///
+    /// The default implementation loads every session; backends should
+    /// override it with an indexed query where possible.
+    async fn find_by_metadata(&self, key: &str, value: &str) -> Result<Vec<SessionState>> {
+        let mut matches = Vec::new();
+        for summary in self.list().await? {
+            if let Some(session) = self.load(&summary.id).await? {
+                if session.metadata.get(key).map(String::as_str) == Some(value) {
+                    matches.push(session);
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Take the advisory lock marking session `id` as running, on behalf of
+    /// `owner`. Returns `false` if a different owner holds it.
+    ///
+    /// Locks not refreshed within `stale_after` are treated as abandoned by
+    /// a crashed process and taken over. Calling this again as the same
+    /// owner refreshes the lock. Backends without locking always succeed.
+    async fn try_lock(&self, _id: &str, _owner: &str, _stale_after: Duration) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Release a lock taken with [`Storage::try_lock`] if `owner` still
+    /// holds it
+    async fn unlock(&self, _id: &str, _owner: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Subscribe to session saves and deletes made through this storage.
+    ///
+    /// Notifications are in-process only. Backends that can't support
+    /// watching return an error.
+    fn watch(&self) -> Result<broadcast::Receiver<SessionEvent>> {
+        anyhow::bail!("this storage backend does not support watching sessions")
+    }
 }
@@ -0,0 +1,433 @@
+//! Test doubles for running agents without a real model or real tool side
+//! effects. Available with the `testing` feature.
+//!
+//! ```
+//! use dev_killer::testing::{MockLlmProvider, text_response};
+//! use dev_killer::{CoderAgent, Executor, ToolRegistry};
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! // Reply based on the latest user message instead of a fixed order
+//! let provider = MockLlmProvider::from_script(|last_user_message| {
+//!     if last_user_message.contains("greeting") {
+//!         text_response("Added a greeting")
+//!     } else {
+//!         text_response("Nothing to do")
+//!     }
+//! });
+//!
+//! let executor = Executor::new(ToolRegistry::new());
+//! let output = executor
+//!     .run(&CoderAgent::new(), "add a greeting", &provider)
+//!     .await
+//!     .unwrap();
+//! assert_eq!(output, "Added a greeting");
+//! # });
+//! ```
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::llm::{LlmProvider, LlmResponse, Message, MessageRole, ToolCall};
+use crate::tools::{CommandOutput, Tool, ToolRegistry};
+
+/// Reply function for [`MockLlmProvider::from_script`]
+type Script = Box<dyn Fn(&str) -> LlmResponse + Send + Sync>;
+
+/// Reply function for [`MockLlmProvider::from_fn`]
+type Responder = Box<dyn Fn(&str, &[Message], &[&dyn Tool]) -> LlmResponse + Send + Sync>;
+
+/// Where a [`MockLlmProvider`]'s replies come from
+enum Replies {
+    /// Returned in order, one per call
+    Queue(Mutex<VecDeque<LlmResponse>>),
+    /// Computed from the latest user message
+    Script(Script),
+    /// Computed from the full request
+    Fn(Responder),
+}
+
+/// LLM provider that replays scripted responses and records the messages it
+/// was sent
+pub struct MockLlmProvider {
+    replies: Replies,
+    requests: Mutex<Vec<Vec<Message>>>,
+    structured_output: bool,
+    schemas: Mutex<Vec<serde_json::Value>>,
+    delay: Option<Duration>,
+}
+
+impl MockLlmProvider {
+    /// Create a provider that returns the given responses, one per call
+    pub fn with_responses(responses: Vec<LlmResponse>) -> Self {
+        Self::with_replies(Replies::Queue(Mutex::new(responses.into())))
+    }
+
+    /// Create a provider that answers one call with `text`
+    pub fn single_response(text: impl Into<String>) -> Self {
+        Self::with_responses(vec![text_response(text)])
+    }
+
+    /// Create a provider that decides each reply from the content of the
+    /// latest user message (empty if there is none), for tests where the
+    /// order of calls isn't fixed
+    pub fn from_script(script: impl Fn(&str) -> LlmResponse + Send + Sync + 'static) -> Self {
+        Self::with_replies(Replies::Script(Box::new(script)))
+    }
+
+    /// Create a provider that computes each reply from the whole request:
+    /// the system prompt, the conversation so far and the tools offered
+    pub fn from_fn(
+        respond: impl Fn(&str, &[Message], &[&dyn Tool]) -> LlmResponse + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_replies(Replies::Fn(Box::new(respond)))
+    }
+
+    fn with_replies(replies: Replies) -> Self {
+        Self {
+            replies,
+            requests: Mutex::new(Vec::new()),
+            structured_output: false,
+            schemas: Mutex::new(Vec::new()),
+            delay: None,
+        }
+    }
+
+    /// Act like a provider with JSON mode: structured calls skip the prompt
+    /// fallback and the scripted reply must be bare JSON
+    pub fn with_structured_output(mut self) -> Self {
+        self.structured_output = true;
+        self
+    }
+
+    /// Wait this long before each reply, like a slow model, e.g. to
+    /// exercise timeouts
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// The schemas passed to native structured calls so far
+    pub fn schemas(&self) -> Vec<serde_json::Value> {
+        self.schemas.lock().expect("mock lock poisoned").clone()
+    }
+
+    /// The messages sent on each call so far
+    pub fn requests(&self) -> Vec<Vec<Message>> {
+        self.requests.lock().expect("mock lock poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockLlmProvider {
+    async fn chat(
+        &self,
+        system: &str,
+        messages: &[Message],
+        tools: &[&dyn Tool],
+    ) -> Result<LlmResponse> {
+        self.requests
+            .lock()
+            .expect("mock lock poisoned")
+            .push(messages.to_vec());
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        match &self.replies {
+            Replies::Queue(responses) => responses
+                .lock()
+                .expect("mock lock poisoned")
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("mock provider has no more responses")),
+            Replies::Script(script) => {
+                let last_user_message = messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == MessageRole::User)
+                    .map_or("", |m| m.content.as_str());
+                Ok(script(last_user_message))
+            }
+            Replies::Fn(respond) => Ok(respond(system, messages, tools)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        self.structured_output
+    }
+
+    async fn chat_structured(
+        &self,
+        system: &str,
+        messages: &[Message],
+        schema: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        if !self.structured_output {
+            return crate::llm::fallback_chat_structured(self, system, messages, schema).await;
+        }
+        self.schemas
+            .lock()
+            .expect("mock lock poisoned")
+            .push(schema.clone());
+        let response = self.chat(system, messages, &[]).await?;
+        serde_json::from_str(&response.message.content).context("mock reply is not bare JSON")
+    }
+}
+
+/// A plain-text response with no tool calls
+pub fn text_response(text: impl Into<String>) -> LlmResponse {
+    LlmResponse {
+        message: Message::assistant(text),
+        tool_calls: Vec::new(),
+        usage: None,
+    }
+}
+
+/// A response requesting a single tool call
+pub fn tool_call_response(
+    id: impl Into<String>,
+    name: impl Into<String>,
+    arguments: serde_json::Value,
+) -> LlmResponse {
+    LlmResponse {
+        message: Message::assistant(""),
+        tool_calls: vec![ToolCall {
+            id: id.into(),
+            name: name.into(),
+            arguments,
+        }],
+        usage: None,
+    }
+}
+
+/// Tool that returns scripted outputs in order instead of executing, and
+/// records the arguments of every call.
+///
+/// Clones share the script and the call log, so a test can keep a clone to
+/// inspect after registering the tool.
+#[derive(Clone)]
+pub struct StubTool {
+    name: String,
+    description: String,
+    outputs: Arc<Mutex<VecDeque<Result<CommandOutput, String>>>>,
+    calls: Arc<Mutex<Vec<Value>>>,
+    writes_files: bool,
+}
+
+impl StubTool {
+    /// Create a stub standing in for the tool called `name`
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            description: format!("Stub of the {} tool", name),
+            name,
+            outputs: Arc::new(Mutex::new(VecDeque::new())),
+            calls: Arc::new(Mutex::new(Vec::new())),
+            writes_files: false,
+        }
+    }
+
+    /// Report every call as changing files, like `write_file`, so
+    /// read-only agents are refused it
+    pub fn with_file_writes(mut self) -> Self {
+        self.writes_files = true;
+        self
+    }
+
+    /// Queue a successful output for the next unanswered call
+    pub fn with_output(self, output: impl Into<String>) -> Self {
+        self.with_exit_status(output, 0)
+    }
+
+    /// Queue the output of a command that exits with `status` for the next
+    /// unanswered call, for stubs standing in for `shell`.
+    /// [`Tool::run_command`] reports the status apart from the output;
+    /// `execute` appends a non-zero one as `shell` does.
+    pub fn with_exit_status(self, output: impl Into<String>, status: i32) -> Self {
+        self.push(Ok(CommandOutput {
+            output: output.into(),
+            status,
+        }));
+        self
+    }
+
+    /// Queue a failure for the next unanswered call
+    pub fn with_error(self, message: impl Into<String>) -> Self {
+        self.push(Err(message.into()));
+        self
+    }
+
+    /// The arguments of each call so far
+    pub fn calls(&self) -> Vec<Value> {
+        self.calls.lock().expect("stub lock poisoned").clone()
+    }
+
+    fn push(&self, output: Result<CommandOutput, String>) {
+        self.outputs
+            .lock()
+            .expect("stub lock poisoned")
+            .push_back(output);
+    }
+
+    fn next(&self, params: Value) -> Result<CommandOutput> {
+        self.calls.lock().expect("stub lock poisoned").push(params);
+        match self.outputs.lock().expect("stub lock poisoned").pop_front() {
+            Some(Ok(output)) => Ok(output),
+            Some(Err(message)) => Err(anyhow::anyhow!(message)),
+            None => anyhow::bail!("stub tool '{}' has no more scripted outputs", self.name),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for StubTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn modifies_files(&self, _params: &Value) -> bool {
+        self.writes_files
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let CommandOutput { mut output, status } = self.next(params)?;
+        if status != 0 {
+            output.push_str(&format!("\n[exit code: {}]", status));
+        }
+        Ok(output)
+    }
+
+    async fn run_command(&self, params: Value) -> Result<CommandOutput> {
+        self.next(params)
+    }
+}
+
+/// A registry holding only the given stubs, for running agents with no
+/// filesystem, shell or network access
+pub fn stub_registry(stubs: impl IntoIterator<Item = StubTool>) -> ToolRegistry {
+    let mut tools = ToolRegistry::new();
+    for stub in stubs {
+        tools.register(stub);
+    }
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stub_replays_its_script_and_records_calls() {
+        let stub = StubTool::new("read_file")
+            .with_output("fn main() {}")
+            .with_error("permission denied");
+        let tools = stub_registry([stub.clone()]);
+        let tool = tools.get("read_file").unwrap();
+
+        let first = tool.execute(json!({ "path": "src/main.rs" })).await;
+        assert_eq!(first.unwrap(), "fn main() {}");
+        let second = tool.execute(json!({ "path": "secret" })).await;
+        assert_eq!(second.unwrap_err().to_string(), "permission denied");
+        let third = tool.execute(json!({})).await;
+        assert!(
+            third
+                .unwrap_err()
+                .to_string()
+                .contains("no more scripted outputs")
+        );
+
+        assert_eq!(stub.calls().len(), 3);
+        assert_eq!(stub.calls()[0]["path"], "src/main.rs");
+    }
+
+    #[tokio::test]
+    async fn shell_stub_reports_exit_status_to_run_command() {
+        let stub = StubTool::new("shell")
+            .with_output("test result: ok")
+            .with_exit_status("test result: FAILED", 101)
+            .with_exit_status("error: could not compile", 1);
+        let tools = stub_registry([stub]);
+        let tool = tools.get("shell").unwrap();
+
+        let passed = tool
+            .run_command(json!({ "command": "cargo test" }))
+            .await
+            .unwrap();
+        assert_eq!(passed.status, 0);
+        assert_eq!(passed.output, "test result: ok");
+        let failed = tool
+            .run_command(json!({ "command": "cargo test" }))
+            .await
+            .unwrap();
+        assert_eq!(failed.status, 101);
+
+        let output = tool.execute(json!({ "command": "cargo build" })).await;
+        assert_eq!(output.unwrap(), "error: could not compile\n[exit code: 1]");
+    }
+
+    #[tokio::test]
+    async fn script_replies_to_the_latest_user_message() {
+        let provider = MockLlmProvider::from_script(|last_user_message| {
+            text_response(format!("echo: {}", last_user_message))
+        });
+        let messages = [
+            Message::user("first"),
+            Message::assistant("ok"),
+            Message::user("second"),
+            Message::tool_result("1", "tool output"),
+        ];
+
+        let response = provider.chat("", &messages, &[]).await.unwrap();
+        assert_eq!(response.message.content, "echo: second");
+        let response = provider.chat("", &[], &[]).await.unwrap();
+        assert_eq!(response.message.content, "echo: ");
+        assert_eq!(provider.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn from_fn_sees_the_whole_request() {
+        let provider = MockLlmProvider::from_fn(|system, messages, tools| {
+            let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+            text_response(format!(
+                "{system} / {} messages / {}",
+                messages.len(),
+                names.join(",")
+            ))
+        });
+        let tools = stub_registry([StubTool::new("read_file")]);
+        let read_file = tools.get("read_file").unwrap();
+
+        let response = provider
+            .chat("be brief", &[Message::user("hi")], &[read_file.as_ref()])
+            .await
+            .unwrap();
+        assert_eq!(
+            response.message.content,
+            "be brief / 1 messages / read_file"
+        );
+        assert_eq!(provider.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn single_response_answers_once() {
+        let provider = MockLlmProvider::single_response("done");
+
+        let response = provider.chat("", &[], &[]).await.unwrap();
+        assert_eq!(response.message.content, "done");
+        assert!(provider.chat("", &[], &[]).await.is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, timeout};
+
+use super::Tool;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+/// A question the agent needs answered before it can continue
+#[derive(Debug)]
+pub struct UserInputRequest {
+    /// The question to show the user
+    pub prompt: String,
+    /// Channel for the user's reply
+    pub response: oneshot::Sender<String>,
+}
+
+/// Tool for asking the user a clarifying question mid-run.
+///
+/// Each call sends a [`UserInputRequest`] to the consumer of the paired
+/// receiver and waits (up to a timeout) for the reply.
+pub struct AskUserTool {
+    requests: mpsc::Sender<UserInputRequest>,
+    timeout: Duration,
+}
+
+impl AskUserTool {
+    /// Create the tool, sending questions to `requests`
+    pub fn new(requests: mpsc::Sender<UserInputRequest>) -> Self {
+        Self {
+            requests,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        }
+    }
+
+    /// Override how long to wait for a reply (default: 300 seconds)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for AskUserTool {
+    fn name(&self) -> &str {
+        "ask_user"
+    }
+
+    fn description(&self) -> &str {
+        "Ask the user a clarifying question and wait for their answer. Only use when you cannot proceed without it."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": {
+                    "type": "string",
+                    "description": "The question to ask the user"
+                }
+            },
+            "required": ["prompt"]
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let prompt = params["prompt"]
+            .as_str()
+            .context("missing 'prompt' parameter")?;
+
+        let (response_tx, response_rx) = oneshot::channel();
+        self.requests
+            .send(UserInputRequest {
+                prompt: prompt.to_string(),
+                response: response_tx,
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("user input is not available"))?;
+
+        match timeout(self.timeout, response_rx).await {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => anyhow::bail!("user input request was dropped without a reply"),
+            Err(_) => anyhow::bail!(
+                "no response from user within {} seconds",
+                self.timeout.as_secs()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn returns_scripted_user_response() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let tool = AskUserTool::new(tx);
+
+        let user = tokio::spawn(async move {
+            let request = rx.recv().await.unwrap();
+            assert_eq!(request.prompt, "Which database?");
+            request.response.send("postgres".to_string()).unwrap();
+        });
+
+        let answer = tool
+            .execute(json!({"prompt": "Which database?"}))
+            .await
+            .unwrap();
+
+        user.await.unwrap();
+        assert_eq!(answer, "postgres");
+    }
+
+    #[tokio::test]
+    async fn times_out_without_reply() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let tool = AskUserTool::new(tx).with_timeout(Duration::from_millis(10));
+
+        // Hold the request open without answering
+        let user = tokio::spawn(async move { rx.recv().await });
+
+        let result = tool.execute(json!({"prompt": "Anyone there?"})).await;
+
+        assert!(result.unwrap_err().to_string().contains("no response"));
+        drop(user.await);
+    }
+
+    #[tokio::test]
+    async fn errors_when_no_one_is_listening() {
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let tool = AskUserTool::new(tx);
+
+        let result = tool.execute(json!({"prompt": "Hello?"})).await;
+
+        assert!(result.unwrap_err().to_string().contains("not available"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Line-based unified diffs for previewing file changes, and a tool that
+//! shows them to agents.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use similar::TextDiff;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+use super::Tool;
+use super::validate_path;
+use crate::config::Policy;
+
+/// Lines of unchanged context shown around a change
+const CONTEXT_LINES: usize = 3;
+/// Diff output longer than this is truncated
+const MAX_OUTPUT_BYTES: usize = 100_000;
+/// How long `git diff` may run
+const GIT_DIFF_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tool for showing what changed in a file, as a unified diff
+pub struct DiffTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for DiffTool {
+    fn name(&self) -> &str {
+        "diff"
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Show a unified diff. Pass `path` to see a file's uncommitted changes (git diff), \
+        `path_before` and `path_after` to compare two files, or `content_before` and \
+        `content_after` to compare two texts."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "File to diff against its last committed version"
+                },
+                "path_before": {
+                    "type": "string",
+                    "description": "Original file to compare"
+                },
+                "path_after": {
+                    "type": "string",
+                    "description": "Changed file to compare"
+                },
+                "content_before": {
+                    "type": "string",
+                    "description": "Original text to compare"
+                },
+                "content_after": {
+                    "type": "string",
+                    "description": "Changed text to compare"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let param = |name: &str| params[name].as_str();
+
+        let diff = match (
+            param("path"),
+            param("path_before").zip(param("path_after")),
+            param("content_before").zip(param("content_after")),
+        ) {
+            (Some(path), None, None) => self.git_diff(path).await?,
+            (None, Some((before, after)), None) => self.diff_files(before, after).await?,
+            (None, None, Some((before, after))) => labeled_diff("before", "after", before, after),
+            _ => anyhow::bail!(
+                "pass exactly one of: path, path_before + path_after, or content_before + content_after"
+            ),
+        };
+
+        if diff.is_empty() {
+            return Ok("No differences".to_string());
+        }
+        Ok(truncate_diff(diff, MAX_OUTPUT_BYTES))
+    }
+}
+
+impl DiffTool {
+    /// Diff two files on disk
+    async fn diff_files(&self, before: &str, after: &str) -> Result<String> {
+        let before_path = validate_path(before, &self.policy)?;
+        let after_path = validate_path(after, &self.policy)?;
+        let old = tokio::fs::read_to_string(&before_path)
+            .await
+            .with_context(|| format!("failed to read file: {}", before))?;
+        let new = tokio::fs::read_to_string(&after_path)
+            .await
+            .with_context(|| format!("failed to read file: {}", after))?;
+        Ok(labeled_diff(before, after, &old, &new))
+    }
+
+    /// A file's uncommitted changes, from `git diff` run in its directory
+    async fn git_diff(&self, path: &str) -> Result<String> {
+        let validated = validate_path(path, &self.policy)?;
+        let dir = validated.parent().unwrap_or(Path::new("."));
+
+        let output = tokio::time::timeout(
+            GIT_DIFF_TIMEOUT,
+            // External diff drivers and textconv filters come from the repo's
+            // config and .gitattributes, and would run arbitrary commands
+            Command::new("git")
+                .args(["diff", "--no-ext-diff", "--no-textconv", "--"])
+                .arg(&validated)
+                .current_dir(dir)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .context("git diff timed out")?
+        .context("failed to run git")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git diff failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Render the change from `old` to `new` as a unified diff, with a hunk
+/// per group of nearby changes. Returns an empty string when the texts are
+/// identical.
+pub(crate) fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    labeled_diff(path, path, old, new)
+}
+
+/// [`unified_diff`] with separate names for the old and new side
+fn labeled_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .missing_newline_hint(false)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// Cut a diff down to at most `max_bytes` (on a line boundary where
+/// possible), noting how much was left out
+pub(crate) fn truncate_diff(diff: String, max_bytes: usize) -> String {
+    if diff.len() <= max_bytes {
+        return diff;
+    }
+    let mut end = max_bytes;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = diff[..end].rfind('\n').map_or(end, |i| i + 1);
+    format!(
+        "{}... (diff truncated, {} more bytes)\n",
+        &diff[..end],
+        diff.len() - end
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_change_with_surrounding_context() {
+        let old = "1\n2\n3\n4\nold\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nnew\n6\n7\n8\n9\n";
+
+        assert_eq!(
+            unified_diff("lib.rs", old, new),
+            "--- lib.rs\n+++ lib.rs\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-old\n+new\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn separate_edits_get_separate_hunks() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                18 => "eighteen\n".to_string(),
+                _ => format!("{i}\n"),
+            })
+            .collect();
+
+        let diff = unified_diff("lib.rs", &old, &new);
+        assert_eq!(diff.matches("@@ -").count(), 2, "{diff}");
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n"), "{diff}");
+        assert!(
+            diff.contains("@@ -15,6 +15,6 @@\n 15\n 16\n 17\n-18\n+eighteen\n"),
+            "{diff}"
+        );
+    }
+
+    #[test]
+    fn new_file_is_all_additions() {
+        assert_eq!(
+            unified_diff("new.txt", "", "a\nb\n"),
+            "--- new.txt\n+++ new.txt\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+    }
+
+    #[test]
+    fn truncates_long_diffs_on_line_boundaries() {
+        let diff = "--- f\n+++ f\n@@ -1,2 +1,2 @@\n-old line\n+new line\n".to_string();
+        assert_eq!(truncate_diff(diff.clone(), 1000), diff);
+
+        let truncated = truncate_diff(diff, 30);
+        assert_eq!(
+            truncated,
+            "--- f\n+++ f\n@@ -1,2 +1,2 @@\n... (diff truncated, 20 more bytes)\n"
+        );
+    }
+
+    #[test]
+    fn identical_texts_have_no_diff() {
+        assert_eq!(unified_diff("same.txt", "a\n", "a\n"), "");
+    }
+
+    fn tool() -> DiffTool {
+        DiffTool {
+            policy: Policy::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn diffs_two_texts() {
+        let output = tool()
+            .execute(json!({ "content_before": "a\nb\n", "content_after": "a\nc\n" }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            output,
+            "--- before\n+++ after\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn diffs_two_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let before = dir.path().join("old.txt");
+        let after = dir.path().join("new.txt");
+        std::fs::write(&before, "same\n").unwrap();
+        std::fs::write(&after, "same\n").unwrap();
+
+        let params = json!({
+            "path_before": before.to_str().unwrap(),
+            "path_after": after.to_str().unwrap()
+        });
+        assert_eq!(
+            tool().execute(params.clone()).await.unwrap(),
+            "No differences"
+        );
+
+        std::fs::write(&after, "changed\n").unwrap();
+        let output = tool().execute(params).await.unwrap();
+        assert!(output.contains("-same\n+changed"));
+    }
+
+    #[tokio::test]
+    async fn diffs_file_against_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        };
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn a() {}\n").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "lib.rs"]);
+        git(&["commit", "-q", "-m", "init"]);
+        std::fs::write(&file, "fn b() {}\n").unwrap();
+
+        let output = tool()
+            .execute(json!({ "path": file.to_str().unwrap() }))
+            .await
+            .unwrap();
+        assert!(output.contains("-fn a() {}\n+fn b() {}"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn rejects_ambiguous_params() {
+        let err = tool()
+            .execute(json!({ "path": "a", "content_before": "x", "content_after": "y" }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exactly one of"));
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::ErrorKind;
+
+/// A tool failure that may go away if the same call is repeated: a timeout,
+/// a process that failed to start, a dropped connection. Only such failures
+/// are retried; the message is what the model sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransientToolError {
+    pub reason: String,
+}
+
+impl TransientToolError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for TransientToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for TransientToolError {}
+
+/// Whether a tool error is worth retrying: it was marked with
+/// [`TransientToolError`] or was caused by an I/O timeout, an interruption
+/// or a failed connection
+pub(crate) fn is_transient(error: &anyhow::Error) -> bool {
+    error.is::<TransientToolError>()
+        || error.chain().any(|cause| {
+            cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    ErrorKind::TimedOut
+                        | ErrorKind::Interrupted
+                        | ErrorKind::WouldBlock
+                        | ErrorKind::ConnectionReset
+                        | ErrorKind::ConnectionAborted
+                )
+            }) || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_timeout() || e.is_connect())
+        })
+}
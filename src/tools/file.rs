@@ -4,7 +4,8 @@ use serde_json::{Value, json};
 use std::path::{Component, Path, PathBuf};
 
 use super::Tool;
-use crate::config::Policy;
+use super::diff::{truncate_diff, unified_diff};
+use crate::config::{Policy, PolicyViolation};
 
 /// Validates a file path for security.
 ///
@@ -19,27 +20,14 @@ pub(crate) fn validate_path(path: &str, policy: &Policy) -> Result<PathBuf> {
         .components()
         .any(|c| c == Component::ParentDir)
     {
-        anyhow::bail!("path traversal detected: '..' is not allowed in paths");
+        return Err(
+            PolicyViolation::new("path traversal detected: '..' is not allowed in paths").into(),
+        );
     }
 
     // Canonicalize the path to resolve symlinks and relative components
     let canonical = std::fs::canonicalize(path)
-        .or_else(|_| {
-            // If the file doesn't exist yet (for write operations),
-            // canonicalize the parent directory and append the filename
-            let p = Path::new(path);
-            if let (Some(parent), Some(file_name)) = (p.parent(), p.file_name()) {
-                let parent_path = if parent.as_os_str().is_empty() {
-                    Path::new(".")
-                } else {
-                    parent
-                };
-                let canonical_parent = std::fs::canonicalize(parent_path)?;
-                Ok(canonical_parent.join(file_name))
-            } else {
-                anyhow::bail!("invalid path: {}", path)
-            }
-        })
+        .or_else(|_| canonicalize_missing(Path::new(path)))
         .with_context(|| format!("failed to resolve path: {}", path))?;
 
     let path_str = canonical.to_string_lossy();
@@ -54,17 +42,51 @@ pub(crate) fn validate_path(path: &str, policy: &Policy) -> Result<PathBuf> {
         // Check policy deny_paths
         for denied in &policy.deny_paths {
             if path_str.starts_with(denied) {
-                anyhow::bail!("access to {} is denied by policy", denied);
+                return Err(PolicyViolation::new(format!(
+                    "access to {} is denied by policy",
+                    denied
+                ))
+                .into());
             }
         }
 
         // Check hardcoded sensitive paths
-        check_hardcoded_path_denials(&canonical, &path_str)?;
+        check_hardcoded_path_denials(&canonical, &path_str)
+            .map_err(|e| PolicyViolation::new(e.to_string()))?;
     }
 
     Ok(canonical)
 }
 
+/// Resolve a path that doesn't exist yet (e.g. a write or move target in a
+/// new directory) by canonicalizing its nearest existing ancestor and
+/// appending the missing components
+fn canonicalize_missing(path: &Path) -> Result<PathBuf> {
+    let mut ancestor = path;
+    let mut missing = Vec::new();
+    loop {
+        let existing = if ancestor.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            ancestor
+        };
+        if let Ok(canonical) = std::fs::canonicalize(existing) {
+            return Ok(missing
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name)));
+        }
+
+        match (ancestor.parent(), ancestor.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                ancestor = parent;
+            }
+            _ => anyhow::bail!("invalid path: {}", path.display()),
+        }
+    }
+}
+
 /// Check hardcoded path denials (system-sensitive directories)
 fn check_hardcoded_path_denials(canonical: &Path, path_str: &str) -> Result<()> {
     // Check for sensitive system directories
@@ -147,8 +169,13 @@ impl Tool for ReadFileTool {
         "read_file"
     }
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
-        "Read the contents of a file at the given path"
+        "Read the contents of a file at the given path. For large files, pass a line range \
+        (start_line/end_line) or byte range (start_byte/end_byte) to read only part of it."
     }
 
     fn schema(&self) -> Value {
@@ -158,6 +185,22 @@ impl Tool for ReadFileTool {
                 "path": {
                     "type": "string",
                     "description": "The path to the file to read"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "description": "First line to read (1-based, inclusive)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "description": "Last line to read (1-based, inclusive; clamped to the end of the file)"
+                },
+                "start_byte": {
+                    "type": "integer",
+                    "description": "Byte offset to start reading at (0-based, inclusive)"
+                },
+                "end_byte": {
+                    "type": "integer",
+                    "description": "Byte offset to stop reading at (exclusive; default: end of file)"
                 }
             },
             "required": ["path"]
@@ -169,16 +212,369 @@ impl Tool for ReadFileTool {
             .as_str()
             .context("missing 'path' parameter")?;
 
+        let start_line = optional_usize(&params, "start_line")?;
+        let end_line = optional_usize(&params, "end_line")?;
+        let start_byte = optional_usize(&params, "start_byte")?;
+        let end_byte = optional_usize(&params, "end_byte")?;
+
+        let has_line_range = start_line.is_some() || end_line.is_some();
+        let has_byte_range = start_byte.is_some() || end_byte.is_some();
+        if has_line_range && has_byte_range {
+            anyhow::bail!("use either a line range or a byte range, not both");
+        }
+
         let validated_path = validate_path(path, &self.policy)?;
+        check_file_size(path, &validated_path, &self.policy).await?;
+
+        if has_byte_range {
+            let bytes = tokio::fs::read(&validated_path)
+                .await
+                .with_context(|| format!("failed to read file: {}", path))?;
+            return read_byte_range(path, &bytes, start_byte, end_byte);
+        }
 
         let content = tokio::fs::read_to_string(&validated_path)
             .await
             .with_context(|| format!("failed to read file: {}", path))?;
 
+        if has_line_range {
+            return read_line_range(path, &content, start_line, end_line);
+        }
+
         Ok(content)
     }
 }
 
+/// Tool for checking a file's size, line count and modification time
+/// without reading it into the conversation
+pub struct FileStatTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for FileStatTool {
+    fn name(&self) -> &str {
+        "file_stat"
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Get a file's size in bytes, line count and last-modified time. Much cheaper than \
+        reading it; use it to decide whether to read a large file by line range."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The path to the file"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let path = params["path"]
+            .as_str()
+            .context("missing 'path' parameter")?;
+
+        let validated_path = validate_path(path, &self.policy)?;
+        let metadata = tokio::fs::metadata(&validated_path)
+            .await
+            .with_context(|| format!("failed to stat file: {}", path))?;
+        if !metadata.is_file() {
+            anyhow::bail!("not a file: {}", path);
+        }
+
+        let lines = count_lines(&validated_path)
+            .await
+            .with_context(|| format!("failed to read file: {}", path))?;
+        let modified = metadata
+            .modified()
+            .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339())
+            .unwrap_or_else(|_| "unknown".to_string());
+
+        Ok(format!(
+            "{}: {} bytes, {} lines, modified {}",
+            path,
+            metadata.len(),
+            lines,
+            modified
+        ))
+    }
+}
+
+/// Longest a single `watch_file` call may wait
+const MAX_WATCH_TIMEOUT_SECS: u64 = 300;
+
+/// How often `watch_file` checks for appended bytes
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Most appended output a single `watch_file` call returns
+const MAX_WATCH_BYTES: usize = 100_000;
+
+/// Tool for following output appended to a file, such as the log of a
+/// server started in the background
+pub struct WatchFileTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for WatchFileTool {
+    fn name(&self) -> &str {
+        "watch_file"
+    }
+
+    fn description(&self) -> &str {
+        "Wait for content to be appended to a file (like `tail -f`) and return it. Starts at \
+        from_byte_offset, or the current end of the file; returns once new output stops \
+        arriving or timeout_secs passes. The result says which offset to pass next time."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The path to the file to watch"
+                },
+                "from_byte_offset": {
+                    "type": "integer",
+                    "description": "Byte offset to read from (default: the current end of the file)"
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "description": "How long to wait for new content (max 300)"
+                }
+            },
+            "required": ["path", "timeout_secs"]
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let path = params["path"]
+            .as_str()
+            .context("missing 'path' parameter")?;
+        let from_byte_offset = optional_usize(&params, "from_byte_offset")?.map(|n| n as u64);
+        let timeout_secs = params["timeout_secs"]
+            .as_u64()
+            .context("missing 'timeout_secs' parameter")?
+            .min(MAX_WATCH_TIMEOUT_SECS);
+
+        let validated_path = validate_path(path, &self.policy)?;
+        let mut file = tokio::fs::File::open(&validated_path)
+            .await
+            .with_context(|| format!("failed to open file: {}", path))?;
+        let len = file.metadata().await?.len();
+        let offset = match from_byte_offset {
+            Some(offset) if offset > len => anyhow::bail!(
+                "from_byte_offset {} is past the end of {} ({} bytes); it may have been truncated",
+                offset,
+                path,
+                len
+            ),
+            Some(offset) => offset,
+            None => len,
+        };
+        file.seek(std::io::SeekFrom::Start(offset))
+            .await
+            .with_context(|| format!("failed to seek in file: {}", path))?;
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+        let mut appended = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = file
+                .read(&mut buf)
+                .await
+                .with_context(|| format!("failed to read file: {}", path))?;
+            appended.extend_from_slice(&buf[..read]);
+            if appended.len() >= MAX_WATCH_BYTES {
+                break;
+            }
+            if read > 0 {
+                continue;
+            }
+            // Once output has arrived, stop at the first poll that adds
+            // nothing rather than holding it until the timeout
+            let now = tokio::time::Instant::now();
+            if !appended.is_empty() || now >= deadline {
+                break;
+            }
+            tokio::time::sleep_until(deadline.min(now + WATCH_POLL_INTERVAL)).await;
+        }
+        appended.truncate(MAX_WATCH_BYTES);
+
+        let next_offset = offset + appended.len() as u64;
+        if appended.is_empty() {
+            return Ok(format!(
+                "{}: no new content within {}s (next offset {})",
+                path, timeout_secs, next_offset
+            ));
+        }
+        Ok(format!(
+            "{}: {} new bytes (next offset {})\n{}",
+            path,
+            appended.len(),
+            next_offset,
+            String::from_utf8_lossy(&appended)
+        ))
+    }
+}
+
+/// Count lines the way `str::lines` does (a trailing newline doesn't start
+/// another line), streaming the file instead of loading it
+async fn count_lines(path: &Path) -> Result<usize> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut newlines = 0;
+    let mut last = None;
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        newlines += buf[..read].iter().filter(|&&b| b == b'\n').count();
+        last = Some(buf[read - 1]);
+    }
+
+    Ok(match last {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    })
+}
+
+/// Fail if the file at `validated` is larger than `Policy::max_file_size_bytes`
+async fn check_file_size(path: &str, validated: &Path, policy: &Policy) -> Result<()> {
+    let Some(limit) = policy.max_file_size_bytes else {
+        return Ok(());
+    };
+    let size = tokio::fs::metadata(validated)
+        .await
+        .with_context(|| format!("failed to stat file: {}", path))?
+        .len();
+    if size > limit {
+        anyhow::bail!(
+            "{} is {} bytes, over the policy's {}-byte file size limit",
+            path,
+            size,
+            limit
+        );
+    }
+    Ok(())
+}
+
+/// Fail if `size` bytes of new content for `path` exceed
+/// `Policy::max_file_size_bytes`
+fn check_content_size(path: &str, size: usize, policy: &Policy) -> Result<()> {
+    match policy.max_file_size_bytes {
+        Some(limit) if size as u64 > limit => anyhow::bail!(
+            "writing {} bytes to {} would exceed the policy's {}-byte file size limit",
+            size,
+            path,
+            limit
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Read an optional non-negative integer parameter
+fn optional_usize(params: &Value, name: &str) -> Result<Option<usize>> {
+    match &params[name] {
+        Value::Null => Ok(None),
+        value => value
+            .as_u64()
+            .map(|n| Some(n as usize))
+            .with_context(|| format!("'{}' must be a non-negative integer", name)),
+    }
+}
+
+/// Select lines `start..=end` (1-based) with a header saying where they are.
+/// `end` is clamped to the last line; a `start` past the end is an error.
+fn read_line_range(
+    path: &str,
+    content: &str,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let total = lines.len();
+    let start = start.unwrap_or(1);
+    let end = end.unwrap_or(total).min(total);
+
+    if start == 0 {
+        anyhow::bail!("start_line is 1-based and must be at least 1");
+    }
+    if start > total {
+        anyhow::bail!(
+            "start_line {} is past the end of {} ({} lines)",
+            start,
+            path,
+            total
+        );
+    }
+    if start > end {
+        anyhow::bail!(
+            "start_line ({}) must not be after end_line ({})",
+            start,
+            end
+        );
+    }
+
+    Ok(format!(
+        "Lines {}-{} of {}\n{}",
+        start,
+        end,
+        path,
+        lines[start - 1..end].join("\n")
+    ))
+}
+
+/// Select bytes `start..end` with a header saying where they are
+fn read_byte_range(
+    path: &str,
+    bytes: &[u8],
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<String> {
+    let total = bytes.len();
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(total);
+
+    if start >= end {
+        anyhow::bail!("start_byte ({}) must be before end_byte ({})", start, end);
+    }
+    if end > total {
+        anyhow::bail!(
+            "byte range {}-{} is outside the file ({} bytes)",
+            start,
+            end,
+            total
+        );
+    }
+
+    // Offsets may split a multi-byte character; show those bytes lossily
+    Ok(format!(
+        "Bytes {}-{} of {} ({} bytes)\n{}",
+        start,
+        end,
+        path,
+        total,
+        String::from_utf8_lossy(&bytes[start..end])
+    ))
+}
+
 /// Tool for writing files
 pub struct WriteFileTool {
     pub policy: Policy,
@@ -190,6 +586,10 @@ impl Tool for WriteFileTool {
         "write_file"
     }
 
+    fn modifies_files(&self, _params: &Value) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Write content to a file at the given path, creating parent directories if needed"
     }
@@ -221,6 +621,14 @@ impl Tool for WriteFileTool {
 
         // First validate the path to ensure it's not in a restricted location
         let validated_path = validate_path(path, &self.policy)?;
+        check_content_size(path, content.len(), &self.policy)?;
+
+        if self.policy.dry_run {
+            let existing = tokio::fs::read_to_string(&validated_path)
+                .await
+                .unwrap_or_default();
+            return Ok(dry_run_preview(path, &existing, content));
+        }
 
         // Create parent directories using the validated path, not the raw input
         if let Some(parent) = validated_path.parent() {
@@ -243,6 +651,32 @@ impl Tool for WriteFileTool {
     }
 }
 
+/// Cap on diffs included in tool results, so a huge change doesn't flood
+/// the agent's context
+const MAX_DIFF_BYTES: usize = 4096;
+
+/// Unified diff of a file change for tool results, capped at
+/// [`MAX_DIFF_BYTES`]
+fn change_diff(path: &str, old: &str, new: &str) -> String {
+    let diff = unified_diff(path, old, new);
+    if diff.is_empty() {
+        "(no changes)".to_string()
+    } else {
+        truncate_diff(diff, MAX_DIFF_BYTES)
+    }
+}
+
+/// What a write would have done under `Policy::dry_run`, as a diff against
+/// the current file contents
+fn dry_run_preview(path: &str, old: &str, new: &str) -> String {
+    format!(
+        "[dry-run] would write {} bytes to {}\n\n{}",
+        new.len(),
+        path,
+        change_diff(path, old, new)
+    )
+}
+
 /// Tool for editing files (find and replace)
 pub struct EditFileTool {
     pub policy: Policy,
@@ -254,8 +688,13 @@ impl Tool for EditFileTool {
         "edit_file"
     }
 
+    fn modifies_files(&self, _params: &Value) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
-        "Edit a file by replacing old_string with new_string. The old_string must be unique in the file."
+        "Edit a file by replacing old_string with new_string. The old_string must be unique in the file \
+        unless replace_all is true."
     }
 
     fn schema(&self) -> Value {
@@ -273,6 +712,10 @@ impl Tool for EditFileTool {
                 "new_string": {
                     "type": "string",
                     "description": "The string to replace it with"
+                },
+                "replace_all": {
+                    "type": "boolean",
+                    "description": "Replace every occurrence instead of requiring a unique match (default: false)"
                 }
             },
             "required": ["path", "old_string", "new_string"]
@@ -289,12 +732,14 @@ impl Tool for EditFileTool {
         let new_string = params["new_string"]
             .as_str()
             .context("missing 'new_string' parameter")?;
+        let replace_all = params["replace_all"].as_bool().unwrap_or(false);
 
         if old_string.is_empty() {
             anyhow::bail!("old_string must not be empty");
         }
 
         let validated_path = validate_path(path, &self.policy)?;
+        check_file_size(path, &validated_path, &self.policy).await?;
 
         let content = tokio::fs::read_to_string(&validated_path)
             .await
@@ -304,7 +749,7 @@ impl Tool for EditFileTool {
         if count == 0 {
             anyhow::bail!("old_string not found in file: {}", path);
         }
-        if count > 1 {
+        if count > 1 && !replace_all {
             anyhow::bail!(
                 "old_string found {} times in file (must be unique): {}",
                 count,
@@ -312,13 +757,115 @@ impl Tool for EditFileTool {
             );
         }
 
-        let new_content = content.replacen(old_string, new_string, 1);
+        let new_content = content.replace(old_string, new_string);
+        check_content_size(path, new_content.len(), &self.policy)?;
+
+        if self.policy.dry_run {
+            return Ok(dry_run_preview(path, &content, &new_content));
+        }
 
         tokio::fs::write(&validated_path, &new_content)
             .await
             .with_context(|| format!("failed to write file: {}", path))?;
 
-        Ok(format!("Successfully edited {}", path))
+        let summary = if replace_all {
+            format!("Replaced {} occurrences in {}", count, path)
+        } else {
+            format!("Successfully edited {}", path)
+        };
+        Ok(format!(
+            "{}\n\n{}",
+            summary,
+            change_diff(path, &content, &new_content)
+        ))
+    }
+}
+
+/// Tool for moving or renaming files
+pub struct MoveFileTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for MoveFileTool {
+    fn name(&self) -> &str {
+        "move_file"
+    }
+
+    fn modifies_files(&self, _params: &Value) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Move or rename a file, creating the destination's parent directories if needed. \
+        Fails if the destination exists unless overwrite is true."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "from": {
+                    "type": "string",
+                    "description": "The path of the file to move (alias: source)"
+                },
+                "to": {
+                    "type": "string",
+                    "description": "The destination path (alias: destination)"
+                },
+                "overwrite": {
+                    "type": "boolean",
+                    "description": "Whether to replace an existing destination (default: false)"
+                }
+            },
+            "required": ["from", "to"]
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let from = params["from"]
+            .as_str()
+            .or_else(|| params["source"].as_str())
+            .context("missing 'from' parameter")?;
+        let to = params["to"]
+            .as_str()
+            .or_else(|| params["destination"].as_str())
+            .context("missing 'to' parameter")?;
+        let overwrite = params["overwrite"].as_bool().unwrap_or(false);
+
+        let validated_from = validate_path(from, &self.policy)?;
+        let validated_to = validate_path(to, &self.policy)?;
+
+        if !overwrite && tokio::fs::try_exists(&validated_to).await.unwrap_or(false) {
+            anyhow::bail!(
+                "destination already exists: {} (set overwrite to replace it)",
+                to
+            );
+        }
+
+        if let Some(parent) = validated_to.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("failed to create directory: {}", parent.display()))?;
+        }
+
+        match tokio::fs::rename(&validated_from, &validated_to).await {
+            Ok(()) => {}
+            // rename can't cross filesystems; fall back to copy + remove
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                tokio::fs::copy(&validated_from, &validated_to)
+                    .await
+                    .with_context(|| format!("failed to copy {} to {}", from, to))?;
+                tokio::fs::remove_file(&validated_from)
+                    .await
+                    .with_context(|| format!("failed to remove {} after copying", from))?;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to move {} to {}", from, to));
+            }
+        }
+
+        Ok(format!("Successfully moved {} to {}", from, to))
     }
 }
 
@@ -415,6 +962,91 @@ mod tests {
         assert!(validate_path(file.to_str().unwrap(), &policy).is_err());
     }
 
+    #[tokio::test]
+    async fn file_stat_reports_size_and_line_count() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lines.txt");
+        fs::write(&file, "one\ntwo\nthree").unwrap();
+        let tool = FileStatTool {
+            policy: default_policy(),
+        };
+
+        let output = tool
+            .execute(json!({ "path": file.to_str().unwrap() }))
+            .await
+            .unwrap();
+        assert!(
+            output.contains(": 13 bytes, 3 lines, modified "),
+            "{output}"
+        );
+
+        // A trailing newline doesn't add a line
+        fs::write(&file, "one\ntwo\n").unwrap();
+        let output = tool
+            .execute(json!({ "path": file.to_str().unwrap() }))
+            .await
+            .unwrap();
+        assert!(output.contains(": 8 bytes, 2 lines,"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn file_stat_rejects_denied_path() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("secret.txt");
+        fs::write(&file, "token").unwrap();
+        let canonical_dir = fs::canonicalize(dir.path()).unwrap();
+        let tool = FileStatTool {
+            policy: Policy {
+                deny_paths: vec![canonical_dir.to_string_lossy().to_string()],
+                ..Policy::default()
+            },
+        };
+
+        let err = tool
+            .execute(json!({ "path": file.to_str().unwrap() }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("denied by policy"));
+    }
+
+    #[tokio::test]
+    async fn files_over_size_limit_are_not_read_or_written() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("big.txt");
+        fs::write(&file, "0123456789").unwrap();
+        let path = file.to_str().unwrap();
+        let policy = Policy {
+            max_file_size_bytes: Some(8),
+            ..Policy::default()
+        };
+
+        let err = ReadFileTool {
+            policy: policy.clone(),
+        }
+        .execute(json!({ "path": path }))
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("is 10 bytes, over the policy's 8-byte")
+        );
+
+        let err = WriteFileTool {
+            policy: policy.clone(),
+        }
+        .execute(json!({ "path": path, "content": "way too long" }))
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("would exceed the policy's 8-byte"));
+
+        let err = EditFileTool { policy }
+            .execute(json!({ "path": path, "old_string": "0", "new_string": "x" }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("over the policy's 8-byte"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "0123456789");
+    }
+
     #[test]
     fn validate_path_allows_normal_files() {
         let dir = tempdir().unwrap();
@@ -423,4 +1055,485 @@ mod tests {
 
         assert!(validate_path(file.to_str().unwrap(), &default_policy()).is_ok());
     }
+
+    fn move_tool() -> MoveFileTool {
+        MoveFileTool {
+            policy: default_policy(),
+        }
+    }
+
+    #[tokio::test]
+    async fn move_file_renames_within_directory() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("old.txt");
+        let to = dir.path().join("new.txt");
+        fs::write(&from, "content").unwrap();
+
+        move_tool()
+            .execute(json!({ "from": from.to_str().unwrap(), "to": to.to_str().unwrap() }))
+            .await
+            .unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "content");
+    }
+
+    #[tokio::test]
+    async fn move_file_moves_across_directories() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("a").join("file.txt");
+        let to = dir.path().join("b").join("file.txt");
+        fs::create_dir_all(from.parent().unwrap()).unwrap();
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::write(&from, "content").unwrap();
+
+        move_tool()
+            .execute(json!({ "from": from.to_str().unwrap(), "to": to.to_str().unwrap() }))
+            .await
+            .unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "content");
+    }
+
+    #[tokio::test]
+    async fn move_file_refuses_to_overwrite_unless_asked() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("from.txt");
+        let to = dir.path().join("to.txt");
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        let params = json!({ "from": from.to_str().unwrap(), "to": to.to_str().unwrap() });
+        assert!(move_tool().execute(params).await.is_err());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "old");
+
+        let params = json!({
+            "from": from.to_str().unwrap(),
+            "to": to.to_str().unwrap(),
+            "overwrite": true
+        });
+        move_tool().execute(params).await.unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn move_file_denies_blocked_destination() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("config.txt");
+        let to = dir.path().join(".env");
+        fs::write(&from, "SECRET=foo").unwrap();
+
+        let result = move_tool()
+            .execute(json!({ "from": from.to_str().unwrap(), "to": to.to_str().unwrap() }))
+            .await;
+
+        assert!(result.is_err());
+        assert!(from.exists());
+        assert!(!to.exists());
+    }
+
+    fn read_tool() -> ReadFileTool {
+        ReadFileTool {
+            policy: default_policy(),
+        }
+    }
+
+    fn numbered_lines(count: usize) -> String {
+        (1..=count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[tokio::test]
+    async fn read_file_returns_middle_of_file() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("big.rs");
+        fs::write(&file, numbered_lines(950)).unwrap();
+        let path = file.to_str().unwrap();
+
+        let result = read_tool()
+            .execute(json!({ "path": path, "start_line": 100, "end_line": 102 }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result,
+            format!("Lines 100-102 of {}\nline 100\nline 101\nline 102", path)
+        );
+    }
+
+    #[tokio::test]
+    async fn read_file_returns_single_line() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("small.rs");
+        fs::write(&file, numbered_lines(10)).unwrap();
+        let path = file.to_str().unwrap();
+
+        let result = read_tool()
+            .execute(json!({ "path": path, "start_line": 7, "end_line": 7 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result, format!("Lines 7-7 of {}\nline 7", path));
+    }
+
+    #[tokio::test]
+    async fn read_file_clamps_end_line_to_last_line() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("small.rs");
+        fs::write(&file, numbered_lines(10)).unwrap();
+        let path = file.to_str().unwrap();
+
+        let result = read_tool()
+            .execute(json!({ "path": path, "start_line": 9, "end_line": 500 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result, format!("Lines 9-10 of {}\nline 9\nline 10", path));
+    }
+
+    #[tokio::test]
+    async fn read_file_errors_when_start_line_is_out_of_range() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("small.rs");
+        fs::write(&file, numbered_lines(10)).unwrap();
+
+        let err = read_tool()
+            .execute(json!({ "path": file.to_str().unwrap(), "start_line": 11 }))
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("past the end"));
+    }
+
+    #[tokio::test]
+    async fn read_file_rejects_invalid_line_ranges() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("small.rs");
+        fs::write(&file, numbered_lines(10)).unwrap();
+        let path = file.to_str().unwrap();
+
+        for params in [
+            json!({ "path": path, "start_line": 5, "end_line": 4 }),
+            json!({ "path": path, "start_line": 0 }),
+            json!({ "path": path, "start_line": 1, "start_byte": 0 }),
+        ] {
+            assert!(read_tool().execute(params).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn read_file_returns_requested_byte_range() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("data.txt");
+        fs::write(&file, "hello world").unwrap();
+
+        let path = file.to_str().unwrap();
+
+        let result = read_tool()
+            .execute(json!({ "path": path, "start_byte": 6 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result, format!("Bytes 6-11 of {} (11 bytes)\nworld", path));
+    }
+
+    #[tokio::test]
+    async fn move_file_creates_destination_directories() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("lib.rs");
+        let destination = dir.path().join("src").join("util").join("lib.rs");
+        fs::write(&source, "content").unwrap();
+
+        move_tool()
+            .execute(json!({
+                "source": source.to_str().unwrap(),
+                "destination": destination.to_str().unwrap()
+            }))
+            .await
+            .unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "content");
+    }
+
+    #[test]
+    fn validate_path_resolves_targets_in_missing_directories() {
+        let dir = tempdir().unwrap();
+        let canonical_dir = fs::canonicalize(dir.path()).unwrap();
+        let target = dir.path().join("a").join("b").join("new.txt");
+
+        let resolved = validate_path(target.to_str().unwrap(), &default_policy()).unwrap();
+        assert_eq!(resolved, canonical_dir.join("a").join("b").join("new.txt"));
+    }
+
+    fn edit_tool() -> EditFileTool {
+        EditFileTool {
+            policy: default_policy(),
+        }
+    }
+
+    #[tokio::test]
+    async fn edit_file_replace_all_replaces_every_occurrence() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "foo(); foo(); foo();").unwrap();
+        let path = file.to_str().unwrap();
+
+        let result = edit_tool()
+            .execute(json!({
+                "path": path,
+                "old_string": "foo",
+                "new_string": "bar",
+                "replace_all": true
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.starts_with(&format!("Replaced 3 occurrences in {}\n\n", path)));
+        assert!(result.contains("-foo(); foo(); foo();\n+bar(); bar(); bar();"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "bar(); bar(); bar();");
+    }
+
+    #[tokio::test]
+    async fn edit_file_replace_all_still_errors_without_matches() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "foo();").unwrap();
+
+        let result = edit_tool()
+            .execute(json!({
+                "path": file.to_str().unwrap(),
+                "old_string": "missing",
+                "new_string": "bar",
+                "replace_all": true
+            }))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "foo();");
+    }
+
+    #[tokio::test]
+    async fn edit_file_requires_unique_match_by_default() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "foo(); foo();").unwrap();
+        let path = file.to_str().unwrap();
+
+        let duplicate = edit_tool()
+            .execute(json!({ "path": path, "old_string": "foo", "new_string": "bar" }))
+            .await;
+        assert!(duplicate.is_err());
+
+        edit_tool()
+            .execute(
+                json!({ "path": path, "old_string": "foo(); foo", "new_string": "bar(); baz" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "bar(); baz();");
+    }
+
+    #[tokio::test]
+    async fn edit_file_rejects_empty_old_string_with_replace_all() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "foo();").unwrap();
+
+        let result = edit_tool()
+            .execute(json!({
+                "path": file.to_str().unwrap(),
+                "old_string": "",
+                "new_string": "bar",
+                "replace_all": true
+            }))
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    fn dry_run_policy() -> Policy {
+        Policy {
+            dry_run: true,
+            ..default_policy()
+        }
+    }
+
+    #[tokio::test]
+    async fn write_file_dry_run_leaves_disk_untouched() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("new").join("notes.txt");
+        let path = file.to_str().unwrap();
+
+        let preview = WriteFileTool {
+            policy: dry_run_policy(),
+        }
+        .execute(json!({ "path": path, "content": "hello\nworld\n" }))
+        .await
+        .unwrap();
+
+        assert!(preview.starts_with(&format!("[dry-run] would write 12 bytes to {}", path)));
+        assert!(preview.contains("+hello\n+world\n"));
+        assert!(!file.exists());
+        assert!(!dir.path().join("new").exists());
+    }
+
+    #[tokio::test]
+    async fn edit_file_dry_run_previews_change_with_context() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        let preview = EditFileTool {
+            policy: dry_run_policy(),
+        }
+        .execute(json!({ "path": path, "old_string": "fn b() {}", "new_string": "fn b2() {}" }))
+        .await
+        .unwrap();
+
+        assert!(preview.starts_with("[dry-run] would write"));
+        assert!(preview.contains(" fn a() {}\n-fn b() {}\n+fn b2() {}\n fn c() {}\n"));
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "fn a() {}\nfn b() {}\nfn c() {}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_file_result_shows_diff_with_context() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(
+            &file,
+            "use std::fmt;\n\nfn greet() {\n    println!(\"hi\");\n}\n",
+        )
+        .unwrap();
+        let path = file.to_str().unwrap();
+
+        let result = edit_tool()
+            .execute(json!({
+                "path": path,
+                "old_string": "println!(\"hi\")",
+                "new_string": "println!(\"hello\")"
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.starts_with(&format!("Successfully edited {}\n\n", path)));
+        assert!(result.contains("@@ -1,5 +1,5 @@\n"));
+        assert!(result.contains("-    println!(\"hi\");\n+    println!(\"hello\");\n"));
+        assert!(result.contains(" fn greet() {\n"));
+    }
+
+    #[tokio::test]
+    async fn edit_file_result_caps_large_diffs() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("big.txt");
+        fs::write(&file, "marker\n").unwrap();
+        let replacement = "line\n".repeat(5000);
+
+        let result = edit_tool()
+            .execute(json!({
+                "path": file.to_str().unwrap(),
+                "old_string": "marker\n",
+                "new_string": replacement
+            }))
+            .await
+            .unwrap();
+
+        assert!(result.len() < MAX_DIFF_BYTES + 200);
+        assert!(result.contains("(diff truncated,"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), replacement);
+    }
+
+    #[tokio::test]
+    async fn watch_file_returns_content_appended_while_waiting() {
+        use std::io::Write;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("server.log");
+        fs::write(&file, "starting\n").unwrap();
+        let tool = WatchFileTool {
+            policy: default_policy(),
+        };
+
+        let writer_path = file.clone();
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            let mut log = fs::OpenOptions::new()
+                .append(true)
+                .open(writer_path)
+                .unwrap();
+            log.write_all(b"listening on :8080\n").unwrap();
+        });
+
+        let output = tool
+            .execute(json!({ "path": file.to_str().unwrap(), "timeout_secs": 5 }))
+            .await
+            .unwrap();
+        writer.await.unwrap();
+
+        assert!(
+            output.contains(": 19 new bytes (next offset 28)"),
+            "{output}"
+        );
+        assert!(output.ends_with("listening on :8080\n"), "{output}");
+        assert!(!output.contains("starting"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn watch_file_reads_from_offset_and_times_out_quietly() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("server.log");
+        fs::write(&file, "one\ntwo\n").unwrap();
+        let tool = WatchFileTool {
+            policy: default_policy(),
+        };
+        let path = file.to_str().unwrap();
+
+        let output = tool
+            .execute(json!({ "path": path, "from_byte_offset": 4, "timeout_secs": 1 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            format!("{path}: 4 new bytes (next offset 8)\ntwo\n")
+        );
+
+        let output = tool
+            .execute(json!({ "path": path, "from_byte_offset": 8, "timeout_secs": 0 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            format!("{path}: no new content within 0s (next offset 8)")
+        );
+
+        let err = tool
+            .execute(json!({ "path": path, "from_byte_offset": 100, "timeout_secs": 0 }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("past the end"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn watch_file_rejects_denied_path() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("secret.log");
+        fs::write(&file, "token").unwrap();
+        let canonical_dir = fs::canonicalize(dir.path()).unwrap();
+        let tool = WatchFileTool {
+            policy: Policy {
+                deny_paths: vec![canonical_dir.to_string_lossy().to_string()],
+                ..Policy::default()
+            },
+        };
+
+        let err = tool
+            .execute(json!({ "path": file.to_str().unwrap(), "timeout_secs": 0 }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("denied by policy"), "{err}");
+    }
 }
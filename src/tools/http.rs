@@ -0,0 +1,394 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{Value, json};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use super::Tool;
+use crate::config::{Policy, PolicyViolation};
+
+const MAX_OUTPUT_BYTES: usize = 100_000;
+/// Bodies are read up to this size; anything past it is dropped unread
+const MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Redirects followed before giving up; each hop is checked like the first URL
+const MAX_REDIRECTS: usize = 5;
+
+/// Elements whose content is never readable text
+static NON_TEXT_ELEMENTS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<(script|style|noscript|svg|head)\b.*?</(script|style|noscript|svg|head)\s*>")
+        .expect("valid regex")
+});
+static COMMENTS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").expect("valid regex"));
+/// Tags that end a line of text
+static BLOCK_TAGS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)</?(p|div|br|li|tr|h[1-6]|pre|section|article|table|ul|ol)\b[^>]*>")
+        .expect("valid regex")
+});
+static TAGS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").expect("valid regex"));
+static BLANK_LINES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\n\s*\n+").expect("valid regex"));
+
+/// Find the largest byte index <= `index` that is a valid char boundary.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    let mut i = index;
+    while i > 0 && !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Tool for fetching a web page or file over HTTP(S), e.g. API docs
+pub struct HttpFetchTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for HttpFetchTool {
+    fn name(&self) -> &str {
+        "http_fetch"
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a URL with an HTTP GET and return its body. HTML pages are converted to plain \
+        text. Useful for reading documentation, RFCs or example code."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "The http:// or https:// URL to fetch"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let url = params["url"].as_str().context("missing 'url' parameter")?;
+        let mut current =
+            reqwest::Url::parse(url).with_context(|| format!("invalid URL: {}", url))?;
+
+        // Redirects are followed by hand so every hop goes through the same
+        // host and address checks as the first request
+        let mut redirects = 0;
+        let mut response = loop {
+            let response = self.fetch_one(&current).await?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok());
+            match location {
+                Some(location) if response.status().is_redirection() => {
+                    if redirects == MAX_REDIRECTS {
+                        anyhow::bail!("{} redirected more than {} times", url, MAX_REDIRECTS);
+                    }
+                    redirects += 1;
+                    current = current
+                        .join(location)
+                        .with_context(|| format!("invalid redirect location: {}", location))?;
+                }
+                _ => break response,
+            }
+        };
+
+        let status = response.status();
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("html"));
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("failed to read response from {}", url))?
+        {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&body);
+        let mut text = if is_html {
+            html_to_text(&text)
+        } else {
+            text.into_owned()
+        };
+        if text.len() > MAX_OUTPUT_BYTES {
+            let boundary = floor_char_boundary(&text, MAX_OUTPUT_BYTES);
+            text.truncate(boundary);
+            text.push_str("\n... (output truncated)");
+        }
+
+        Ok(format!("HTTP {} {}\n\n{}", status.as_u16(), url, text))
+    }
+}
+
+impl HttpFetchTool {
+    /// Send a GET for `url` without following redirects. The host is
+    /// resolved up front and the connection pinned to the checked address,
+    /// so a DNS answer can't change between the check and the request.
+    async fn fetch_one(&self, url: &reqwest::Url) -> Result<reqwest::Response> {
+        validate_url(url, &self.policy)?;
+        let host = url.host_str().context("URL has no host")?;
+        let port = url.port_or_known_default().context("URL has no port")?;
+        let addr = resolve_host(host, port, &self.policy).await?;
+
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(host, addr)
+            .build()
+            .context("failed to create HTTP client")?;
+        client
+            .get(url.clone())
+            .send()
+            .await
+            .with_context(|| format!("failed to fetch {}", url))
+    }
+}
+
+/// Resolve `host`, rejecting it if any address is loopback, private,
+/// link-local or unspecified (unless the policy allows private addresses)
+async fn resolve_host(host: &str, port: u16, policy: &Policy) -> Result<SocketAddr> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("failed to resolve {}", host))?
+        .collect();
+    if !policy.allow_private_http {
+        if let Some(addr) = addrs.iter().find(|addr| is_private_address(addr.ip())) {
+            return Err(PolicyViolation::new(format!(
+                "host '{}' resolves to the non-public address {}",
+                host,
+                addr.ip()
+            ))
+            .into());
+        }
+    }
+    addrs
+        .into_iter()
+        .next()
+        .with_context(|| format!("{} did not resolve to any address", host))
+}
+
+/// Addresses that reach the local machine or network rather than the internet
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_address(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Only plain http(s) URLs to hosts in `Policy::allow_http_hosts` (when set)
+fn validate_url(url: &reqwest::Url, policy: &Policy) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("only http and https URLs can be fetched");
+    }
+    let host = url.host_str().context("URL has no host")?;
+
+    if !policy.allow_http_hosts.is_empty() {
+        // An entry allows the host itself and its subdomains
+        let allowed = policy.allow_http_hosts.iter().any(|entry| {
+            host.eq_ignore_ascii_case(entry)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+        });
+        if !allowed {
+            return Err(PolicyViolation::new(format!(
+                "host '{}' is not in the policy's allow_http_hosts",
+                host
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Reduce an HTML page to its readable text with a few regex passes. Not
+/// a real parser, but good enough for documentation pages.
+fn html_to_text(html: &str) -> String {
+    let text = NON_TEXT_ELEMENTS.replace_all(html, "");
+    let text = COMMENTS.replace_all(&text, "");
+    let text = BLOCK_TAGS.replace_all(&text, "\n");
+    let text = TAGS.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    BLANK_LINES
+        .replace_all(lines.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A policy that lets tests reach their local server
+    fn local_policy() -> Policy {
+        Policy {
+            allow_private_http: true,
+            ..Policy::default()
+        }
+    }
+
+    /// Serve one HTTP response on a local port, returning the URL to fetch
+    async fn serve_once(content_type: &'static str, body: &'static str) -> String {
+        serve_raw(format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        ))
+        .await
+    }
+
+    /// Serve one raw HTTP response on a local port, returning the URL to fetch
+    async fn serve_raw(response: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}/docs", addr)
+    }
+
+    #[tokio::test]
+    async fn fetches_html_as_plain_text() {
+        let url = serve_once(
+            "text/html; charset=utf-8",
+            "<html><head><title>t</title><style>p{}</style></head>\
+            <body><h1>Docs</h1><p>Use <code>foo()</code> &amp; bar.</p></body></html>",
+        )
+        .await;
+
+        let output = HttpFetchTool {
+            policy: local_policy(),
+        }
+        .execute(json!({ "url": url }))
+        .await
+        .unwrap();
+
+        assert_eq!(
+            output,
+            format!("HTTP 200 {}\n\nDocs\n\nUse foo() & bar.", url)
+        );
+    }
+
+    #[tokio::test]
+    async fn hosts_outside_allow_list_are_rejected() {
+        let tool = HttpFetchTool {
+            policy: Policy {
+                allow_http_hosts: vec!["docs.rs".to_string()],
+                ..Policy::default()
+            },
+        };
+
+        let err = tool
+            .execute(json!({ "url": "https://example.com/" }))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "host 'example.com' is not in the policy's allow_http_hosts"
+        );
+        assert!(validate_url(&"https://api.docs.rs/x".parse().unwrap(), &tool.policy).is_ok());
+        assert!(validate_url(&"https://notdocs.rs/".parse().unwrap(), &tool.policy).is_err());
+    }
+
+    #[tokio::test]
+    async fn only_http_urls_are_fetched() {
+        let err = HttpFetchTool {
+            policy: Policy::default(),
+        }
+        .execute(json!({ "url": "file:///etc/passwd" }))
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("only http and https"));
+    }
+
+    #[tokio::test]
+    async fn loopback_targets_are_rejected() {
+        let url = serve_once("text/plain", "internal").await;
+        let tool = HttpFetchTool {
+            policy: Policy::default(),
+        };
+
+        let err = tool.execute(json!({ "url": url })).await.unwrap_err();
+        assert!(err.is::<PolicyViolation>());
+        assert_eq!(
+            err.to_string(),
+            "host '127.0.0.1' resolves to the non-public address 127.0.0.1"
+        );
+        assert!(is_private_address("169.254.169.254".parse().unwrap()));
+        assert!(is_private_address("10.0.0.1".parse().unwrap()));
+        assert!(is_private_address("::ffff:127.0.0.1".parse().unwrap()));
+        assert!(is_private_address("fe80::1".parse().unwrap()));
+        assert!(!is_private_address("93.184.216.34".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn redirects_are_checked_against_the_allow_list() {
+        let url = serve_raw(
+            "HTTP/1.1 302 Found\r\nLocation: http://evil.example/steal\r\n\
+            Content-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+        )
+        .await;
+        let tool = HttpFetchTool {
+            policy: Policy {
+                allow_http_hosts: vec!["127.0.0.1".to_string()],
+                ..local_policy()
+            },
+        };
+
+        let err = tool.execute(json!({ "url": url })).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "host 'evil.example' is not in the policy's allow_http_hosts"
+        );
+    }
+}
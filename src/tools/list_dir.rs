@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::path::Path;
+use tokio::task;
+
+use super::Tool;
+use super::validate_path;
+use crate::config::Policy;
+
+pub(super) const MAX_ENTRIES: usize = 200;
+/// Directories never descended into or listed: VCS internals are noise
+pub(super) const SKIPPED_DIRS: &[&str] = &[".git"];
+
+/// Tool for listing a directory's entries with their types and sizes
+pub struct ListDirectoryTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for ListDirectoryTool {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "List the entries of a directory with a type marker (d = directory, f = file, \
+        l = symlink) and size in bytes, optionally recursing into subdirectories"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The directory to list (default: current directory)"
+                },
+                "recursive": {
+                    "type": "boolean",
+                    "description": "Also list the contents of subdirectories (default: false)"
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "With recursive, how many levels deep to list (1 = only the directory itself)"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let path = params["path"].as_str().unwrap_or(".");
+        let recursive = params["recursive"].as_bool().unwrap_or(false);
+        let max_depth = if recursive {
+            params["max_depth"]
+                .as_u64()
+                .map_or(usize::MAX, |depth| depth.max(1) as usize)
+        } else {
+            1
+        };
+
+        let root = validate_path(path, &self.policy)?;
+        if !root.is_dir() {
+            anyhow::bail!("not a directory: {}", path);
+        }
+
+        let policy = self.policy.clone();
+        let (entries, truncated) =
+            task::spawn_blocking(move || list_entries(&root, max_depth, &policy))
+                .await
+                .context("list_dir task failed")??;
+
+        if entries.is_empty() {
+            return Ok(format!("{} is empty", path));
+        }
+
+        let mut output = format!("Contents of {} ({} entries):\n", path, entries.len());
+        output.push_str(&entries.join("\n"));
+        if truncated {
+            output.push_str(&format!("\n... (truncated at {} entries)", MAX_ENTRIES));
+        }
+        Ok(output)
+    }
+}
+
+/// Walk `root` depth-first in name order, returning formatted entry lines
+/// (paths relative to `root`) and whether the listing hit [`MAX_ENTRIES`]
+fn list_entries(root: &Path, max_depth: usize, policy: &Policy) -> Result<(Vec<String>, bool)> {
+    let mut listing = Listing {
+        root,
+        max_depth,
+        policy,
+        entries: Vec::new(),
+        truncated: false,
+    };
+    listing.walk(root, 1)?;
+    Ok((listing.entries, listing.truncated))
+}
+
+/// State of an in-progress directory walk
+struct Listing<'a> {
+    root: &'a Path,
+    max_depth: usize,
+    policy: &'a Policy,
+    entries: Vec<String>,
+    truncated: bool,
+}
+
+impl Listing<'_> {
+    fn walk(&mut self, dir: &Path, depth: usize) -> Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory: {}", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .collect();
+        children.sort_by_key(|entry| entry.file_name());
+
+        for child in children {
+            if self.entries.len() >= MAX_ENTRIES {
+                self.truncated = true;
+                return Ok(());
+            }
+
+            let path = child.path();
+            let name = child.file_name();
+            if SKIPPED_DIRS.iter().any(|skipped| name == *skipped) {
+                continue;
+            }
+            // Skip anything the policy denies rather than failing the listing
+            if validate_path(&path.to_string_lossy(), self.policy).is_err() {
+                continue;
+            }
+            // Don't follow symlinks, so links can't cause cycles
+            let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+                continue;
+            };
+
+            let relative = path.strip_prefix(self.root).unwrap_or(&path).display();
+            if metadata.is_dir() {
+                self.entries.push(format!("d {:>10} {}/", "-", relative));
+                if depth < self.max_depth {
+                    self.walk(&path, depth + 1)?;
+                }
+            } else if metadata.is_symlink() {
+                self.entries.push(format!("l {:>10} {}", "-", relative));
+            } else {
+                self.entries
+                    .push(format!("f {:>10} {}", metadata.len(), relative));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn tool() -> ListDirectoryTool {
+        ListDirectoryTool {
+            policy: Policy::default(),
+        }
+    }
+
+    /// The relative paths in a listing, in order
+    fn listed_paths(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().nth(2))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lists_flat_directory_with_types_and_sizes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("b.txt"), "hello").unwrap();
+        fs::write(dir.path().join("a.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("lib.rs"), "x").unwrap();
+
+        let output = tool()
+            .execute(json!({ "path": dir.path().to_str().unwrap() }))
+            .await
+            .unwrap();
+
+        assert!(output.contains("(3 entries)"));
+        assert!(output.contains("f          5 b.txt"));
+        assert!(output.contains("d          - src/"));
+        assert_eq!(listed_paths(&output), ["a.txt", "b.txt", "src/"]);
+    }
+
+    #[tokio::test]
+    async fn recursive_listing_respects_max_depth() {
+        let dir = tempdir().unwrap();
+        let deep = dir.path().join("a").join("b").join("c");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("deep.txt"), "x").unwrap();
+        fs::write(dir.path().join("a").join("top.txt"), "x").unwrap();
+
+        let output = tool()
+            .execute(json!({
+                "path": dir.path().to_str().unwrap(),
+                "recursive": true,
+                "max_depth": 2
+            }))
+            .await
+            .unwrap();
+        assert_eq!(listed_paths(&output), ["a/", "a/b/", "a/top.txt"]);
+
+        let unlimited = tool()
+            .execute(json!({ "path": dir.path().to_str().unwrap(), "recursive": true }))
+            .await
+            .unwrap();
+        assert!(listed_paths(&unlimited).contains(&"a/b/c/deep.txt"));
+    }
+
+    #[tokio::test]
+    async fn skips_git_directory() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git").join("HEAD"), "ref").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let output = tool()
+            .execute(json!({ "path": dir.path().to_str().unwrap(), "recursive": true }))
+            .await
+            .unwrap();
+
+        assert_eq!(listed_paths(&output), ["main.rs"]);
+    }
+}
@@ -1,13 +1,28 @@
+mod ask_user;
+mod diff;
+mod error;
 mod file;
+mod http;
+mod list_dir;
 mod registry;
 mod search;
 mod shell;
+mod tree;
 
+pub use ask_user::{AskUserTool, UserInputRequest};
+pub use diff::DiffTool;
+pub use error::TransientToolError;
+pub(crate) use error::is_transient;
 pub(crate) use file::validate_path;
-pub use file::{EditFileTool, ReadFileTool, WriteFileTool};
+pub use file::{
+    EditFileTool, FileStatTool, MoveFileTool, ReadFileTool, WatchFileTool, WriteFileTool,
+};
+pub use http::HttpFetchTool;
+pub use list_dir::ListDirectoryTool;
 pub use registry::ToolRegistry;
 pub use search::{GlobTool, GrepTool};
 pub use shell::ShellTool;
+pub use tree::TreeTool;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -27,4 +42,34 @@ pub trait Tool: Send + Sync {
 
     /// Execute the tool with the given parameters
     async fn execute(&self, params: Value) -> Result<String>;
+
+    /// Whether a failed call can safely be repeated with the same
+    /// parameters. Only such tools are retried after a failure; anything
+    /// that changes files or runs commands should keep the default.
+    fn is_idempotent(&self) -> bool {
+        false
+    }
+
+    /// Whether this call would change files. Read-only agents (the tester,
+    /// reviewer and security auditor) are refused such calls, so a custom
+    /// tool that writes files should say so.
+    fn modifies_files(&self, _params: &Value) -> bool {
+        false
+    }
+
+    /// Run a command and report its exit status apart from its output, so
+    /// callers can tell success from failure without parsing text. Only
+    /// tools that run commands (like `shell`) support this.
+    async fn run_command(&self, _params: Value) -> Result<CommandOutput> {
+        anyhow::bail!("the '{}' tool does not run commands", self.name())
+    }
+}
+
+/// What [`Tool::run_command`] reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Standard output, then standard error
+    pub output: String,
+    /// Exit code: 0 on success, -1 if the command was killed by a signal
+    pub status: i32,
 }
@@ -1,11 +1,99 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::warn;
 
-use super::Tool;
+use super::{
+    CommandOutput, DiffTool, EditFileTool, FileStatTool, GlobTool, GrepTool, HttpFetchTool,
+    ListDirectoryTool, MoveFileTool, ReadFileTool, ShellTool, Tool, TreeTool, WatchFileTool,
+    WriteFileTool,
+};
+use crate::config::Policy;
+
+type ToolFuture = Pin<Box<dyn Future<Output = Result<Box<dyn Tool>>> + Send>>;
+type ToolFactory = Box<dyn Fn() -> ToolFuture + Send + Sync>;
+
+/// A tool built by an async factory the first time it is needed
+struct LazyTool {
+    name: String,
+    factory: ToolFactory,
+    tool: OnceCell<Box<dyn Tool>>,
+}
+
+impl LazyTool {
+    /// Build the tool if it hasn't been yet. A failed build is not cached,
+    /// so the next use tries again.
+    async fn materialize(&self) -> Result<&dyn Tool> {
+        let tool = self
+            .tool
+            .get_or_try_init(|| async {
+                let tool = (self.factory)()
+                    .await
+                    .with_context(|| format!("failed to initialize tool '{}'", self.name))?;
+                if tool.name() != self.name {
+                    warn!(
+                        registered = %self.name,
+                        built = %tool.name(),
+                        "tool factory built a tool with a different name"
+                    );
+                }
+                Ok::<_, anyhow::Error>(tool)
+            })
+            .await?;
+        Ok(tool.as_ref())
+    }
+}
+
+#[async_trait]
+impl Tool for LazyTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Description and schema are only known once the tool is built; the
+    // agent loop initializes tools before advertising them to the model
+    fn description(&self) -> &str {
+        self.tool
+            .get()
+            .map_or("(not yet initialized)", |tool| tool.description())
+    }
+
+    fn schema(&self) -> Value {
+        self.tool
+            .get()
+            .map_or_else(|| json!({ "type": "object" }), |tool| tool.schema())
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        self.materialize().await?.execute(params).await
+    }
+
+    async fn run_command(&self, params: Value) -> Result<CommandOutput> {
+        self.materialize().await?.run_command(params).await
+    }
+
+    fn is_idempotent(&self) -> bool {
+        self.tool.get().is_some_and(|tool| tool.is_idempotent())
+    }
+
+    // Assume the worst until the tool exists to ask
+    fn modifies_files(&self, params: &Value) -> bool {
+        self.tool
+            .get()
+            .is_none_or(|tool| tool.modifies_files(params))
+    }
+}
 
 /// Registry for tools
 pub struct ToolRegistry {
     tools: HashMap<String, Arc<dyn Tool>>,
+    /// Tools from `register_async_factory`, also present in `tools`
+    lazy: HashMap<String, Arc<LazyTool>>,
 }
 
 impl ToolRegistry {
@@ -13,15 +101,112 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            lazy: HashMap::new(),
         }
     }
 
+    /// Create a registry with the built-in file, shell and search tools,
+    /// all governed by `policy`
+    pub fn with_default_tools(policy: &Policy) -> Self {
+        let mut registry = Self::new();
+        // File tools
+        registry.register(ReadFileTool {
+            policy: policy.clone(),
+        });
+        registry.register(FileStatTool {
+            policy: policy.clone(),
+        });
+        registry.register(WriteFileTool {
+            policy: policy.clone(),
+        });
+        registry.register(EditFileTool {
+            policy: policy.clone(),
+        });
+        registry.register(MoveFileTool {
+            policy: policy.clone(),
+        });
+        registry.register(WatchFileTool {
+            policy: policy.clone(),
+        });
+        registry.register(ListDirectoryTool {
+            policy: policy.clone(),
+        });
+        registry.register(TreeTool {
+            policy: policy.clone(),
+        });
+        registry.register(DiffTool {
+            policy: policy.clone(),
+        });
+        // Shell tool
+        registry.register(ShellTool {
+            policy: policy.clone(),
+        });
+        // Search tools
+        registry.register(GlobTool {
+            policy: policy.clone(),
+        });
+        registry.register(GrepTool {
+            policy: policy.clone(),
+        });
+        // Network tools
+        registry.register(HttpFetchTool {
+            policy: policy.clone(),
+        });
+        registry
+    }
+
     /// Register a tool
     pub fn register(&mut self, tool: impl Tool + 'static) {
         let name = tool.name().to_string();
+        self.lazy.remove(&name);
         self.tools.insert(name, Arc::new(tool));
     }
 
+    /// Register a tool that needs async setup (e.g. connecting to a
+    /// database). `factory` runs the first time the tool is needed, either
+    /// by [`ToolRegistry::initialize`] or by a call, and its tool is cached.
+    pub fn register_async_factory<F, Fut>(&mut self, name: &str, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Box<dyn Tool>>> + Send + 'static,
+    {
+        let tool = Arc::new(LazyTool {
+            name: name.to_string(),
+            factory: Box::new(move || Box::pin(factory())),
+            tool: OnceCell::new(),
+        });
+        self.tools.insert(name.to_string(), tool.clone());
+        self.lazy.insert(name.to_string(), tool);
+    }
+
+    /// Build any factory-registered tools among `names` (all of them if
+    /// `None`) that haven't been built yet, so their descriptions and
+    /// schemas are available
+    pub async fn initialize(&self, names: Option<&[&str]>) -> Result<()> {
+        for (name, tool) in &self.lazy {
+            if names.is_none_or(|names| names.contains(&name.as_str())) {
+                tool.materialize().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a tool by name, returning whether it was registered.
+    ///
+    /// Handy for trimming the default set, e.g. dropping `shell` for a
+    /// read-only deployment.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.lazy.remove(name);
+        self.tools.remove(name).is_some()
+    }
+
+    /// Keep only the tools whose name satisfies `keep`
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.tools.retain(|name, _| keep(name));
+        let tools = &self.tools;
+        self.lazy.retain(|name, _| tools.contains_key(name));
+    }
+
     /// Get a tool by name
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
         self.tools.get(name).cloned()
@@ -32,6 +217,13 @@ impl ToolRegistry {
         self.tools.values().map(|t| t.as_ref()).collect()
     }
 
+    /// Get tool names in sorted order
+    pub fn list_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tools.keys().map(|s| s.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
     /// Get tool names
     pub fn names(&self) -> Vec<&str> {
         self.tools.keys().map(|s| s.as_str()).collect()
@@ -47,9 +239,7 @@ impl Default for ToolRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anyhow::Result;
-    use async_trait::async_trait;
-    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     struct FakeTool {
         tool_name: &'static str,
@@ -103,6 +293,54 @@ mod tests {
         assert_eq!(names, vec!["bar", "foo"]);
     }
 
+    #[test]
+    fn list_names_is_sorted() {
+        let mut registry = ToolRegistry::new();
+        registry.register(FakeTool { tool_name: "foo" });
+        registry.register(FakeTool { tool_name: "bar" });
+        registry.register(FakeTool { tool_name: "baz" });
+
+        assert_eq!(registry.list_names(), vec!["bar", "baz", "foo"]);
+    }
+
+    #[test]
+    fn unregister_removes_tool() {
+        let mut registry = ToolRegistry::with_default_tools(&Policy::default());
+
+        assert!(registry.unregister("shell"));
+        assert!(!registry.unregister("shell"));
+        assert!(registry.get("shell").is_none());
+        assert!(!registry.list_names().contains(&"shell"));
+        assert!(registry.all().iter().all(|tool| tool.name() != "shell"));
+
+        registry.register(FakeTool { tool_name: "shell" });
+        assert!(registry.get("shell").is_some());
+    }
+
+    #[test]
+    fn default_tools_include_move_file() {
+        let registry = ToolRegistry::with_default_tools(&Policy::default());
+
+        assert_eq!(
+            registry.list_names(),
+            vec![
+                "diff",
+                "edit_file",
+                "file_stat",
+                "glob",
+                "grep",
+                "http_fetch",
+                "list_dir",
+                "move_file",
+                "read_file",
+                "shell",
+                "tree",
+                "watch_file",
+                "write_file"
+            ]
+        );
+    }
+
     #[test]
     fn duplicate_registration_overwrites() {
         let mut registry = ToolRegistry::new();
@@ -112,4 +350,61 @@ mod tests {
         // Should still have 1 entry
         assert_eq!(registry.names().len(), 1);
     }
+
+    #[tokio::test]
+    async fn async_factory_runs_once_on_first_use() {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let mut registry = ToolRegistry::new();
+        let counter = builds.clone();
+        registry.register_async_factory("db", move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(Box::new(FakeTool { tool_name: "db" }) as Box<dyn Tool>)
+            }
+        });
+
+        assert_eq!(registry.list_names(), vec!["db"]);
+        assert_eq!(builds.load(Ordering::SeqCst), 0);
+
+        let tool = registry.get("db").unwrap();
+        assert_eq!(tool.execute(Value::Null).await.unwrap(), "ok");
+        assert_eq!(tool.execute(Value::Null).await.unwrap(), "ok");
+        registry.initialize(None).await.unwrap();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert_eq!(tool.description(), "fake");
+    }
+
+    #[tokio::test]
+    async fn failed_async_factory_is_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut registry = ToolRegistry::new();
+        let counter = attempts.clone();
+        registry.register_async_factory("db", move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    anyhow::bail!("migration failed");
+                }
+                Ok(Box::new(FakeTool { tool_name: "db" }) as Box<dyn Tool>)
+            }
+        });
+
+        let err = registry.initialize(Some(&["db"])).await.unwrap_err();
+        assert_eq!(err.to_string(), "failed to initialize tool 'db'");
+        registry.initialize(Some(&["db"])).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn initialize_skips_unlisted_factories() {
+        let mut registry = ToolRegistry::new();
+        registry
+            .register_async_factory("broken", || async { anyhow::bail!("should not be built") });
+
+        registry.initialize(Some(&["read_file"])).await.unwrap();
+        assert!(registry.unregister("broken"));
+        registry.initialize(None).await.unwrap();
+    }
 }
@@ -4,6 +4,8 @@ use glob::glob;
 use regex::Regex;
 use serde_json::{Value, json};
 use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::task;
 
 use super::Tool;
 use super::validate_path;
@@ -11,6 +13,11 @@ use crate::config::Policy;
 
 const MAX_RESULTS: usize = 100;
 const MAX_CONTENT_PREVIEW: usize = 200;
+const MAX_CONTEXT_LINES: usize = 10;
+const DEFAULT_SEARCH_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_SEARCH_MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Appended to results when a search stops at its deadline
+const SEARCH_TIMED_OUT: &str = "\n[search timed out]";
 
 /// Find the largest byte index <= `index` that is a valid char boundary.
 fn floor_char_boundary(s: &str, index: usize) -> usize {
@@ -35,6 +42,10 @@ impl Tool for GlobTool {
         "glob"
     }
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Find files matching a glob pattern (e.g., '**/*.rs', 'src/**/*.txt')"
     }
@@ -75,32 +86,46 @@ impl Tool for GlobTool {
             pattern.to_string()
         };
 
-        // Execute glob
+        // Execute glob off the async runtime; a huge tree can take a while
         let entries = glob(&full_pattern)
             .with_context(|| format!("invalid glob pattern: {}", full_pattern))?;
-
-        let mut matches = Vec::new();
-        for entry in entries {
-            match entry {
-                Ok(path) => {
-                    // Filter results through path validation
-                    let path_str = path.display().to_string();
-                    if validate_path(&path_str, &self.policy).is_ok() {
-                        matches.push(path_str);
-                        if matches.len() >= MAX_RESULTS {
-                            break;
+        let policy = self.policy.clone();
+        let deadline = search_deadline(&self.policy);
+
+        let (matches, timed_out) = task::spawn_blocking(move || {
+            let mut matches = Vec::new();
+            let mut timed_out = false;
+            for entry in entries {
+                match entry {
+                    Ok(path) => {
+                        // Filter results through path validation
+                        let path_str = path.display().to_string();
+                        if validate_path(&path_str, &policy).is_ok() {
+                            matches.push(path_str);
+                            if matches.len() >= MAX_RESULTS {
+                                break;
+                            }
                         }
                     }
+                    Err(e) => {
+                        // Skip entries we can't read
+                        tracing::debug!("glob entry error: {}", e);
+                    }
                 }
-                Err(e) => {
-                    // Skip entries we can't read
-                    tracing::debug!("glob entry error: {}", e);
+                if Instant::now() >= deadline {
+                    timed_out = true;
+                    break;
                 }
             }
-        }
+            (matches, timed_out)
+        })
+        .await
+        .context("glob task failed")?;
+
+        let timeout_note = if timed_out { SEARCH_TIMED_OUT } else { "" };
 
         if matches.is_empty() {
-            Ok("No files found matching pattern".to_string())
+            Ok(format!("No files found matching pattern{}", timeout_note))
         } else {
             let truncated = if matches.len() >= MAX_RESULTS {
                 format!("\n... (truncated at {} results)", MAX_RESULTS)
@@ -108,10 +133,11 @@ impl Tool for GlobTool {
                 String::new()
             };
             Ok(format!(
-                "Found {} files:\n{}{}",
+                "Found {} files:\n{}{}{}",
                 matches.len(),
                 matches.join("\n"),
-                truncated
+                truncated,
+                timeout_note
             ))
         }
     }
@@ -128,8 +154,13 @@ impl Tool for GrepTool {
         "grep"
     }
 
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
-        "Search for a regex pattern in files. Returns matching lines with file paths and line numbers."
+        "Search for a regex pattern in files. Returns matching lines with file paths and line numbers; \
+        pass context_before/context_after to include surrounding lines, like grep -B/-A."
     }
 
     fn schema(&self) -> Value {
@@ -151,6 +182,14 @@ impl Tool for GrepTool {
                 "case_insensitive": {
                     "type": "boolean",
                     "description": "Whether to ignore case (default: false)"
+                },
+                "context_before": {
+                    "type": "integer",
+                    "description": "Lines to show before each match (default: 0, max 10)"
+                },
+                "context_after": {
+                    "type": "integer",
+                    "description": "Lines to show after each match (default: 0, max 10)"
                 }
             },
             "required": ["pattern", "path"]
@@ -168,6 +207,10 @@ impl Tool for GrepTool {
 
         let file_pattern = params["file_pattern"].as_str();
         let case_insensitive = params["case_insensitive"].as_bool().unwrap_or(false);
+        let context = ContextLines {
+            before: context_lines(&params, "context_before")?,
+            after: context_lines(&params, "context_after")?,
+        };
 
         // Validate the search path
         validate_path(path, &self.policy)?;
@@ -180,58 +223,203 @@ impl Tool for GrepTool {
         }
         .with_context(|| format!("invalid regex pattern: {}", pattern))?;
 
-        let path = Path::new(path);
-        let mut results = Vec::new();
+        let path = Path::new(path).to_path_buf();
+        let file_pattern = file_pattern.map(str::to_string);
+        let policy = self.policy.clone();
+        let limits = SearchLimits {
+            deadline: search_deadline(&self.policy),
+            max_file_bytes: self
+                .policy
+                .search_max_file_bytes
+                .unwrap_or(DEFAULT_SEARCH_MAX_FILE_BYTES),
+        };
 
-        if path.is_file() {
-            search_file(path, &regex, &mut results)?;
-        } else if path.is_dir() {
-            search_directory(path, &regex, file_pattern, &self.policy, &mut results)?;
-        } else {
-            anyhow::bail!("path does not exist: {}", path.display());
+        // Search off the async runtime, stopping at the deadline
+        let outcome = task::spawn_blocking(move || {
+            let mut outcome = GrepOutcome::default();
+            if path.is_file() {
+                search_file(&path, &regex, &limits, context, &mut outcome);
+            } else if path.is_dir() {
+                search_directory(
+                    &path,
+                    &regex,
+                    file_pattern.as_deref(),
+                    &policy,
+                    &limits,
+                    context,
+                    &mut outcome,
+                )?;
+            } else {
+                anyhow::bail!("path does not exist: {}", path.display());
+            }
+            Ok(outcome)
+        })
+        .await
+        .context("grep task failed")??;
+
+        let mut notes = String::new();
+        if outcome.skipped_large > 0 {
+            notes.push_str(&format!(
+                "\n(skipped {} files larger than {} bytes)",
+                outcome.skipped_large, limits.max_file_bytes
+            ));
+        }
+        if outcome.timed_out {
+            notes.push_str(SEARCH_TIMED_OUT);
         }
 
-        if results.is_empty() {
-            Ok("No matches found".to_string())
+        if outcome.matches == 0 {
+            Ok(format!("No matches found{}", notes))
         } else {
-            let truncated = if results.len() >= MAX_RESULTS {
+            let truncated = if outcome.matches >= MAX_RESULTS {
                 format!("\n... (truncated at {} results)", MAX_RESULTS)
             } else {
                 String::new()
             };
+            // Separate groups of context lines the way grep does
+            let separator = if context.is_empty() { "\n" } else { "\n--\n" };
             Ok(format!(
-                "Found {} matches:\n{}{}",
-                results.len(),
-                results.join("\n"),
-                truncated
+                "Found {} matches:\n{}{}{}",
+                outcome.matches,
+                outcome.results.join(separator),
+                truncated,
+                notes
             ))
         }
     }
 }
 
-fn search_file(path: &Path, regex: &Regex, results: &mut Vec<String>) -> Result<()> {
+/// Bounds on a single grep run
+#[derive(Clone, Copy)]
+struct SearchLimits {
+    deadline: Instant,
+    max_file_bytes: u64,
+}
+
+/// Lines to show around each match
+#[derive(Clone, Copy)]
+struct ContextLines {
+    before: usize,
+    after: usize,
+}
+
+impl ContextLines {
+    fn is_empty(&self) -> bool {
+        self.before == 0 && self.after == 0
+    }
+}
+
+/// What a grep run found before finishing or hitting its limits
+#[derive(Default)]
+struct GrepOutcome {
+    /// One entry per match, or per group of overlapping context when
+    /// context lines were requested
+    results: Vec<String>,
+    matches: usize,
+    timed_out: bool,
+    skipped_large: usize,
+}
+
+fn context_lines(params: &Value, name: &str) -> Result<usize> {
+    match &params[name] {
+        Value::Null => Ok(0),
+        value => value
+            .as_u64()
+            .map(|n| (n as usize).min(MAX_CONTEXT_LINES))
+            .with_context(|| format!("'{}' must be a non-negative integer", name)),
+    }
+}
+
+/// A line as shown in results, cut short if it's very long
+fn preview(line: &str) -> String {
+    if line.len() > MAX_CONTENT_PREVIEW {
+        let boundary = floor_char_boundary(line, MAX_CONTENT_PREVIEW);
+        format!("{}...", &line[..boundary])
+    } else {
+        line.to_string()
+    }
+}
+
+fn search_deadline(policy: &Policy) -> Instant {
+    let secs = policy
+        .search_timeout_secs
+        .unwrap_or(DEFAULT_SEARCH_TIMEOUT_SECS);
+    Instant::now() + Duration::from_secs(secs)
+}
+
+fn search_file(
+    path: &Path,
+    regex: &Regex,
+    limits: &SearchLimits,
+    context: ContextLines,
+    outcome: &mut GrepOutcome,
+) {
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.len() > limits.max_file_bytes {
+            outcome.skipped_large += 1;
+            return;
+        }
+    }
+
     let content = match std::fs::read_to_string(path) {
         Ok(c) => c,
-        Err(_) => return Ok(()), // Skip files we can't read
+        Err(_) => return, // Skip files we can't read
     };
 
-    for (line_num, line) in content.lines().enumerate() {
-        if results.len() >= MAX_RESULTS {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut matched = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if outcome.matches + matched.len() >= MAX_RESULTS {
             break;
         }
-
         if regex.is_match(line) {
-            let preview = if line.len() > MAX_CONTENT_PREVIEW {
-                let boundary = floor_char_boundary(line, MAX_CONTENT_PREVIEW);
-                format!("{}...", &line[..boundary])
-            } else {
-                line.to_string()
-            };
-            results.push(format!("{}:{}: {}", path.display(), line_num + 1, preview));
+            matched.push(index);
+        }
+    }
+    outcome.matches += matched.len();
+
+    if context.is_empty() {
+        for index in matched {
+            outcome.results.push(format!(
+                "{}:{}: {}",
+                path.display(),
+                index + 1,
+                preview(lines[index])
+            ));
         }
+        return;
     }
 
-    Ok(())
+    // Merge overlapping or adjacent context ranges into one group, as grep
+    // does; matches are marked `=` and context lines `>`
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &index in &matched {
+        let start = index.saturating_sub(context.before);
+        let end = (index + context.after).min(lines.len() - 1);
+        match groups.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => groups.push((start, end)),
+        }
+    }
+    for (start, end) in groups {
+        let group: Vec<String> = (start..=end)
+            .map(|index| {
+                let marker = if matched.binary_search(&index).is_ok() {
+                    '='
+                } else {
+                    '>'
+                };
+                format!(
+                    "{}:{}{} {}",
+                    path.display(),
+                    index + 1,
+                    marker,
+                    preview(lines[index])
+                )
+            })
+            .collect();
+        outcome.results.push(group.join("\n"));
+    }
 }
 
 fn search_directory(
@@ -239,7 +427,9 @@ fn search_directory(
     regex: &Regex,
     file_pattern: Option<&str>,
     policy: &Policy,
-    results: &mut Vec<String>,
+    limits: &SearchLimits,
+    context: ContextLines,
+    outcome: &mut GrepOutcome,
 ) -> Result<()> {
     let glob_pattern = if let Some(fp) = file_pattern {
         format!("{}/**/{}", dir.display(), fp)
@@ -250,7 +440,7 @@ fn search_directory(
     let entries = glob(&glob_pattern).with_context(|| "failed to create glob pattern")?;
 
     for entry in entries {
-        if results.len() >= MAX_RESULTS {
+        if outcome.matches >= MAX_RESULTS {
             break;
         }
 
@@ -259,10 +449,16 @@ fn search_directory(
                 // Skip files that fail path validation
                 let path_str = path.display().to_string();
                 if validate_path(&path_str, policy).is_ok() {
-                    search_file(&path, regex, results)?;
+                    search_file(&path, regex, limits, context, outcome);
                 }
             }
         }
+
+        // Checked after each entry so some progress is always made
+        if Instant::now() >= limits.deadline {
+            outcome.timed_out = true;
+            break;
+        }
     }
 
     Ok(())
@@ -315,4 +511,84 @@ mod tests {
         assert!(result.contains("hello world"));
         assert!(result.contains("hello again"));
     }
+
+    #[tokio::test]
+    async fn grep_returns_partial_results_when_timed_out() {
+        let dir = tempdir().unwrap();
+        for i in 0..50 {
+            fs::write(dir.path().join(format!("file{i:02}.txt")), "needle\n").unwrap();
+        }
+
+        // A zero timeout stops after the first file
+        let tool = GrepTool {
+            policy: Policy {
+                search_timeout_secs: Some(0),
+                ..Policy::default()
+            },
+        };
+        let params = json!({
+            "pattern": "needle",
+            "path": dir.path().to_str().unwrap()
+        });
+
+        let result = tool.execute(params).await.unwrap();
+        assert!(result.contains("Found 1 matches"));
+        assert!(result.ends_with("[search timed out]"));
+    }
+
+    #[tokio::test]
+    async fn grep_skips_files_over_size_cap() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("small.txt"), "needle").unwrap();
+        fs::write(dir.path().join("big.txt"), "needle".repeat(100)).unwrap();
+
+        let tool = GrepTool {
+            policy: Policy {
+                search_max_file_bytes: Some(64),
+                ..Policy::default()
+            },
+        };
+        let params = json!({
+            "pattern": "needle",
+            "path": dir.path().to_str().unwrap()
+        });
+
+        let result = tool.execute(params).await.unwrap();
+        assert!(result.contains("Found 1 matches"));
+        assert!(result.contains("small.txt"));
+        assert!(result.contains("(skipped 1 files larger than 64 bytes)"));
+    }
+
+    #[tokio::test]
+    async fn grep_includes_context_lines() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(
+            &file,
+            "a\nb\nerror one\nc\nd\ne\nf\nerror two\ng\nerror three\n",
+        )
+        .unwrap();
+        let path = file.to_str().unwrap();
+
+        let tool = GrepTool {
+            policy: Policy::default(),
+        };
+        let params = json!({
+            "pattern": "error",
+            "path": path,
+            "context_before": 1,
+            "context_after": 1
+        });
+
+        let result = tool.execute(params).await.unwrap();
+        // The last two matches' context overlaps, so they share a group
+        assert_eq!(
+            result,
+            format!(
+                "Found 3 matches:\n\
+                {path}:2> b\n{path}:3= error one\n{path}:4> c\n--\n\
+                {path}:7> f\n{path}:8= error two\n{path}:9> g\n{path}:10= error three"
+            )
+        );
+    }
 }
@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::path::Path;
+use tokio::task;
+
+use super::Tool;
+use super::list_dir::{MAX_ENTRIES, SKIPPED_DIRS};
+use super::validate_path;
+use crate::config::Policy;
+
+/// Tool for showing a directory's structure as an indented tree, in the
+/// format of `tree -L <depth>`
+pub struct TreeTool {
+    pub policy: Policy,
+}
+
+#[async_trait]
+impl Tool for TreeTool {
+    fn name(&self) -> &str {
+        "tree"
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Show a directory's structure as an indented tree, like `tree -L <depth>`. Easier to \
+        read than a flat file list when getting to know a project."
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The directory to show (default: current directory)"
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": "How many levels deep to descend (default: unlimited; 1 = only the directory's own entries)"
+                },
+                "show_hidden": {
+                    "type": "boolean",
+                    "description": "Include entries whose names start with '.' (default: false)"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, params: Value) -> Result<String> {
+        let path = params["path"].as_str().unwrap_or(".");
+        let max_depth = params["max_depth"]
+            .as_u64()
+            .map_or(usize::MAX, |depth| depth.max(1) as usize);
+        let show_hidden = params["show_hidden"].as_bool().unwrap_or(false);
+
+        let root = validate_path(path, &self.policy)?;
+        if !root.is_dir() {
+            anyhow::bail!("not a directory: {}", path);
+        }
+
+        let policy = self.policy.clone();
+        let tree = task::spawn_blocking(move || {
+            let mut tree = Tree {
+                max_depth,
+                show_hidden,
+                policy: &policy,
+                lines: Vec::new(),
+                directories: 0,
+                files: 0,
+                truncated: false,
+            };
+            tree.walk(&root, "", 1)?;
+            Ok::<_, anyhow::Error>((tree.lines, tree.directories, tree.files, tree.truncated))
+        })
+        .await
+        .context("tree task failed")??;
+        let (lines, directories, files, truncated) = tree;
+
+        let mut output = path.to_string();
+        for line in lines {
+            output.push('\n');
+            output.push_str(&line);
+        }
+        if truncated {
+            output.push_str(&format!("\n... (truncated at {} entries)", MAX_ENTRIES));
+        }
+        output.push_str(&format!(
+            "\n\n{} {}, {} {}",
+            directories,
+            if directories == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            files,
+            if files == 1 { "file" } else { "files" }
+        ));
+        Ok(output)
+    }
+}
+
+/// State of an in-progress tree walk
+struct Tree<'a> {
+    max_depth: usize,
+    show_hidden: bool,
+    policy: &'a Policy,
+    lines: Vec<String>,
+    directories: usize,
+    files: usize,
+    truncated: bool,
+}
+
+impl Tree<'_> {
+    /// Add `dir`'s entries in name order, each line drawn under `prefix`
+    fn walk(&mut self, dir: &Path, prefix: &str, depth: usize) -> Result<()> {
+        let mut children: Vec<_> = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory: {}", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                !SKIPPED_DIRS.contains(&name.as_ref())
+                    && (self.show_hidden || !name.starts_with('.'))
+                    // Skip anything the policy denies rather than failing the tree
+                    && validate_path(&entry.path().to_string_lossy(), self.policy).is_ok()
+            })
+            .collect();
+        children.sort_by_key(|entry| entry.file_name());
+
+        let count = children.len();
+        for (i, child) in children.into_iter().enumerate() {
+            if self.lines.len() >= MAX_ENTRIES {
+                self.truncated = true;
+                return Ok(());
+            }
+
+            let last = i + 1 == count;
+            let branch = if last { "└── " } else { "├── " };
+            let path = child.path();
+            let name = child.file_name().to_string_lossy().into_owned();
+            // Don't follow symlinks, so links can't cause cycles
+            let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+                continue;
+            };
+
+            if metadata.is_symlink() {
+                let target = std::fs::read_link(&path)
+                    .map(|target| target.display().to_string())
+                    .unwrap_or_default();
+                self.lines
+                    .push(format!("{}{}{} -> {}", prefix, branch, name, target));
+                self.files += 1;
+            } else if metadata.is_dir() {
+                self.lines.push(format!("{}{}{}", prefix, branch, name));
+                self.directories += 1;
+                if depth < self.max_depth {
+                    let indent = if last { "    " } else { "│   " };
+                    self.walk(&path, &format!("{}{}", prefix, indent), depth + 1)?;
+                }
+            } else {
+                self.lines.push(format!("{}{}{}", prefix, branch, name));
+                self.files += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn tool() -> TreeTool {
+        TreeTool {
+            policy: Policy::default(),
+        }
+    }
+
+    fn sample_project() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        fs::write(dir.path().join(".editorconfig"), "").unwrap();
+        fs::create_dir_all(dir.path().join("src/tools")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("src/tools/mod.rs"), "").unwrap();
+        fs::create_dir(dir.path().join("tests")).unwrap();
+        fs::write(dir.path().join("tests/cli.rs"), "").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn draws_tree_like_tree_command() {
+        let dir = sample_project();
+        let path = dir.path().to_str().unwrap();
+
+        let output = tool().execute(json!({ "path": path })).await.unwrap();
+
+        assert_eq!(
+            output,
+            format!(
+                "{path}\n\
+                ├── Cargo.toml\n\
+                ├── src\n\
+                │   ├── lib.rs\n\
+                │   └── tools\n\
+                │       └── mod.rs\n\
+                └── tests\n    \
+                    └── cli.rs\n\
+                \n\
+                3 directories, 4 files"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn max_depth_and_show_hidden() {
+        let dir = sample_project();
+        let path = dir.path().to_str().unwrap();
+
+        let output = tool()
+            .execute(json!({ "path": path, "max_depth": 1, "show_hidden": true }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            output,
+            format!(
+                "{path}\n\
+                ├── .editorconfig\n\
+                ├── Cargo.toml\n\
+                ├── src\n\
+                └── tests\n\
+                \n\
+                2 directories, 2 files"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_denied_paths() {
+        let dir = sample_project();
+        let canonical = fs::canonicalize(dir.path()).unwrap();
+        let tool = TreeTool {
+            policy: Policy {
+                deny_paths: vec![canonical.join("src").to_string_lossy().to_string()],
+                ..Policy::default()
+            },
+        };
+
+        let output = tool
+            .execute(json!({ "path": canonical.to_str().unwrap() }))
+            .await
+            .unwrap();
+
+        assert!(!output.contains("src"), "{output}");
+        assert!(output.ends_with("1 directory, 2 files"), "{output}");
+    }
+}